@@ -1,16 +1,35 @@
 use csv::StringRecord;
 use rfd::FileDialog;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command, Stdio};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Emitter, State};
 
 #[derive(Default)]
 struct ProcessState {
-    child: Arc<Mutex<Option<Child>>>,
+    children: Arc<Mutex<Vec<Child>>>,
     stop_requested: Arc<AtomicBool>,
+    is_running: Arc<AtomicBool>,
+}
+
+struct RunningGuard(Arc<AtomicBool>);
+
+impl RunningGuard {
+    fn acquire(flag: Arc<AtomicBool>) -> Result<Self, String> {
+        flag.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .map_err(|_| "An export is already in progress".to_string())?;
+        Ok(Self(flag))
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -18,6 +37,7 @@ struct ClipRow {
     clip_name: String,
     start_time: String,
     end_time: String,
+    overrides: RowOverrides,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -25,17 +45,245 @@ struct ClipRowInput {
     clip_name: String,
     start_time: String,
     end_time: String,
+    #[serde(default)]
+    overrides: RowOverrides,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RowOverrides {
+    mode: Option<String>,
+    crf: Option<String>,
+    preset: Option<String>,
+    resolution: Option<String>,
+    source: Option<String>,
+    crop_x: Option<String>,
+    crop_y: Option<String>,
+    crop_w: Option<String>,
+    crop_h: Option<String>,
+    audio_offset_ms: Option<String>,
+    featured: Option<String>,
+    lufs: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CropRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+const OUTPUT_DIR_ENV: &str = "CLIPCHOP_OUTPUT_DIR";
+const FFMPEG_ENV: &str = "CLIPCHOP_FFMPEG";
+const FFPROBE_ENV: &str = "CLIPCHOP_FFPROBE";
+const MAX_AUDIO_OFFSET_MS: i32 = 10_000;
+const PROCESSING_MODES: &[&str] = &[
+    "reencode_precise",
+    "copy_fast",
+    "reencode_fast_seek",
+    "webp",
+];
+const X264_PRESETS: &[&str] = &[
+    "ultrafast",
+    "superfast",
+    "veryfast",
+    "faster",
+    "fast",
+    "medium",
+];
+const CRF_RANGE: std::ops::RangeInclusive<u8> = 16..=35;
+const LUFS_RANGE: std::ops::RangeInclusive<f64> = -70.0..=-5.0;
+const SOCIAL_RESOLUTION: &str = "1080x1920";
+const SOCIAL_LOUDNESS_LUFS: f64 = -14.0;
+const NORMALIZE_LOUDNESS_LUFS: f64 = -14.0;
+const MAX_STRIP_FRAMES: u32 = 50;
+const STRIP_TILE_WIDTH: u32 = 160;
+const MANIFEST_FILE: &str = "clipchop-manifest.json";
+const RESOLVE_METADATA_FILE: &str = "resolve_metadata.csv";
+const PID_FILE: &str = "clipchop-ffmpeg.pids";
+
+// Serializes pidfile rewrites across worker threads.
+static PID_FILE_LOCK: Mutex<()> = Mutex::new(());
+const CONCAT_OUTPUT_STEM: &str = "output";
+const QC_MIN_SIZE_BYTES: u64 = 10 * 1024;
+const SILENCE_THRESHOLD_DB: std::ops::RangeInclusive<f64> = -90.0..=-20.0;
+const MAX_EDGE_TRIM_SEC: f64 = 10.0;
+const MAX_PARALLEL: usize = 16;
+const MAX_LOCKED_RETRIES: u32 = 10;
+const LOCKED_RETRY_DELAY_MS: u64 = 2000;
+const POOL_POLL_MS: u64 = 20;
+const STDERR_TAIL_LINES: usize = 20;
+const MAX_TIME_PRECISION: usize = 6;
+const PROXY_DIR: &str = "proxies";
+const PROXY_HEIGHT: u32 = 540;
+const PROXY_CRF: u8 = 28;
+const DEFAULT_TIMECODE_FPS: f64 = 30.0;
+const VIDEO_ENCODERS: &[&str] = &[
+    "libx264",
+    "h264_nvenc",
+    "h264_videotoolbox",
+    "h264_qsv",
+    "hevc_nvenc",
+];
+const UNIQUE_SUFFIX_LEN: std::ops::RangeInclusive<usize> = 4..=16;
+const MEDIA_EXTENSIONS: &[&str] = &["mp4", "mov", "mkv", "m4v", "avi", "webm", "webp", "ts"];
+
+const CLIP_NAME_ALIASES: &[&str] = &["clip name", "name", "clip"];
+const START_TIME_ALIASES: &[&str] = &["clip start time", "start time", "start", "in"];
+const END_TIME_ALIASES: &[&str] = &["clip end time", "end time", "end", "out"];
+const DURATION_ALIASES: &[&str] = &["duration", "length", "len", "clip duration", "clip length"];
+const MODE_ALIASES: &[&str] = &["mode", "processing mode", "export mode"];
+const CRF_ALIASES: &[&str] = &["crf"];
+const PRESET_ALIASES: &[&str] = &["preset", "speed preset", "x264 preset"];
+const RESOLUTION_ALIASES: &[&str] = &["resolution", "res"];
+const SOURCE_ALIASES: &[&str] = &["source", "source video", "source file"];
+const CROP_X_ALIASES: &[&str] = &["crop x"];
+const CROP_Y_ALIASES: &[&str] = &["crop y"];
+const CROP_W_ALIASES: &[&str] = &["crop w", "crop width"];
+const CROP_H_ALIASES: &[&str] = &["crop h", "crop height"];
+const AUDIO_OFFSET_ALIASES: &[&str] = &["audio offset ms", "audio offset", "av offset ms"];
+const FEATURED_ALIASES: &[&str] = &["featured", "is featured"];
+const LUFS_ALIASES: &[&str] = &["lufs", "loudness", "loudness target"];
+const TITLE_ALIASES: &[&str] = &["title", "chapter title"];
+const DESCRIPTION_ALIASES: &[&str] = &["description", "desc"];
+
+struct CsvColumns {
+    name: usize,
+    start: usize,
+    end: Option<usize>,
+    duration: Option<usize>,
+    mode: Option<usize>,
+    crf: Option<usize>,
+    preset: Option<usize>,
+    resolution: Option<usize>,
+    source: Option<usize>,
+    crop_x: Option<usize>,
+    crop_y: Option<usize>,
+    crop_w: Option<usize>,
+    crop_h: Option<usize>,
+    audio_offset_ms: Option<usize>,
+    featured: Option<usize>,
+    lufs: Option<usize>,
+    title: Option<usize>,
+    description: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 struct ExportSettings {
     processing_mode: String,
     preset: String,
     crf: u8,
     resolution: String,
+    social_preset: Option<String>,
+    crop_w: Option<u32>,
+    crop_h: Option<u32>,
+    crop_x: Option<u32>,
+    crop_y: Option<u32>,
     audio_codec: String,
     audio_bitrate_kbps: u16,
     fps: Option<f64>,
+    webp_quality: u8,
+    preview_fps: f64,
+    preview_width: u32,
+    source_timecode_offset: Option<String>,
+    probe_start_timecode: bool,
+    output_mode: Option<String>,
+    merge_gap_sec: Option<f64>,
+    exact_frames: bool,
+    fragmented_mp4: bool,
+    accurate_seek: Option<bool>,
+    watermark_image: Option<String>,
+    watermark_position: String,
+    watermark_opacity: f64,
+    watermark_scale: f64,
+    csv_encoding: String,
+    audio_offset_ms: i32,
+    export_bookends: bool,
+    bookend_width: u32,
+    generate_thumbnail: bool,
+    thumbnail_position: String,
+    thumbnail_offset_sec: f64,
+    thumbnail_width: u32,
+    rename_on_collision: bool,
+    collision_suffix: String,
+    on_conflict: String,
+    autoswap_times: bool,
+    force_keyframe_at_start: bool,
+    fixed_duration: Option<f64>,
+    end_hold_sec: f64,
+    fade_in_sec: f64,
+    fade_out_sec: f64,
+    fade_audio: bool,
+    chapters_per_source: bool,
+    loudness_target: Option<f64>,
+    normalize_audio: bool,
+    loudnorm_two_pass: bool,
+    delete_source_on_success: bool,
+    confirm_delete_source: bool,
+    denoise: String,
+    sharpen: bool,
+    parallel_scope: String,
+    color_range: String,
+    start_index: Option<usize>,
+    continue_numbering: bool,
+    resolve_export: bool,
+    copy_container_fallback: bool,
+    max_total_duration: Option<f64>,
+    unique_suffix: Option<usize>,
+    filename_template: Option<String>,
+    trim_edge_silence: bool,
+    silence_threshold_db: f64,
+    silence_max_trim_sec: f64,
+    rules_file: Option<String>,
+    progress_log_file: Option<String>,
+    max_parallel: usize,
+    downmix: String,
+    trim_black: bool,
+    black_threshold: f64,
+    black_max_trim_sec: f64,
+    assumed_fps: Option<f64>,
+    fallback_naming: String,
+    intro_file: Option<String>,
+    outro_file: Option<String>,
+    clamp_to_source: bool,
+    muxer_format: Option<String>,
+    output_extension: Option<String>,
+    locked_retries: u32,
+    encoder: String,
+    output_format: Option<String>,
+    preserve_mtime: bool,
+    concat: bool,
+    fallback_reencode: bool,
+    snap_to_keyframe: bool,
+    accurate_copy: bool,
+    burn_subtitle_track: Option<usize>,
+    burn_label: bool,
+    label_position: String,
+    label_font_size: u32,
+    label_font_file: Option<String>,
+    replaygain_tag: bool,
+    report_path: Option<String>,
+    verbose: bool,
+    time_precision: usize,
+    strict_validation: bool,
+    ffmpeg_path: Option<String>,
+    ffprobe_path: Option<String>,
+    source_dir: Option<String>,
+    fast_segment_split: bool,
+}
+
+impl ExportSettings {
+    /// Frame rate used for the FF field of HH:MM:SS:FF timestamps: the chosen
+    /// output FPS, else the source's probed rate, else `assumed_fps`, else 30.
+    fn timecode_fps(&self, source_fps: Option<f64>) -> f64 {
+        self.fps
+            .or(source_fps)
+            .or(self.assumed_fps)
+            .unwrap_or(DEFAULT_TIMECODE_FPS)
+    }
 }
 
 impl Default for ExportSettings {
@@ -45,13 +293,155 @@ impl Default for ExportSettings {
             preset: "ultrafast".to_string(),
             crf: 20,
             resolution: "source".to_string(),
+            social_preset: None,
+            crop_w: None,
+            crop_h: None,
+            crop_x: None,
+            crop_y: None,
             audio_codec: "aac".to_string(),
             audio_bitrate_kbps: 128,
             fps: None,
+            webp_quality: 75,
+            preview_fps: 12.0,
+            preview_width: 480,
+            source_timecode_offset: None,
+            probe_start_timecode: false,
+            output_mode: None,
+            merge_gap_sec: None,
+            exact_frames: false,
+            fragmented_mp4: false,
+            accurate_seek: None,
+            watermark_image: None,
+            watermark_position: "bottom_right".to_string(),
+            watermark_opacity: 0.5,
+            watermark_scale: 0.15,
+            csv_encoding: "auto".to_string(),
+            audio_offset_ms: 0,
+            export_bookends: false,
+            bookend_width: 320,
+            generate_thumbnail: false,
+            thumbnail_position: "midpoint".to_string(),
+            thumbnail_offset_sec: 0.0,
+            thumbnail_width: 640,
+            rename_on_collision: false,
+            collision_suffix: "-{n}".to_string(),
+            on_conflict: "overwrite".to_string(),
+            autoswap_times: false,
+            force_keyframe_at_start: false,
+            fixed_duration: None,
+            end_hold_sec: 0.0,
+            fade_in_sec: 0.0,
+            fade_out_sec: 0.0,
+            fade_audio: true,
+            chapters_per_source: false,
+            loudness_target: None,
+            normalize_audio: false,
+            loudnorm_two_pass: false,
+            delete_source_on_success: false,
+            confirm_delete_source: false,
+            denoise: "none".to_string(),
+            sharpen: false,
+            parallel_scope: "global".to_string(),
+            color_range: "auto".to_string(),
+            start_index: None,
+            continue_numbering: false,
+            resolve_export: false,
+            copy_container_fallback: false,
+            max_total_duration: None,
+            unique_suffix: None,
+            filename_template: None,
+            trim_edge_silence: false,
+            silence_threshold_db: -50.0,
+            silence_max_trim_sec: 1.5,
+            rules_file: None,
+            progress_log_file: None,
+            max_parallel: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+                .clamp(1, MAX_PARALLEL),
+            downmix: "none".to_string(),
+            trim_black: false,
+            black_threshold: 0.1,
+            black_max_trim_sec: 2.0,
+            assumed_fps: None,
+            fallback_naming: "literal".to_string(),
+            intro_file: None,
+            outro_file: None,
+            clamp_to_source: false,
+            muxer_format: None,
+            output_extension: None,
+            locked_retries: 3,
+            encoder: "libx264".to_string(),
+            output_format: None,
+            preserve_mtime: false,
+            concat: false,
+            fallback_reencode: false,
+            snap_to_keyframe: false,
+            accurate_copy: false,
+            burn_subtitle_track: None,
+            burn_label: false,
+            label_position: "top_left".to_string(),
+            label_font_size: 36,
+            label_font_file: None,
+            replaygain_tag: false,
+            report_path: None,
+            verbose: false,
+            time_precision: 3,
+            strict_validation: false,
+            ffmpeg_path: None,
+            ffprobe_path: None,
+            source_dir: None,
+            fast_segment_split: false,
+        }
+    }
+}
+
+/// Where a run's progress events go: `emit` (the `export-progress` event in the
+/// app), plus `progress_log_file` when set.
+struct ProgressLog {
+    file: Option<Mutex<std::fs::File>>,
+    run_id: String,
+    emit: ProgressSink,
+}
+
+type ProgressSink = Box<dyn Fn(&ProgressLogLine) + Send + Sync>;
+
+/// What every progress event of one `run_export` shares.
+struct RunProgress<'a> {
+    log: &'a ProgressLog,
+    total: usize,
+    row_weights: &'a [f64],
+    total_weight: f64,
+}
+
+impl RunProgress<'_> {
+    /// A running, run-level event with the bar at the rows finished so far.
+    fn payload(&self, row_results: &[RowResult], message: String) -> ProgressPayload {
+        ProgressPayload {
+            total: self.total,
+            completed: row_results.len(),
+            current_clip: String::new(),
+            status: "running".to_string(),
+            message,
+            row_index: None,
+            row_result: None,
+            phase: None,
+            clip_progress: None,
+            weighted_percent: weighted_percent(
+                finished_weight(self.row_weights, row_results),
+                self.total_weight,
+            ),
         }
     }
 }
 
+#[derive(Serialize, Clone)]
+struct ProgressLogLine {
+    run_id: String,
+    #[serde(flatten)]
+    payload: ProgressPayload,
+}
+
 #[derive(Serialize, Clone)]
 struct ProgressPayload {
     total: usize,
@@ -61,15 +451,289 @@ struct ProgressPayload {
     message: String,
     row_index: Option<usize>,
     row_result: Option<String>,
+    phase: Option<String>,
+    clip_progress: Option<f64>,
+    weighted_percent: Option<f64>,
 }
 
 #[derive(Serialize)]
 struct RunSummary {
+    run_id: String,
     total_rows: usize,
     exported: usize,
     skipped: usize,
     failed: usize,
     errors: Vec<String>,
+    warnings: Vec<String>,
+    merged: usize,
+    rows: Vec<RowResult>,
+    source_outputs: Vec<SourceOutput>,
+    validate_only: bool,
+    dry_run: bool,
+    planned_commands: Vec<PlannedCommand>,
+    deleted_sources: Vec<String>,
+    budget_included: Option<usize>,
+    budget_used_sec: Option<f64>,
+}
+
+/// A clip's ffmpeg commands as a dry run would have run them. `args` writes the
+/// `.partial` file that is renamed to `output_path` once it finished.
+#[derive(Serialize)]
+struct PlannedCommand {
+    row_index: usize,
+    output_path: String,
+    args: Vec<String>,
+    /// Retries when `args` fails: the .mkv container fallback, then the re-encode.
+    fallback_args: Vec<Vec<String>>,
+    /// The pass that joins the intro/outro around the finished clip.
+    bumper_args: Option<Vec<String>>,
+}
+
+struct ClipJob {
+    idx: usize,
+    row: ClipRow,
+    settings: ExportSettings,
+    source_video: PathBuf,
+    source_key: PathBuf,
+    destination: PathBuf,
+    row_ext: String,
+    start_sec: f64,
+    end_sec: f64,
+    audio_offset: Option<i32>,
+    times_swapped: bool,
+    featured: bool,
+    loudness_target: Option<f64>,
+    trimmed_duration: Option<f64>,
+    extended_duration: Option<f64>,
+    fade_note: Option<String>,
+    keyframe_start: Option<f64>,
+    replaygain_db: Option<f64>,
+    rule: Option<String>,
+    composed_of: Option<Vec<String>>,
+    filters: Vec<String>,
+}
+
+/// Jobs running on worker threads, collected in the order they finish so one long
+/// clip doesn't keep the other slots waiting behind it.
+struct WorkerPool<J, R> {
+    jobs: Vec<(J, std::thread::JoinHandle<R>)>,
+}
+
+impl<J, R> WorkerPool<J, R> {
+    fn new() -> Self {
+        Self { jobs: Vec::new() }
+    }
+
+    fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    fn push(&mut self, job: J, handle: std::thread::JoinHandle<R>) {
+        self.jobs.push((job, handle));
+    }
+
+    fn any(&self, f: impl Fn(&J) -> bool) -> bool {
+        self.jobs.iter().any(|(job, _)| f(job))
+    }
+
+    /// Removes a job whose worker has finished and returns it with the worker's
+    /// result. With `wait` it blocks until one finishes; without, it returns `None`
+    /// while every job is still running. Always `None` for an empty pool.
+    fn take_finished(&mut self, wait: bool) -> Option<(J, std::thread::Result<R>)> {
+        loop {
+            if let Some(pos) = self.jobs.iter().position(|(_, h)| h.is_finished()) {
+                let (job, handle) = self.jobs.remove(pos);
+                return Some((job, handle.join()));
+            }
+            if !wait || self.jobs.is_empty() {
+                return None;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(POOL_POLL_MS));
+        }
+    }
+}
+
+/// How a collected clip job ended.
+struct JobEnd {
+    succeeded: bool,
+    destination: PathBuf,
+    container_fallback: bool,
+    ffmpeg_log: String,
+    cleanup_note: String,
+}
+
+/// Reads the worker result of row `idx`, recording its errors. `destination` is
+/// the planned output, kept when the worker never reported one.
+fn job_end(
+    idx: usize,
+    destination: PathBuf,
+    joined: std::thread::Result<Result<ClipOutcome, String>>,
+    errors: &mut Vec<String>,
+) -> JobEnd {
+    let failed = |destination| JobEnd {
+        succeeded: false,
+        destination,
+        container_fallback: false,
+        ffmpeg_log: String::new(),
+        cleanup_note: String::new(),
+    };
+    match joined {
+        Ok(Ok(outcome)) => {
+            if outcome.reencode_fallback {
+                errors.push(format!("Row {} fell back to re-encode", idx + 2));
+            }
+            JobEnd {
+                succeeded: outcome.finished,
+                destination: outcome.destination,
+                container_fallback: outcome.container_fallback,
+                ffmpeg_log: stderr_tail(&outcome.stderr),
+                cleanup_note: outcome
+                    .removed_partial
+                    .map(|path| format!(" (removed partial output {})", path.display()))
+                    .unwrap_or_default(),
+            }
+        }
+        Ok(Err(err)) => {
+            errors.push(format!("Row {}: {}", idx + 2, err));
+            failed(destination)
+        }
+        Err(_) => {
+            errors.push(format!(
+                "Row {}: ffmpeg worker stopped unexpectedly",
+                idx + 2
+            ));
+            failed(destination)
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Bumper {
+    path: PathBuf,
+    duration: f64,
+    has_audio: bool,
+}
+
+/// Intro/outro clips joined around each re-encoded clip, plus the encoder
+/// settings of the row they wrap.
+#[derive(Clone)]
+struct Bumpers {
+    intro: Option<Bumper>,
+    outro: Option<Bumper>,
+    encoder: String,
+    audio_encoder: String,
+    preset: String,
+    crf: u8,
+    audio_bitrate_kbps: u16,
+    movflags: Option<String>,
+}
+
+/// Retry arguments (everything between the global flags and the output path)
+/// for a clip whose first attempt fails.
+struct ClipFallbacks {
+    container: Option<Vec<std::ffi::OsString>>,
+    reencode: Option<Vec<std::ffi::OsString>>,
+    loglevel: &'static str,
+}
+
+struct ClipOutcome {
+    status: ExitStatus,
+    destination: PathBuf,
+    container_fallback: bool,
+    reencode_fallback: bool,
+    /// ffmpeg's stderr from the last attempt, kept for failure messages.
+    stderr: String,
+    /// Whether the clip finished and was moved to its final name.
+    finished: bool,
+    /// Unfinished output deleted after a failed or stopped encode.
+    removed_partial: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct BlackInterval {
+    start_sec: f64,
+    end_sec: f64,
+    duration_sec: f64,
+}
+
+#[derive(Serialize)]
+struct BlackReport {
+    intervals: Vec<BlackInterval>,
+    leading_black_sec: f64,
+    trailing_black_sec: f64,
+}
+
+struct ChapterPart {
+    path: PathBuf,
+    title: String,
+    description: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct SourceOutput {
+    source: String,
+    output_path: String,
+    clip_count: usize,
+    reencoded: bool,
+}
+
+#[derive(Serialize, Clone)]
+struct RowResult {
+    row_index: usize,
+    clip_name: String,
+    status: String,
+    output_path: Option<String>,
+    output_size_bytes: Option<u64>,
+    audio_offset_ms: Option<i32>,
+    first_frame_path: Option<String>,
+    last_frame_path: Option<String>,
+    thumbnail_path: Option<String>,
+    times_swapped: bool,
+    requested_duration_sec: Option<f64>,
+    actual_duration_sec: Option<f64>,
+    featured: bool,
+    loudness_target: Option<f64>,
+    metadata_sidecar: Option<String>,
+    container_fallback: Option<String>,
+    trimmed_duration_sec: Option<f64>,
+    extended_duration_sec: Option<f64>,
+    keyframe_start_sec: Option<f64>,
+    replaygain_db: Option<f64>,
+    adjusted_start_sec: Option<f64>,
+    adjusted_end_sec: Option<f64>,
+    rule: Option<String>,
+    composed_of: Option<Vec<String>>,
+}
+
+impl RowResult {
+    fn new(row_index: usize, clip_name: &str, status: &str) -> Self {
+        Self {
+            row_index,
+            clip_name: clip_name.to_string(),
+            status: status.to_string(),
+            output_path: None,
+            output_size_bytes: None,
+            audio_offset_ms: None,
+            first_frame_path: None,
+            last_frame_path: None,
+            thumbnail_path: None,
+            times_swapped: false,
+            requested_duration_sec: None,
+            actual_duration_sec: None,
+            featured: false,
+            loudness_target: None,
+            metadata_sidecar: None,
+            container_fallback: None,
+            trimmed_duration_sec: None,
+            extended_duration_sec: None,
+            keyframe_start_sec: None,
+            replaygain_db: None,
+            adjusted_start_sec: None,
+            adjusted_end_sec: None,
+            rule: None,
+            composed_of: None,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -77,6 +741,263 @@ struct CsvPreview {
     total_rows: usize,
     rows: Vec<ClipRowPreview>,
     validation_errors: Vec<String>,
+    validation_warnings: Vec<String>,
+    timecode_offset_sec: f64,
+    source_duration_sec: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct CsvBatchPreview {
+    files: Vec<CsvFilePreview>,
+    total_errors: usize,
+    source_duration_sec: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct CsvFilePreview {
+    csv_path: String,
+    preview: Option<CsvPreview>,
+    error: Option<String>,
+    error_count: usize,
+}
+
+#[derive(Serialize)]
+struct DurationSummary {
+    total_seconds: f64,
+    formatted: String,
+    valid_clips: usize,
+    invalid_clips: usize,
+}
+
+#[derive(Serialize)]
+struct TimecodeFix {
+    row_index: usize,
+    field: String,
+    original: String,
+    suggested: String,
+}
+
+#[derive(Serialize)]
+struct SegmentSummary {
+    segment_seconds: f64,
+    files: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct OrphanCleanup {
+    found: Vec<u32>,
+    killed: Vec<u32>,
+    errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ClipStrip {
+    output_path: String,
+    frames: u32,
+}
+
+#[derive(Serialize)]
+struct FcpxmlSummary {
+    output_path: String,
+    clips: usize,
+    errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ProxyPackageSummary {
+    proxies: Vec<String>,
+    project_path: String,
+    errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SourceProbe {
+    path: String,
+    info: Option<VideoInfo>,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ExportManifest {
+    clips: Vec<ManifestEntry>,
+    #[serde(default)]
+    next_index: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    rules: Vec<RuleInput>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleInput {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(rename = "match")]
+    pattern: String,
+    settings: serde_json::Map<String, serde_json::Value>,
+}
+
+struct SettingsRule {
+    name: String,
+    pattern: String,
+    settings: ExportSettings,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ManifestEntry {
+    file_name: String,
+    clip_name: String,
+    start_time: String,
+    end_time: String,
+    /// The export run that last wrote this file.
+    #[serde(default)]
+    run_id: Option<String>,
+}
+
+/// Per-run export report written to `report_path`.
+#[derive(Serialize)]
+struct ExportReport {
+    run_id: String,
+    started_at_unix: u64,
+    finished_at_unix: u64,
+    status: String,
+    total_rows: usize,
+    exported: usize,
+    skipped: usize,
+    failed: usize,
+    settings: ExportSettings,
+    rows: Vec<ReportRow>,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ReportRow {
+    row_index: usize,
+    clip_name: String,
+    start_time: String,
+    end_time: String,
+    output_file: Option<String>,
+    status: String,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct VideoInfo {
+    duration_sec: Option<f64>,
+    bit_rate: Option<u64>,
+    width: Option<u32>,
+    height: Option<u32>,
+    fps: Option<f64>,
+    video_codec: Option<String>,
+    audio_codec: Option<String>,
+    audio_channels: Option<u32>,
+    color_range: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeOutput {
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
+    format: Option<FfprobeFormat>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeStream {
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    channels: Option<u32>,
+    r_frame_rate: Option<String>,
+    color_range: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeSubtitleOutput {
+    #[serde(default)]
+    streams: Vec<FfprobeSubtitleStream>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeSubtitleStream {
+    codec_name: Option<String>,
+    #[serde(default)]
+    tags: FfprobeStreamTags,
+}
+
+#[derive(Deserialize, Default)]
+struct FfprobeStreamTags {
+    language: Option<String>,
+    title: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SubtitleTrack {
+    /// Position among the subtitle streams, as used by `burn_subtitle_track`.
+    index: usize,
+    codec: Option<String>,
+    language: Option<String>,
+    title: Option<String>,
+    burnable: bool,
+}
+
+#[derive(Deserialize)]
+struct FfprobeFormat {
+    duration: Option<String>,
+    bit_rate: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct QcEntry {
+    file_name: String,
+    info: VideoInfo,
+    size_bytes: u64,
+    issues: Vec<String>,
+    passed: bool,
+}
+
+#[derive(Serialize)]
+struct QcSummary {
+    total: usize,
+    passed: usize,
+    failed: usize,
+    report_path: String,
+    files: Vec<QcEntry>,
+}
+
+#[derive(Serialize)]
+struct EncoderStatus {
+    name: String,
+    hardware: bool,
+    available: bool,
+}
+
+#[derive(Serialize)]
+struct DependencyStatus {
+    ffmpeg: ToolStatus,
+    ffprobe: ToolStatus,
+    install_hint: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ToolStatus {
+    found: bool,
+    path: Option<String>,
+    version: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CopyStartPreview {
+    row_index: usize,
+    clip_name: String,
+    requested_start: Option<f64>,
+    actual_start: Option<f64>,
+    delta_sec: Option<f64>,
+    error: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -84,6 +1005,7 @@ struct ClipRowPreview {
     clip_name: String,
     start_time: String,
     end_time: String,
+    overrides: RowOverrides,
 }
 
 #[tauri::command]
@@ -110,685 +1032,9615 @@ fn pick_output_dir() -> Option<String> {
 }
 
 #[tauri::command]
-fn preview_csv(csv_path: String) -> Result<CsvPreview, String> {
-    let rows = read_clip_rows(&csv_path)?;
-    let mut validation_errors = Vec::new();
-
-    for (idx, row) in rows.iter().enumerate() {
-        let row_num = idx + 2;
-        if row.start_time.trim().is_empty() || row.end_time.trim().is_empty() {
-            validation_errors.push(format!("Row {} missing start/end time", row_num));
-            continue;
-        }
+fn check_dependencies(
+    ffmpeg_path: Option<String>,
+    ffprobe_path: Option<String>,
+) -> DependencyStatus {
+    let tools = Tools::new(ffmpeg_path.as_deref(), ffprobe_path.as_deref());
+    let ffmpeg = tools.status("ffmpeg");
+    let ffprobe = tools.status("ffprobe");
+    let install_hint = if ffmpeg.found && ffprobe.found {
+        None
+    } else {
+        ffmpeg_install_hint().map(str::to_string)
+    };
 
-        if convert_to_seconds(&row.start_time).is_none() {
-            validation_errors.push(format!(
-                "Row {} invalid start time: {}",
-                row_num, row.start_time
-            ));
-        }
+    DependencyStatus {
+        ffmpeg,
+        ffprobe,
+        install_hint,
+    }
+}
 
-        if convert_to_seconds(&row.end_time).is_none() {
-            validation_errors.push(format!(
-                "Row {} invalid end time: {}",
-                row_num, row.end_time
-            ));
-        }
+/// Which of the supported video encoders this ffmpeg build includes. A listed
+/// hardware encoder can still fail at runtime when the GPU or driver is missing.
+#[tauri::command]
+fn detect_encoders(ffmpeg_path: Option<String>) -> Result<Vec<EncoderStatus>, String> {
+    let tools = Tools::new(ffmpeg_path.as_deref(), None);
+    tools.ensure_ffmpeg()?;
+    let output = tools
+        .ffmpeg()
+        .arg("-hide_banner")
+        .arg("-encoders")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg -encoders: {e}"))?;
+    if !output.status.success() {
+        return Err("ffmpeg -encoders failed".to_string());
     }
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let built_in = listing
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .collect::<HashSet<_>>();
 
-    let preview_rows = rows
+    Ok(VIDEO_ENCODERS
         .iter()
-        .map(|r| ClipRowPreview {
-            clip_name: r.clip_name.clone(),
-            start_time: r.start_time.clone(),
-            end_time: r.end_time.clone(),
+        .map(|name| EncoderStatus {
+            name: name.to_string(),
+            hardware: *name != "libx264",
+            available: built_in.contains(name),
         })
-        .collect::<Vec<_>>();
+        .collect())
+}
 
-    Ok(CsvPreview {
-        total_rows: rows.len(),
-        rows: preview_rows,
-        validation_errors,
+#[tauri::command]
+async fn list_subtitle_tracks(
+    video_path: String,
+    ffprobe_path: Option<String>,
+) -> Result<Vec<SubtitleTrack>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let tools = Tools::new(None, ffprobe_path.as_deref());
+        let source = PathBuf::from(&video_path);
+        if !source.is_file() {
+            return Err(format!("Video file not found: {video_path}"));
+        }
+        probe_subtitle_tracks(&tools, &source)
     })
+    .await
+    .map_err(|e| format!("Subtitle probe failed: {e}"))?
 }
 
+/// The `CLIPCHOP_OUTPUT_DIR` folder, if set. A value that isn't a writable
+/// folder is an error so the UI can say why it was ignored.
 #[tauri::command]
-fn stop_export(state: State<ProcessState>) -> Result<(), String> {
-    state.stop_requested.store(true, Ordering::SeqCst);
-    if let Some(child) = state.child.lock().map_err(|e| e.to_string())?.as_mut() {
-        child
-            .kill()
-            .map_err(|e| format!("Failed to stop ffmpeg: {e}"))?;
+fn default_output_dir() -> Result<Option<String>, String> {
+    env_output_dir().map(|dir| dir.map(|p| p.to_string_lossy().to_string()))
+}
+
+#[tauri::command]
+fn total_duration(rows: Vec<ClipRowInput>, assumed_fps: Option<f64>) -> DurationSummary {
+    let fps = assumed_fps.unwrap_or(DEFAULT_TIMECODE_FPS);
+    let durations = rows
+        .iter()
+        .map(|row| {
+            let start = convert_to_seconds(row.start_time.trim(), fps)?;
+            let end = convert_to_seconds(row.end_time.trim(), fps)?;
+            Some(end - start).filter(|d| *d > 0.0)
+        })
+        .collect::<Vec<_>>();
+    let total_seconds = durations.iter().flatten().sum::<f64>();
+    let valid_clips = durations.iter().flatten().count();
+
+    DurationSummary {
+        total_seconds,
+        formatted: format_timestamp(total_seconds),
+        valid_clips,
+        invalid_clips: durations.len() - valid_clips,
     }
-    Ok(())
 }
 
+/// Proposes corrected values for malformed start/end times. Nothing is applied;
+/// the UI decides which suggestions to accept.
 #[tauri::command]
-async fn start_export(
-    app: AppHandle,
-    state: State<'_, ProcessState>,
-    csv_path: String,
-    video_path: String,
+fn suggest_timecode_fixes(rows: Vec<ClipRowInput>, assumed_fps: Option<f64>) -> Vec<TimecodeFix> {
+    let fps = assumed_fps.unwrap_or(DEFAULT_TIMECODE_FPS);
+    rows.iter()
+        .enumerate()
+        .flat_map(|(row_index, row)| {
+            [("start_time", &row.start_time), ("end_time", &row.end_time)]
+                .into_iter()
+                .filter_map(move |(field, value)| {
+                    Some(TimecodeFix {
+                        row_index,
+                        field: field.to_string(),
+                        original: value.clone(),
+                        suggested: suggest_timecode(value, fps)?,
+                    })
+                })
+        })
+        .collect()
+}
+
+#[tauri::command]
+fn preview_csv(
+    csv_path: String,
+    video_path: Option<String>,
+    settings: Option<ExportSettings>,
+) -> Result<CsvPreview, String> {
+    let settings = normalize_settings(settings.unwrap_or_default());
+    let tools = Tools::from_settings(&settings);
+    let video = video_path.as_deref().map(Path::new);
+    let fps = resolve_timecode_fps(&tools, &settings, video);
+    let timecode_offset = resolve_timecode_offset(&tools, &settings, fps, video);
+    let source_duration = video.and_then(|video| probe_duration(&tools, video));
+    build_csv_preview(&csv_path, &settings, timecode_offset, fps, source_duration)
+}
+
+#[tauri::command]
+async fn preview_csv_batch(
+    paths: Vec<String>,
+    video_path: Option<String>,
+    settings: Option<ExportSettings>,
+) -> Result<CsvBatchPreview, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let settings = normalize_settings(settings.unwrap_or_default());
+        let tools = Tools::from_settings(&settings);
+        let video = video_path.as_deref().map(Path::new);
+        let fps = resolve_timecode_fps(&tools, &settings, video);
+        let timecode_offset = resolve_timecode_offset(&tools, &settings, fps, video);
+        let source_duration = video.and_then(|video| probe_duration(&tools, video));
+
+        let settings = &settings;
+        let files = std::thread::scope(|scope| {
+            let handles = paths
+                .iter()
+                .map(|path| {
+                    scope.spawn(move || {
+                        build_csv_preview(path, settings, timecode_offset, fps, source_duration)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            paths
+                .iter()
+                .zip(handles)
+                .map(|(path, handle)| {
+                    let result = handle
+                        .join()
+                        .unwrap_or_else(|_| Err("CSV preview thread panicked".to_string()));
+                    match result {
+                        Ok(preview) => CsvFilePreview {
+                            csv_path: path.clone(),
+                            error_count: preview.validation_errors.len(),
+                            preview: Some(preview),
+                            error: None,
+                        },
+                        Err(err) => CsvFilePreview {
+                            csv_path: path.clone(),
+                            error_count: 1,
+                            preview: None,
+                            error: Some(err),
+                        },
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+
+        CsvBatchPreview {
+            total_errors: files.iter().map(|f| f.error_count).sum(),
+            source_duration_sec: source_duration,
+            files,
+        }
+    })
+    .await
+    .map_err(|e| format!("CSV batch preview failed: {e}"))
+}
+
+fn build_csv_preview(
+    csv_path: &str,
+    settings: &ExportSettings,
+    timecode_offset: f64,
+    fps: f64,
+    source_duration: Option<f64>,
+) -> Result<CsvPreview, String> {
+    let (rows, columns) = read_clip_table(csv_path, &settings.csv_encoding, timecode_offset, fps)?;
+    let mut validation_errors = Vec::new();
+    let mut validation_warnings = Vec::new();
+    if columns.end.is_some() && columns.duration.is_some() {
+        validation_warnings.push(
+            "CSV has both an end time and a duration column; end times are used and durations only fill rows without one"
+                .to_string(),
+        );
+    }
+
+    validation_errors.extend(settings_errors(settings));
+    validate_clip_rows(
+        &rows,
+        settings,
+        timecode_offset,
+        fps,
+        source_duration,
+        &mut validation_errors,
+        &mut validation_warnings,
+    );
+
+    let preview_rows = rows
+        .iter()
+        .map(|r| ClipRowPreview {
+            clip_name: r.clip_name.clone(),
+            start_time: r.start_time.clone(),
+            end_time: r.end_time.clone(),
+            overrides: r.overrides.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(CsvPreview {
+        total_rows: rows.len(),
+        rows: preview_rows,
+        validation_errors,
+        validation_warnings,
+        timecode_offset_sec: timecode_offset,
+        source_duration_sec: source_duration,
+    })
+}
+
+/// Preview-level checks of each row's times and per-row columns.
+fn validate_clip_rows(
+    rows: &[ClipRow],
+    settings: &ExportSettings,
+    timecode_offset: f64,
+    fps: f64,
+    source_duration: Option<f64>,
+    validation_errors: &mut Vec<String>,
+    validation_warnings: &mut Vec<String>,
+) {
+    for (idx, row) in rows.iter().enumerate() {
+        let row_num = idx + 2;
+        if row.start_time.trim().is_empty() || row.end_time.trim().is_empty() {
+            validation_errors.push(format!("Row {} missing start/end time", row_num));
+            continue;
+        }
+
+        // Without a video, end-relative times can only be checked at export.
+        let mut time = |ts: &str, which: &str| match clip_time_seconds(
+            ts,
+            timecode_offset,
+            fps,
+            source_duration,
+        ) {
+            Ok(seconds) => Some(seconds),
+            Err(_) if source_duration.is_none() && seconds_before_end(ts, fps).is_some() => {
+                validation_warnings.push(format!(
+                        "Row {} {} time '{}' counts from the end of the video and is resolved at export",
+                        row_num,
+                        which,
+                        ts.trim()
+                    ));
+                None
+            }
+            Err(reason) => {
+                validation_errors.push(format!("Row {} {} time {}", row_num, which, reason));
+                None
+            }
+        };
+        let start = time(&row.start_time, "start");
+        let end = time(&row.end_time, "end");
+        if let (Some(start), Some(duration)) = (start, source_duration) {
+            if start >= duration {
+                validation_errors.push(format!(
+                    "Row {} start time {} is past the end of the video ({})",
+                    row_num,
+                    row.start_time,
+                    format_timestamp(duration)
+                ));
+            }
+        }
+
+        if let Err(reason) = row_featured(&row.overrides) {
+            validation_errors.push(format!("Row {} {}", row_num, reason));
+        }
+        if let Err(reason) = row_loudness_target(&row.overrides, None) {
+            validation_errors.push(format!("Row {} {}", row_num, reason));
+        }
+        if let Err(reason) = row_settings(&row.overrides, settings) {
+            validation_errors.push(format!("Row {} {}", row_num, reason));
+        }
+
+        if let (Some(start), Some(end)) = (start, end) {
+            if settings.autoswap_times && end < start {
+                validation_warnings.push(format!(
+                    "Row {} end time is before start time and will be swapped",
+                    row_num
+                ));
+            } else if end <= start {
+                validation_errors.push(format!(
+                    "Row {} end time must be greater than start time",
+                    row_num
+                ));
+            }
+        }
+
+        if let Some(end) = end {
+            // A start past the end is already an error; an end that only
+            // overshoots still exports, cut short where the video stops.
+            let start_in_video = !matches!((start, source_duration), (Some(s), Some(d)) if s >= d);
+            if let Some(duration) = source_duration.filter(|d| end > *d && start_in_video) {
+                validation_warnings.push(format!(
+                    "Row {} end time {} exceeds video length {}; the clip will stop at the end of the video",
+                    row_num,
+                    row.end_time,
+                    format_timestamp(duration)
+                ));
+            }
+        }
+    }
+}
+
+#[tauri::command]
+async fn preview_copy_starts(
+    video_path: String,
+    rows: Vec<ClipRowInput>,
+    settings: Option<ExportSettings>,
+) -> Result<Vec<CopyStartPreview>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let source_video = PathBuf::from(&video_path);
+        if !source_video.exists() {
+            return Err(format!("Video file not found: {video_path}"));
+        }
+        let settings = normalize_settings(settings.unwrap_or_default());
+        let tools = Tools::from_settings(&settings);
+        let fps = resolve_timecode_fps(&tools, &settings, Some(&source_video));
+        let timecode_offset = resolve_timecode_offset(&tools, &settings, fps, Some(&source_video));
+        let keyframes = probe_keyframes(&tools, &source_video)?;
+
+        Ok(rows
+            .iter()
+            .enumerate()
+            .map(|(idx, row)| {
+                let Some(requested) = timeline_seconds(&row.start_time, timecode_offset, fps)
+                else {
+                    return CopyStartPreview {
+                        row_index: idx,
+                        clip_name: row.clip_name.clone(),
+                        requested_start: None,
+                        actual_start: None,
+                        delta_sec: None,
+                        error: Some(format!("invalid start time '{}'", row.start_time)),
+                    };
+                };
+                let actual = keyframe_at_or_before(&keyframes, requested)
+                    .or_else(|| keyframes.first().copied());
+                CopyStartPreview {
+                    row_index: idx,
+                    clip_name: row.clip_name.clone(),
+                    requested_start: Some(requested),
+                    actual_start: actual,
+                    delta_sec: actual.map(|a| requested - a),
+                    error: None,
+                }
+            })
+            .collect())
+    })
+    .await
+    .map_err(|e| format!("Copy start preview failed: {e}"))?
+}
+
+#[tauri::command]
+async fn generate_qc_report(
+    app: AppHandle,
     output_dir: String,
+    expected_video_codec: Option<String>,
+    ffprobe_path: Option<String>,
+) -> Result<QcSummary, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        run_qc_report(
+            &Tools::new(None, ffprobe_path.as_deref()),
+            &app,
+            Path::new(&output_dir),
+            expected_video_codec.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| format!("QC report failed: {e}"))?
+}
+
+#[tauri::command]
+async fn export_fcpxml(
+    csv_path: String,
+    video_path: String,
+    output_path: String,
     settings: Option<ExportSettings>,
-    edited_rows: Option<Vec<ClipRowInput>>,
-) -> Result<RunSummary, String> {
-    let child_state = state.child.clone();
+) -> Result<FcpxmlSummary, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let settings = normalize_settings(settings.unwrap_or_default());
+        let tools = Tools::from_settings(&settings);
+        let source = PathBuf::from(&video_path);
+        if !source.is_file() {
+            return Err(format!("Video file not found: {video_path}"));
+        }
+        let info = probe_video(&tools, &source)?;
+        let fps = info
+            .fps
+            .ok_or_else(|| "Could not read the source frame rate".to_string())?;
+        let timecode_fps = settings.timecode_fps(Some(fps));
+        let timecode_offset =
+            resolve_timecode_offset(&tools, &settings, timecode_fps, Some(&source));
+        let rows = read_clip_rows(
+            &csv_path,
+            &settings.csv_encoding,
+            timecode_offset,
+            timecode_fps,
+        )?;
+        let (clips, errors) = timeline_clips(&rows, timecode_offset, timecode_fps, &info);
+        if clips.is_empty() {
+            return Err("No valid rows to write".to_string());
+        }
+
+        let xml = build_fcpxml(&source, &info, fps, &clips);
+        std::fs::write(&output_path, xml).map_err(|e| format!("Failed to write FCPXML: {e}"))?;
+
+        Ok(FcpxmlSummary {
+            output_path,
+            clips: clips.len(),
+            errors,
+        })
+    })
+    .await
+    .map_err(|e| format!("FCPXML export failed: {e}"))?
+}
+
+#[tauri::command]
+async fn export_proxy_package(
+    state: State<'_, ProcessState>,
+    csv_path: String,
+    video_path: String,
+    output_dir: String,
+    settings: Option<ExportSettings>,
+) -> Result<ProxyPackageSummary, String> {
+    let running_guard = RunningGuard::acquire(state.is_running.clone())?;
+    let child_state = state.children.clone();
     let stop_state = state.stop_requested.clone();
 
     tauri::async_runtime::spawn_blocking(move || {
-        run_export(
-            app,
-            child_state,
-            stop_state,
-            csv_path,
-            video_path,
-            output_dir,
-            settings.unwrap_or_default(),
-            edited_rows,
-        )
+        let _running_guard = running_guard;
+        stop_state.store(false, Ordering::SeqCst);
+        let settings = normalize_settings(settings.unwrap_or_default());
+        let tools = Tools::from_settings(&settings);
+        tools.ensure_ffmpeg()?;
+        let source = PathBuf::from(&video_path);
+        if !source.is_file() {
+            return Err(format!("Video file not found: {video_path}"));
+        }
+        let info = probe_video(&tools, &source)?;
+        let fps = info
+            .fps
+            .filter(|fps| fps.is_finite() && *fps > 0.0)
+            .ok_or_else(|| "Could not read the source frame rate".to_string())?;
+        let timecode_fps = settings.timecode_fps(Some(fps));
+        let timecode_offset =
+            resolve_timecode_offset(&tools, &settings, timecode_fps, Some(&source));
+        let rows = read_clip_rows(
+            &csv_path,
+            &settings.csv_encoding,
+            timecode_offset,
+            timecode_fps,
+        )?;
+        let (clips, mut errors) = timeline_clips(&rows, timecode_offset, timecode_fps, &info);
+        if clips.is_empty() {
+            return Err("No valid rows to export".to_string());
+        }
+
+        let output_path = PathBuf::from(&output_dir);
+        let proxy_dir = output_path.join(PROXY_DIR);
+        std::fs::create_dir_all(&proxy_dir)
+            .map_err(|e| format!("Failed to create proxy folder: {e}"))?;
+
+        let mut proxies = Vec::new();
+        for (idx, (name, start, end)) in clips.iter().enumerate() {
+            if stop_state.load(Ordering::SeqCst) {
+                return Err("Proxy export stopped by user".to_string());
+            }
+            let destination = proxy_dir.join(format!(
+                "{:03}-{}-proxy.mp4",
+                idx + 1,
+                sanitize_filename(name)
+            ));
+            let cmd = proxy_command(
+                &tools,
+                &source,
+                *start,
+                end - start,
+                &settings,
+                &destination,
+            );
+            match run_ffmpeg(cmd, &child_state, None) {
+                Ok((status, _)) if status.success() => {
+                    proxies.push(destination.to_string_lossy().to_string());
+                }
+                Ok((_, stderr)) => errors.push(format!(
+                    "Proxy {} ({}) failed: {}",
+                    idx + 1,
+                    name,
+                    stderr_tail(&stderr)
+                )),
+                Err(err) => errors.push(format!("Proxy {} ({}) failed: {}", idx + 1, name, err)),
+            }
+        }
+        if stop_state.load(Ordering::SeqCst) {
+            return Err("Proxy export stopped by user".to_string());
+        }
+
+        // The timeline points at the original source, so editors relink to it
+        // after cutting with the proxies.
+        let stem = source
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let project_path = output_path.join(format!("{}.fcpxml", sanitize_filename(&stem)));
+        std::fs::write(&project_path, build_fcpxml(&source, &info, fps, &clips))
+            .map_err(|e| format!("Failed to write FCPXML: {e}"))?;
+
+        Ok(ProxyPackageSummary {
+            proxies,
+            project_path: project_path.to_string_lossy().to_string(),
+            errors,
+        })
     })
     .await
-    .map_err(|e| format!("Export task failed: {e}"))?
+    .map_err(|e| format!("Proxy export failed: {e}"))?
+}
+
+#[tauri::command]
+async fn detect_black(
+    state: State<'_, ProcessState>,
+    video_path: String,
+    threshold: Option<f64>,
+    ffmpeg_path: Option<String>,
+    ffprobe_path: Option<String>,
+) -> Result<BlackReport, String> {
+    let child_state = state.children.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let tools = Tools::new(ffmpeg_path.as_deref(), ffprobe_path.as_deref());
+        let source = PathBuf::from(&video_path);
+        if !source.is_file() {
+            return Err(format!("Video file not found: {video_path}"));
+        }
+        let threshold = threshold.unwrap_or(0.1);
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err("Black threshold must be between 0 and 1".to_string());
+        }
+        tools.ensure_ffmpeg()?;
+
+        let duration = probe_duration(&tools, &source)
+            .ok_or_else(|| format!("Could not read the duration of {video_path}"))?;
+        let periods = detect_black_periods(&tools, &child_state, &source, None, threshold)?;
+        let (leading_black_sec, trailing_black_sec) = edge_periods(&periods, duration);
+        let intervals = periods
+            .into_iter()
+            .map(|(start, end)| {
+                let end = end.unwrap_or(duration);
+                BlackInterval {
+                    start_sec: start,
+                    end_sec: end,
+                    duration_sec: end - start,
+                }
+            })
+            .collect();
+
+        Ok(BlackReport {
+            intervals,
+            leading_black_sec,
+            trailing_black_sec,
+        })
+    })
+    .await
+    .map_err(|e| format!("Black detection failed: {e}"))?
+}
+
+#[tauri::command]
+async fn clip_strip(
+    state: State<'_, ProcessState>,
+    video_path: String,
+    start: String,
+    end: String,
+    count: u32,
+    output_path: String,
+    assumed_fps: Option<f64>,
+    ffmpeg_path: Option<String>,
+    ffprobe_path: Option<String>,
+) -> Result<ClipStrip, String> {
+    let child_state = state.children.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let tools = Tools::new(ffmpeg_path.as_deref(), ffprobe_path.as_deref());
+        let source = PathBuf::from(&video_path);
+        if !source.is_file() {
+            return Err(format!("Video file not found: {video_path}"));
+        }
+        if !(1..=MAX_STRIP_FRAMES).contains(&count) {
+            return Err(format!(
+                "Frame count must be between 1 and {MAX_STRIP_FRAMES}"
+            ));
+        }
+        let fps = assumed_fps.unwrap_or(DEFAULT_TIMECODE_FPS);
+        if !(fps.is_finite() && fps > 0.0) {
+            return Err("assumed_fps must be a positive number".to_string());
+        }
+        let start_sec = convert_to_seconds(&start, fps)
+            .ok_or_else(|| format!("Invalid start time: {start}"))?;
+        let end_sec =
+            convert_to_seconds(&end, fps).ok_or_else(|| format!("Invalid end time: {end}"))?;
+        if end_sec <= start_sec {
+            return Err("End time must be greater than start time".to_string());
+        }
+
+        // A clip shorter than `count` frames can't supply distinct samples, so
+        // the strip shrinks to the frames that actually exist.
+        let duration = end_sec - start_sec;
+        let available = probe_video(&tools, &source)
+            .ok()
+            .and_then(|info| info.fps)
+            .map(|fps| ((duration * fps).floor() as u32).max(1))
+            .unwrap_or(count);
+        let frames = count.min(available);
+
+        let mut cmd = tools.ffmpeg();
+        cmd.arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-ss")
+            .arg(start_sec.to_string())
+            .arg("-t")
+            .arg(duration.to_string())
+            .arg("-i")
+            .arg(&source)
+            .arg("-vf")
+            .arg(format!(
+                "fps={}/{},scale={}:-2,tile={}x1",
+                frames, duration, STRIP_TILE_WIDTH, frames
+            ))
+            .arg("-frames:v")
+            .arg("1")
+            .arg("-q:v")
+            .arg("3")
+            .arg(&output_path);
+        let (status, stderr) = run_ffmpeg(cmd, &child_state, None)?;
+
+        if status.success() && Path::new(&output_path).exists() {
+            Ok(ClipStrip {
+                output_path,
+                frames,
+            })
+        } else {
+            Err(format!("Failed to build strip: {}", stderr.trim()))
+        }
+    })
+    .await
+    .map_err(|e| format!("Clip strip failed: {e}"))?
+}
+
+#[tauri::command]
+async fn probe_sources(
+    app: AppHandle,
+    paths: Vec<String>,
+    ffprobe_path: Option<String>,
+) -> Result<Vec<SourceProbe>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let tools = Tools::new(None, ffprobe_path.as_deref());
+        let mut seen = HashSet::new();
+        let unique = paths
+            .into_iter()
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .filter(|p| seen.insert(std::fs::canonicalize(p).unwrap_or_else(|_| PathBuf::from(p))))
+            .collect::<Vec<_>>();
+        let total = unique.len();
+
+        unique
+            .into_iter()
+            .enumerate()
+            .map(|(idx, path)| {
+                let _ = app.emit(
+                    "source-probe-progress",
+                    ProgressPayload {
+                        total,
+                        completed: idx,
+                        current_clip: path.clone(),
+                        status: "running".to_string(),
+                        message: format!("Probing source {} of {}", idx + 1, total),
+                        row_index: Some(idx),
+                        row_result: None,
+                        phase: Some("probe".to_string()),
+                        clip_progress: None,
+                        weighted_percent: None,
+                    },
+                );
+
+                let result = if Path::new(&path).is_file() {
+                    probe_video(&tools, Path::new(&path))
+                } else {
+                    Err(format!("Video file not found: {path}"))
+                };
+                match result {
+                    Ok(info) => SourceProbe {
+                        path,
+                        info: Some(info),
+                        error: None,
+                    },
+                    Err(err) => SourceProbe {
+                        path,
+                        info: None,
+                        error: Some(err),
+                    },
+                }
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|e| format!("Source probe failed: {e}"))
+}
+
+#[tauri::command]
+fn renumber_outputs(output_dir: String, ordered_names: Vec<String>) -> Result<Vec<String>, String> {
+    let dir = Path::new(&output_dir);
+    if !dir.is_dir() {
+        return Err(format!("Output directory not found: {output_dir}"));
+    }
+
+    let mut seen = HashSet::new();
+    for name in &ordered_names {
+        if !seen.insert(name.as_str()) {
+            return Err(format!("File listed more than once: {name}"));
+        }
+        if Path::new(name).file_name() != Some(std::ffi::OsStr::new(name)) {
+            return Err(format!("Invalid file name: {name}"));
+        }
+        if !dir.join(name).is_file() {
+            return Err(format!("File not found: {name}"));
+        }
+    }
+
+    let new_names = ordered_names
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| renumbered_name(name, idx))
+        .collect::<Vec<_>>();
+    for new_name in &new_names {
+        if !seen.contains(new_name.as_str()) && dir.join(new_name).exists() {
+            return Err(format!("Renumbering would overwrite {new_name}"));
+        }
+    }
+
+    // Move everything to temp names first so a swap (001 <-> 002) never clobbers a file.
+    let moves = ordered_names
+        .iter()
+        .zip(&new_names)
+        .enumerate()
+        .flat_map(|(idx, (old, new))| {
+            let old_path = dir.join(old);
+            let new_path = dir.join(new);
+            let mut pairs = vec![(old_path.clone(), new_path.clone())];
+            for label in ["first", "last"] {
+                let sidecar = sidecar_path(&old_path, label, "jpg");
+                if sidecar.is_file() {
+                    pairs.push((sidecar, sidecar_path(&new_path, label, "jpg")));
+                }
+            }
+            pairs.into_iter().enumerate().map(move |(n, (from, to))| {
+                let temp = dir.join(format!(".clipchop-renumber-{idx}-{n}.tmp"));
+                (from, temp, to)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (from, temp, _) in &moves {
+        std::fs::rename(from, temp)
+            .map_err(|e| format!("Failed to rename {}: {e}", from.display()))?;
+    }
+    for (_, temp, to) in &moves {
+        std::fs::rename(temp, to)
+            .map_err(|e| format!("Failed to rename to {}: {e}", to.display()))?;
+    }
+
+    if let Some(mut manifest) = read_manifest(dir) {
+        let position = |file_name: &str| ordered_names.iter().position(|n| n == file_name);
+        for entry in &mut manifest.clips {
+            if let Some(idx) = position(&entry.file_name) {
+                entry.file_name = new_names[idx].clone();
+            }
+        }
+        manifest
+            .clips
+            .sort_by_key(|entry| new_names.iter().position(|n| *n == entry.file_name));
+        write_manifest(dir, &manifest)?;
+    }
+
+    Ok(new_names)
 }
 
-fn run_export(
-    app: AppHandle,
-    child_state: Arc<Mutex<Option<Child>>>,
-    stop_state: Arc<AtomicBool>,
-    csv_path: String,
-    video_path: String,
-    output_dir: String,
-    raw_settings: ExportSettings,
-    edited_rows: Option<Vec<ClipRowInput>>,
-) -> Result<RunSummary, String> {
-    stop_state.store(false, Ordering::SeqCst);
-    let settings = normalize_settings(raw_settings);
+/// Splits a whole video into equal `segment_seconds` pieces with one ffmpeg run
+/// of the segment muxer. Copy Streams splits at the nearest keyframes; the
+/// re-encode modes force a keyframe at every boundary so pieces are exact.
+#[tauri::command]
+async fn segment_export(
+    state: State<'_, ProcessState>,
+    video_path: String,
+    output_dir: String,
+    segment_seconds: f64,
+    settings: Option<ExportSettings>,
+) -> Result<SegmentSummary, String> {
+    let running_guard = RunningGuard::acquire(state.is_running.clone())?;
+    let child_state = state.children.clone();
+    let stop_state = state.stop_requested.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let _running_guard = running_guard;
+        stop_state.store(false, Ordering::SeqCst);
+        let settings = normalize_settings(settings.unwrap_or_default());
+        let tools = Tools::from_settings(&settings);
+        if !(segment_seconds.is_finite() && segment_seconds > 0.0) {
+            return Err("segment_seconds must be a positive number".to_string());
+        }
+        if settings.processing_mode == "webp" {
+            return Err("Segment export doesn't support WebP previews".to_string());
+        }
+        if settings
+            .output_format
+            .as_deref()
+            .is_some_and(is_audio_format)
+        {
+            return Err("Segment export doesn't support audio-only output formats".to_string());
+        }
+        check_output_format(&settings)?;
+        tools.ensure_ffmpeg()?;
+        let source = PathBuf::from(&video_path);
+        if !source.is_file() {
+            return Err(format!("Video file not found: {video_path}"));
+        }
+        let output_path = PathBuf::from(&output_dir);
+        std::fs::create_dir_all(&output_path)
+            .map_err(|e| format!("Failed to create output directory: {e}"))?;
+
+        let stem = sanitize_filename(
+            &source
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        );
+        let ext = settings.output_extension.clone().unwrap_or_else(|| {
+            output_extension(
+                &settings.processing_mode,
+                &source,
+                settings.output_format.as_deref(),
+            )
+        });
+        let prefix = format!("{stem}-");
+        let started = std::time::SystemTime::now();
+        let cmd = segment_command(
+            &tools,
+            &source,
+            &output_path.join(format!("{prefix}%03d.{ext}")),
+            segment_seconds,
+            &settings,
+            &ext,
+        );
+        let (status, stderr) = run_ffmpeg(cmd, &child_state, None)?;
+        if stop_state.load(Ordering::SeqCst) {
+            return Err("Segment export stopped by user".to_string());
+        }
+        if !status.success() {
+            return Err(format!("Segment export failed: {}", stderr.trim()));
+        }
+
+        // Only count pieces written by this run, not leftovers of a longer earlier split.
+        let mut files = std::fs::read_dir(&output_path)
+            .map_err(|e| format!("Failed to read output directory: {e}"))?
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let number = name
+                    .strip_prefix(&prefix)
+                    .and_then(|rest| rest.strip_suffix(&format!(".{ext}")));
+                matches!(number, Some(n) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+                    && matches!(
+                        entry.metadata().and_then(|m| m.modified()),
+                        Ok(modified) if modified >= started
+                    )
+            })
+            .map(|entry| entry.path().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        files.sort();
+
+        Ok(SegmentSummary {
+            segment_seconds,
+            files,
+        })
+    })
+    .await
+    .map_err(|e| format!("Segment export failed: {e}"))?
+}
+
+/// Finds ffmpeg processes this app started in an earlier session that are still
+/// running (e.g. after a crash) and, unless `list_only`, terminates them. Only
+/// pids recorded in the pidfile whose process is still named ffmpeg are touched.
+#[tauri::command]
+fn cleanup_orphans(
+    state: State<ProcessState>,
+    list_only: Option<bool>,
+) -> Result<OrphanCleanup, String> {
+    let live = state
+        .children
+        .lock()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(Child::id)
+        .collect::<HashSet<_>>();
+    let _guard = PID_FILE_LOCK.lock().map_err(|e| e.to_string())?;
+    let path = std::env::temp_dir().join(PID_FILE);
+    cleanup_pidfile(&path, &live, list_only.unwrap_or(false))
+}
+
+/// `cleanup_orphans` for the pidfile at `path`; `live` are the pids this
+/// session still runs. The caller holds `PID_FILE_LOCK`.
+fn cleanup_pidfile(
+    path: &Path,
+    live: &HashSet<u32>,
+    list_only: bool,
+) -> Result<OrphanCleanup, String> {
+    let recorded = read_pids(path);
+    let found = recorded
+        .iter()
+        .copied()
+        .filter(|pid| !live.contains(pid) && is_ffmpeg_process(*pid))
+        .collect::<Vec<_>>();
+
+    let mut killed = Vec::new();
+    let mut errors = Vec::new();
+    if !list_only {
+        for pid in &found {
+            match kill_process(*pid) {
+                Ok(()) => killed.push(*pid),
+                Err(err) => errors.push(format!("Failed to stop ffmpeg (pid {pid}): {err}")),
+            }
+        }
+        // Keep only processes this session is still running.
+        let remaining = recorded
+            .into_iter()
+            .filter(|pid| live.contains(pid))
+            .collect::<Vec<_>>();
+        write_pids(path, &remaining)?;
+    }
+
+    Ok(OrphanCleanup {
+        found,
+        killed,
+        errors,
+    })
+}
+
+#[tauri::command]
+fn stop_export(state: State<ProcessState>) -> Result<(), String> {
+    state.stop_requested.store(true, Ordering::SeqCst);
+    let mut children = state.children.lock().map_err(|e| e.to_string())?;
+    let mut result = Ok(());
+    for child in children.iter_mut() {
+        if let Err(e) = child.kill() {
+            result = Err(format!("Failed to stop ffmpeg: {e}"));
+        }
+    }
+    result
+}
+
+#[tauri::command]
+async fn start_export(
+    app: AppHandle,
+    state: State<'_, ProcessState>,
+    csv_path: Option<String>,
+    video_path: String,
+    output_dir: String,
+    settings: Option<ExportSettings>,
+    edited_rows: Option<Vec<ClipRowInput>>,
+    featured_settings: Option<ExportSettings>,
+    validate_only: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<RunSummary, String> {
+    let running_guard = RunningGuard::acquire(state.is_running.clone())?;
+    let child_state = state.children.clone();
+    let stop_state = state.stop_requested.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let _running_guard = running_guard;
+        run_export(
+            Box::new(move |line| {
+                let _ = app.emit("export-progress", line.clone());
+            }),
+            child_state,
+            stop_state,
+            csv_path,
+            video_path,
+            output_dir,
+            settings.unwrap_or_default(),
+            edited_rows,
+            featured_settings,
+            validate_only.unwrap_or(false),
+            dry_run.unwrap_or(false),
+        )
+    })
+    .await
+    .map_err(|e| format!("Export task failed: {e}"))?
+}
+
+fn run_export(
+    emit: ProgressSink,
+    child_state: Arc<Mutex<Vec<Child>>>,
+    stop_state: Arc<AtomicBool>,
+    csv_path: Option<String>,
+    video_path: String,
+    output_dir: String,
+    raw_settings: ExportSettings,
+    edited_rows: Option<Vec<ClipRowInput>>,
+    raw_featured_settings: Option<ExportSettings>,
+    validate_only: bool,
+    dry_run: bool,
+) -> Result<RunSummary, String> {
+    stop_state.store(false, Ordering::SeqCst);
+    // Validation and dry runs plan every clip without writing anything.
+    let writes_outputs = !validate_only && !dry_run;
+    let started_at = unix_seconds();
+    let settings = normalize_settings(raw_settings);
+    let tools = Tools::from_settings(&settings);
+    // The featured profile only changes how featured rows are encoded; run-wide
+    // options (timecode offset, merging, exact frames) still come from `settings`.
+    let featured_profile = raw_featured_settings.map(normalize_settings);
+    let featured_settings = featured_profile.clone().unwrap_or_else(|| settings.clone());
+    let mut problems = settings_errors(&settings);
+    if let Some(featured) = featured_profile.as_ref() {
+        problems.extend(
+            settings_errors(featured)
+                .into_iter()
+                .map(|problem| format!("Featured profile: {problem}")),
+        );
+    }
+    if !problems.is_empty() {
+        return Err(problems.join("\n"));
+    }
+    let exact_fps = settings.fps.filter(|_| settings.exact_frames);
+
+    let rules = match settings.rules_file.as_deref() {
+        Some(path) => load_rules(Path::new(path), &settings)?,
+        None => Vec::new(),
+    };
+
+    let progress_log = Arc::new(ProgressLog {
+        file: settings
+            .progress_log_file
+            .as_deref()
+            .map(|path| open_progress_log(Path::new(path)).map(Mutex::new))
+            .transpose()?,
+        run_id: new_run_id(),
+        emit,
+    });
+    // Rows finished so far, for progress events sent from worker threads.
+    let finished_rows = Arc::new(AtomicUsize::new(0));
+    let done_weight = Arc::new(AtomicU64::new(0f64.to_bits()));
+
+    let csv_path = csv_path.filter(|p| !p.trim().is_empty());
+    if csv_path.is_none() && edited_rows.is_none() {
+        return Err("Provide a CSV file or clip rows to export".to_string());
+    }
+
+    tools.ensure_ffmpeg()?;
+    let bumpers = load_bumpers(&tools, &settings)?;
+    if let Some(track) = settings.burn_subtitle_track {
+        let source = Path::new(&video_path);
+        check_filter_text(&video_path, "Video path")?;
+        let tracks = probe_subtitle_tracks(&tools, source)?;
+        match tracks.get(track) {
+            None => {
+                return Err(format!(
+                    "Subtitle track {track} not found; the video has {} subtitle track(s)",
+                    tracks.len()
+                ))
+            }
+            Some(found) if !found.burnable => {
+                return Err(format!(
+                    "Subtitle track {track} ({}) is image-based and can't be burned in",
+                    found.codec.as_deref().unwrap_or("unknown codec")
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    let source_video = PathBuf::from(&video_path);
+    if !source_video.exists() {
+        return Err(format!("Video file not found: {video_path}"));
+    }
+    let source_dir = settings.source_dir.as_deref().map(PathBuf::from);
+    if let Some(dir) = source_dir.as_deref().filter(|dir| !dir.is_dir()) {
+        return Err(format!("Source folder not found: {}", dir.display()));
+    }
+    let fps = resolve_timecode_fps(&tools, &settings, Some(&source_video));
+    let timecode_offset = resolve_timecode_offset(&tools, &settings, fps, Some(&source_video));
+
+    let clip_rows = if let Some(rows) = edited_rows {
+        let normalized = rows
+            .into_iter()
+            .map(|r| ClipRow {
+                // Blank names stay blank; fallback_naming names their files.
+                clip_name: r.clip_name.trim().to_string(),
+                start_time: r.start_time.trim().to_string(),
+                end_time: r.end_time.trim().to_string(),
+                overrides: r.overrides,
+            })
+            .filter(|r| {
+                !(r.clip_name.is_empty() && r.start_time.is_empty() && r.end_time.is_empty())
+            })
+            .collect::<Vec<_>>();
+
+        if normalized.is_empty() {
+            return Err("No editable rows to export. Load a CSV first.".to_string());
+        }
+
+        normalized
+    } else if let Some(csv_path) = csv_path.as_deref() {
+        read_clip_rows(csv_path, &settings.csv_encoding, timecode_offset, fps)?
+    } else {
+        return Err("Provide a CSV file or clip rows to export".to_string());
+    };
+    if clip_rows.is_empty() {
+        return Err("CSV has no rows".to_string());
+    }
+
+    if settings.strict_validation {
+        let mut invalid = Vec::new();
+        validate_clip_rows(
+            &clip_rows,
+            &settings,
+            timecode_offset,
+            fps,
+            probe_duration(&tools, &source_video),
+            &mut invalid,
+            &mut Vec::new(),
+        );
+        if !invalid.is_empty() {
+            return Err(format!(
+                "strict_validation: {} problem(s) found, nothing was exported:\n{}",
+                invalid.len(),
+                invalid.join("\n")
+            ));
+        }
+    }
+
+    let (clip_rows, merged) = match settings.merge_gap_sec {
+        Some(gap) => merge_adjacent_clips(clip_rows, gap, timecode_offset, fps),
+        None => (clip_rows, 0),
+    };
+    let total = clip_rows.len();
+    // Clip lengths weight the overall progress so long clips move the bar more.
+    let video_duration = clip_rows
+        .iter()
+        .any(|row| counts_from_end(&row.start_time) || counts_from_end(&row.end_time))
+        .then(|| probe_duration(&tools, &source_video))
+        .flatten();
+    let row_weights = clip_rows
+        .iter()
+        .map(|row| {
+            let time = |ts: &str| clip_time_seconds(ts, timecode_offset, fps, video_duration);
+            match (time(&row.start_time), time(&row.end_time)) {
+                (Ok(start), Ok(end)) => (end - start).abs(),
+                _ => 0.0,
+            }
+        })
+        .collect::<Vec<_>>();
+    let total_weight = row_weights.iter().sum::<f64>();
+    let progress = RunProgress {
+        log: &progress_log,
+        total,
+        row_weights: &row_weights,
+        total_weight,
+    };
+
+    let output_path = if output_dir.trim().is_empty() {
+        match env_output_dir() {
+            Ok(Some(dir)) => dir,
+            Ok(None) => {
+                return Err(format!(
+                    "No output directory selected and {OUTPUT_DIR_ENV} is not set"
+                ))
+            }
+            Err(err) => return Err(format!("No output directory selected and {err}")),
+        }
+    } else {
+        PathBuf::from(&output_dir)
+    };
+    let mut warnings = Vec::new();
+    if writes_outputs {
+        std::fs::create_dir_all(&output_path)
+            .map_err(|e| format!("Failed to create output directory: {e}"))?;
+    } else if !output_path.is_dir() {
+        warnings.push(format!(
+            "Output directory {} does not exist yet and will be created",
+            output_path.display()
+        ));
+    }
+
+    let mut exported = 0usize;
+    let mut skipped = 0usize;
+    let mut failed = 0usize;
+    let mut errors = Vec::new();
+    let mut row_results = Vec::new();
+    let mut planned_commands = Vec::new();
+    let mut source_durations: Vec<(PathBuf, Option<f64>)> = Vec::new();
+    let mut source_infos: Vec<(PathBuf, Option<VideoInfo>)> = Vec::new();
+    let mut source_keyframes: Vec<(PathBuf, Result<Vec<f64>, String>)> = Vec::new();
+    // Rule and featured settings can't change the run's budget.
+    let run_budget = settings.max_total_duration;
+    let mut budget_used = 0.0f64;
+    let mut budget_included = 0usize;
+    let mut budget_reached = false;
+    let output_mode = settings.output_mode.as_deref().and_then(parse_octal_mode);
+    if output_mode.is_some() && !cfg!(unix) {
+        warnings.push("output_mode is ignored on this platform".to_string());
+    }
+
+    let output_ext = output_extension(
+        &settings.processing_mode,
+        &source_video,
+        settings.output_format.as_deref(),
+    );
+    let concat_all = settings.concat && settings.processing_mode != "webp";
+    if settings.concat && !concat_all {
+        warnings.push("concat does not apply to WebP previews and was ignored".to_string());
+    }
+    let chapters_per_source =
+        settings.chapters_per_source && settings.processing_mode != "webp" && !concat_all;
+    if settings.chapters_per_source && !chapters_per_source {
+        warnings.push(if concat_all {
+            "concat joins every clip into one file, so chapters_per_source was ignored".to_string()
+        } else {
+            "chapters_per_source does not apply to WebP previews and was ignored".to_string()
+        });
+    }
+    // Clips that get joined afterwards are cut into a temp folder first.
+    let joins_clips = chapters_per_source || concat_all;
+    let chapters_dir = output_path.join(".clipchop-chapters");
+    if joins_clips && writes_outputs {
+        std::fs::create_dir_all(&chapters_dir)
+            .map_err(|e| format!("Failed to create temp folder: {e}"))?;
+    }
+    let mut chapter_clips: Vec<(PathBuf, f64, usize)> = Vec::new();
+    let resolve_metadata_path = output_path.join(RESOLVE_METADATA_FILE);
+    if settings.resolve_export && writes_outputs {
+        check_writable_dir(&output_path)?;
+    }
+    let first_index = settings
+        .continue_numbering
+        .then(|| read_manifest(&output_path).and_then(|m| m.next_index))
+        .flatten()
+        .or(settings.start_index)
+        .unwrap_or(1);
+    let mut used_sources: Vec<PathBuf> = Vec::new();
+    if settings.delete_source_on_success && !settings.confirm_delete_source {
+        warnings.push(
+            "delete_source_on_success needs confirm_delete_source; sources will be kept"
+                .to_string(),
+        );
+    }
+
+    let featured_dir = output_path.join("featured");
+    if writes_outputs
+        && clip_rows
+            .iter()
+            .any(|r| row_featured(&r.overrides) == Ok(true))
+    {
+        std::fs::create_dir_all(&featured_dir)
+            .map_err(|e| format!("Failed to create featured folder: {e}"))?;
+    }
+    let source_frame_size = if clip_rows.iter().any(|r| r.overrides.has_crop()) {
+        probe_frame_size(&tools, &source_video)
+    } else {
+        None
+    };
+    if (settings.trim_edge_silence || settings.trim_black)
+        && matches!(settings.processing_mode.as_str(), "copy_fast" | "webp")
+    {
+        warnings.push(
+            "trim_edge_silence/trim_black only apply to re-encoded clips and were ignored for this mode"
+                .to_string(),
+        );
+    }
+    if settings.downmix != "none"
+        && (matches!(settings.processing_mode.as_str(), "copy_fast" | "webp")
+            || settings.audio_codec != "aac")
+    {
+        warnings
+            .push("downmix needs re-encoded AAC audio and was ignored for this mode".to_string());
+    }
+    if settings.replaygain_tag && settings.processing_mode != "copy_fast" {
+        warnings.push(
+            "replaygain_tag only tags Copy Streams clips and was ignored for this mode".to_string(),
+        );
+    }
+    if settings.snap_to_keyframe && settings.processing_mode != "copy_fast" {
+        warnings.push(
+            "snap_to_keyframe only applies to Copy Streams and was ignored for this mode"
+                .to_string(),
+        );
+    }
+    if (settings.muxer_format.is_some() || settings.output_extension.is_some())
+        && settings.processing_mode == "webp"
+    {
+        warnings.push(
+            "muxer_format/output_extension don't apply to WebP previews and were ignored"
+                .to_string(),
+        );
+    }
+    if (settings.denoise != "none" || settings.sharpen) && settings.processing_mode == "copy_fast" {
+        warnings.push(
+            "denoise/sharpen need a re-encode and were ignored for stream-copied clips".to_string(),
+        );
+    }
+    if settings.force_keyframe_at_start
+        && matches!(settings.processing_mode.as_str(), "copy_fast" | "webp")
+    {
+        warnings.push(
+            "force_keyframe_at_start only applies to re-encoded clips and was ignored for this mode"
+                .to_string(),
+        );
+    }
+
+    let fixed_duration = settings.fixed_duration.filter(|_| {
+        exact_fps.is_none() && !matches!(settings.processing_mode.as_str(), "copy_fast" | "webp")
+    });
+    if settings.fixed_duration.is_some() && fixed_duration.is_none() {
+        warnings.push(
+            "fixed_duration only applies to Fast Seek and Precise re-encodes and was ignored"
+                .to_string(),
+        );
+    }
+    let end_hold = Some(settings.end_hold_sec).filter(|hold| {
+        *hold > 0.0
+            && exact_fps.is_none()
+            && !matches!(settings.processing_mode.as_str(), "copy_fast" | "webp")
+    });
+    if settings.end_hold_sec > 0.0 && end_hold.is_none() {
+        warnings.push(if settings.processing_mode == "copy_fast" {
+            "end_hold_sec can't hold the last frame of a Copy Streams clip and was ignored"
+                .to_string()
+        } else {
+            "end_hold_sec only applies to Fast Seek and Precise re-encodes and was ignored"
+                .to_string()
+        });
+    }
+
+    if (settings.fade_in_sec > 0.0 || settings.fade_out_sec > 0.0)
+        && settings.processing_mode == "webp"
+    {
+        warnings.push(
+            "fade_in_sec/fade_out_sec don't apply to WebP previews and were ignored".to_string(),
+        );
+    }
+
+    if settings.fragmented_mp4 && movflags(&output_ext, true).is_none() {
+        warnings.push(format!(
+            "fragmented_mp4 only applies to mp4/mov outputs and was ignored for .{output_ext}"
+        ));
+    }
+    if dry_run
+        && (settings.trim_black
+            || settings.trim_edge_silence
+            || settings.replaygain_tag
+            || settings.loudnorm_two_pass)
+    {
+        warnings.push(
+            "dry_run plans the requested cuts; trim_black, trim_edge_silence, replaygain_tag and loudnorm_two_pass measure each clip during the export"
+                .to_string(),
+        );
+    }
+    if bumpers.is_some() && matches!(settings.processing_mode.as_str(), "copy_fast" | "webp") {
+        warnings.push(
+            "intro_file/outro_file only apply to re-encoded clips and were ignored for this mode"
+                .to_string(),
+        );
+    }
+
+    emit_progress(
+        &progress_log,
+        progress.payload(&row_results, "Starting export...".to_string()),
+    );
+
+    // Plain stream-copy cuts of one video can all come out of a single segment-muxer
+    // pass; each row then only moves its piece into place.
+    let presplit_dir = output_path.join(".clipchop-segments");
+    let mut presplit: Vec<Option<PathBuf>> = Vec::new();
+    if settings.fast_segment_split && writes_outputs {
+        match segment_split_plan(
+            &clip_rows,
+            &settings,
+            &rules,
+            timecode_offset,
+            fps,
+            video_duration,
+            joins_clips,
+        ) {
+            Err(reason) => warnings.push(format!(
+                "fast_segment_split fell back to per-clip cuts: {reason}"
+            )),
+            Ok((boundaries, segments)) => {
+                emit_progress(
+                    &progress_log,
+                    ProgressPayload {
+                        phase: Some("split".to_string()),
+                        ..progress.payload(
+                            &row_results,
+                            format!(
+                                "Splitting the source into {} pieces in one pass...",
+                                boundaries.len() + 1
+                            ),
+                        )
+                    },
+                );
+                match split_at_boundaries(
+                    &tools,
+                    &source_video,
+                    &presplit_dir,
+                    &boundaries,
+                    &output_ext,
+                    &child_state,
+                )
+                .and_then(|starts| check_segments(&starts, &boundaries, fps))
+                {
+                    Ok(()) => {
+                        presplit = segments
+                            .iter()
+                            .map(|segment| {
+                                Some(presplit_dir.join(format!("{segment:05}.{output_ext}")))
+                                    .filter(|path| {
+                                        matches!(std::fs::metadata(path), Ok(m) if m.len() > 0)
+                                    })
+                            })
+                            .collect();
+                    }
+                    Err(err) => warnings.push(format!(
+                        "fast_segment_split fell back to per-clip cuts: {err}"
+                    )),
+                }
+            }
+        }
+    }
+
+    // Rows are planned in order and their ffmpeg runs handed to worker threads.
+    // Finished jobs are collected as they turn up; the loop waits for the next one
+    // when the pool is full, the next row's source is busy under `per_source`, the
+    // rows run out, or the export is stopped.
+    let mut rows = clip_rows.iter().enumerate().peekable();
+    let mut pool = WorkerPool::new();
+    loop {
+        finished_rows.store(row_results.len(), Ordering::SeqCst);
+        done_weight.store(
+            finished_weight(&row_weights, &row_results).to_bits(),
+            Ordering::SeqCst,
+        );
+        let next_source_busy = settings.parallel_scope == "per_source"
+            && rows
+                .peek()
+                .and_then(|(_, row)| {
+                    row_source_video(&row.overrides, &source_video, source_dir.as_deref()).ok()
+                })
+                .map(|next| source_key(&next))
+                .is_some_and(|key| pool.any(|job: &ClipJob| job.source_key == key));
+        let drain = pool.len() >= settings.max_parallel
+            || next_source_busy
+            || rows.peek().is_none()
+            || stop_state.load(Ordering::SeqCst);
+        if let Some((job, joined)) = pool.take_finished(drain) {
+            let ClipJob {
+                idx,
+                row,
+                settings,
+                source_video,
+                destination,
+                row_ext,
+                start_sec,
+                end_sec,
+                audio_offset,
+                times_swapped,
+                featured,
+                loudness_target,
+                trimmed_duration,
+                extended_duration,
+                fade_note,
+                keyframe_start,
+                replaygain_db,
+                rule,
+                composed_of,
+                filters,
+                ..
+            } = job;
+            let JobEnd {
+                succeeded,
+                destination,
+                container_fallback,
+                ffmpeg_log,
+                cleanup_note,
+            } = job_end(idx, destination, joined, &mut errors);
+            if container_fallback {
+                warnings.push(format!(
+                    "Row {}: .{} could not hold the copied streams, remuxed to .mkv instead",
+                    idx + 2,
+                    row_ext
+                ));
+            }
+
+            // A clip that made it to its final name before the stop still counts.
+            let stopping = stop_state.load(Ordering::SeqCst);
+            if stopping && !succeeded {
+                failed += 1;
+                let message = format!("Stopped while exporting row {}{}", idx + 2, cleanup_note);
+                errors.push(message.clone());
+                row_results.push(RowResult::new(idx, &row.clip_name, "failed"));
+                emit_row_progress(
+                    &progress,
+                    &row_results,
+                    idx,
+                    &row.clip_name,
+                    message,
+                    "failed",
+                    None,
+                );
+                continue;
+            }
+
+            if succeeded {
+                exported += 1;
+                let mut result = RowResult::new(idx, &row.clip_name, "success");
+                result.output_path = Some(destination.to_string_lossy().to_string());
+                result.output_size_bytes = std::fs::metadata(&destination).ok().map(|m| m.len());
+                result.audio_offset_ms = audio_offset;
+                result.times_swapped = times_swapped;
+                result.featured = featured;
+                result.loudness_target = loudness_target;
+                result.container_fallback = container_fallback.then(|| "mkv".to_string());
+                result.trimmed_duration_sec = trimmed_duration;
+                result.extended_duration_sec = extended_duration;
+                result.keyframe_start_sec = keyframe_start;
+                result.replaygain_db = replaygain_db;
+                result.adjusted_start_sec = trimmed_duration.map(|_| start_sec);
+                result.adjusted_end_sec = trimmed_duration.map(|_| end_sec);
+                result.rule = rule;
+                result.composed_of = composed_of;
+                if settings.resolve_export && !joins_clips {
+                    let reel = source_video
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let file_name = destination
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let record = [
+                        file_name,
+                        row.clip_name.clone(),
+                        reel,
+                        format!("{:03}", first_index + idx),
+                        take_from_name(&row.clip_name),
+                    ];
+                    match append_resolve_metadata(&resolve_metadata_path, &record) {
+                        Ok(()) => {
+                            result.metadata_sidecar =
+                                Some(resolve_metadata_path.to_string_lossy().to_string());
+                        }
+                        Err(err) => warnings.push(format!("Row {}: {}", idx + 2, err)),
+                    }
+                }
+                if let Some(target) = fixed_duration {
+                    result.requested_duration_sec = Some(target);
+                    result.actual_duration_sec = probe_duration(&tools, &destination);
+                }
+                if !used_sources.contains(&source_video) {
+                    used_sources.push(source_video.clone());
+                }
+                if joins_clips {
+                    chapter_clips.push((source_video.clone(), start_sec, row_results.len()));
+                }
+                if settings.export_bookends && !joins_clips && !stopping {
+                    emit_row_progress(
+                        &progress,
+                        &row_results,
+                        idx,
+                        &row.clip_name,
+                        format!("Extracting bookend frames for clip {}", idx + 1),
+                        "running",
+                        Some("bookends"),
+                    );
+                    let frame_sec = 1.0 / exact_fps.or(settings.fps).unwrap_or(30.0);
+                    let bookends = [
+                        ("first", start_sec),
+                        ("last", (end_sec - frame_sec).max(start_sec)),
+                    ];
+                    for (label, at_sec) in bookends {
+                        let image = sidecar_path(&destination, label, "jpg");
+                        match extract_frame(
+                            &tools,
+                            &child_state,
+                            &source_video,
+                            at_sec,
+                            settings.bookend_width,
+                            &filters,
+                            &image,
+                        ) {
+                            Ok(()) => {
+                                let image = Some(image.to_string_lossy().to_string());
+                                if label == "first" {
+                                    result.first_frame_path = image;
+                                } else {
+                                    result.last_frame_path = image;
+                                }
+                            }
+                            Err(err) => warnings.push(format!(
+                                "Row {}: {} frame not extracted: {}",
+                                idx + 2,
+                                label,
+                                err
+                            )),
+                        }
+                    }
+                }
+                if settings.generate_thumbnail && !joins_clips && !stopping {
+                    let frame_sec = 1.0 / exact_fps.or(settings.fps).unwrap_or(30.0);
+                    let image = destination.with_extension("jpg");
+                    match extract_frame(
+                        &tools,
+                        &child_state,
+                        &source_video,
+                        thumbnail_time(&settings, start_sec, end_sec, frame_sec),
+                        settings.thumbnail_width,
+                        &filters,
+                        &image,
+                    ) {
+                        Ok(()) => {
+                            result.thumbnail_path = Some(image.to_string_lossy().to_string());
+                        }
+                        Err(err) => warnings.push(format!(
+                            "Row {}: thumbnail not extracted: {}",
+                            idx + 2,
+                            err
+                        )),
+                    }
+                }
+                // Before output_mode, which may make the file read-only.
+                if settings.preserve_mtime {
+                    if let Err(err) = copy_mtime(&source_video, &destination) {
+                        warnings.push(format!("Row {}: {}", idx + 2, err));
+                    }
+                }
+                if let Some(mode) = output_mode.filter(|_| cfg!(unix)) {
+                    if let Err(err) = apply_output_mode(&destination, mode) {
+                        warnings.push(format!("Row {}: {}", idx + 2, err));
+                    }
+                }
+                row_results.push(result);
+            } else {
+                failed += 1;
+                if ffmpeg_log.is_empty() {
+                    errors.push(format!(
+                        "Row {} failed ({}){}",
+                        idx + 2,
+                        row.clip_name,
+                        cleanup_note
+                    ));
+                } else {
+                    errors.push(format!(
+                        "Row {} failed ({}){}:\n{}",
+                        idx + 2,
+                        row.clip_name,
+                        cleanup_note,
+                        ffmpeg_log
+                    ));
+                }
+                row_results.push(RowResult::new(idx, &row.clip_name, "failed"));
+            }
+
+            let message = if succeeded {
+                format!(
+                    "Finished clip {} of {}{}",
+                    idx + 1,
+                    total,
+                    fade_note.unwrap_or_default()
+                )
+            } else if ffmpeg_log.is_empty() {
+                format!("Clip {} of {} failed{}", idx + 1, total, cleanup_note)
+            } else {
+                format!(
+                    "Clip {} of {} failed{}:\n{}",
+                    idx + 1,
+                    total,
+                    cleanup_note,
+                    ffmpeg_log
+                )
+            };
+            let row_state = if succeeded { "success" } else { "failed" };
+            emit_row_progress(
+                &progress,
+                &row_results,
+                idx,
+                &row.clip_name,
+                message,
+                row_state,
+                None,
+            );
+            continue;
+        }
+
+        let Some((idx, row)) = rows.next() else {
+            break;
+        };
+        if stop_state.load(Ordering::SeqCst) {
+            emit_progress(
+                &progress_log,
+                ProgressPayload {
+                    current_clip: row.clip_name.clone(),
+                    status: "stopped".to_string(),
+                    row_index: Some(idx),
+                    row_result: Some("failed".to_string()),
+                    ..progress.payload(&row_results, "Export stopped by user".to_string())
+                },
+            );
+            break;
+        }
+
+        // `end`/`eof` and negative times count back from the end of the row's video.
+        let row_duration = if counts_from_end(&row.start_time) || counts_from_end(&row.end_time) {
+            row_source_video(&row.overrides, &source_video, source_dir.as_deref())
+                .ok()
+                .and_then(|source| cached_duration(&tools, &mut source_durations, &source))
+        } else {
+            None
+        };
+        let start_sec = match clip_time_seconds(&row.start_time, timecode_offset, fps, row_duration)
+        {
+            Ok(v) => v,
+            Err(reason) => {
+                skip_row(
+                    &progress,
+                    &mut row_results,
+                    &mut errors,
+                    &mut skipped,
+                    idx,
+                    &row.clip_name,
+                    &format!("start time {reason}"),
+                );
+                continue;
+            }
+        };
+
+        let end_sec = match clip_time_seconds(&row.end_time, timecode_offset, fps, row_duration) {
+            Ok(v) => v,
+            Err(reason) => {
+                skip_row(
+                    &progress,
+                    &mut row_results,
+                    &mut errors,
+                    &mut skipped,
+                    idx,
+                    &row.clip_name,
+                    &format!("end time {reason}"),
+                );
+                continue;
+            }
+        };
+
+        let times_swapped = settings.autoswap_times && end_sec < start_sec;
+        let (start_sec, end_sec) = if times_swapped {
+            warnings.push(format!(
+                "Row {}: end time is before start time, swapped them",
+                idx + 2
+            ));
+            (end_sec, start_sec)
+        } else {
+            (start_sec, end_sec)
+        };
+
+        if end_sec <= start_sec {
+            skip_row(
+                &progress,
+                &mut row_results,
+                &mut errors,
+                &mut skipped,
+                idx,
+                &row.clip_name,
+                "end time must be greater than start time",
+            );
+            continue;
+        }
+
+        let clip_length = fixed_duration.unwrap_or(end_sec - start_sec) + end_hold.unwrap_or(0.0);
+
+        let featured = match row_featured(&row.overrides) {
+            Ok(featured) => featured,
+            Err(reason) => {
+                skip_row(
+                    &progress,
+                    &mut row_results,
+                    &mut errors,
+                    &mut skipped,
+                    idx,
+                    &row.clip_name,
+                    &reason,
+                );
+                continue;
+            }
+        };
+        let rule = matching_rule(&rules, &row.clip_name);
+        let settings = if let Some(rule) = rule {
+            &rule.settings
+        } else if featured {
+            &featured_settings
+        } else {
+            &settings
+        };
+        let row_settings = match row_settings(&row.overrides, settings) {
+            Ok(row_settings) => row_settings,
+            Err(reason) => {
+                skip_row(
+                    &progress,
+                    &mut row_results,
+                    &mut errors,
+                    &mut skipped,
+                    idx,
+                    &row.clip_name,
+                    &reason,
+                );
+                continue;
+            }
+        };
+        let settings = &row_settings;
+
+        let source_video =
+            match row_source_video(&row.overrides, &source_video, source_dir.as_deref()) {
+                Ok(path) => path,
+                Err(reason) => {
+                    skip_row(
+                        &progress,
+                        &mut row_results,
+                        &mut errors,
+                        &mut skipped,
+                        idx,
+                        &row.clip_name,
+                        &reason,
+                    );
+                    continue;
+                }
+            };
+
+        let (start_sec, end_sec, clip_length) = if settings.clamp_to_source {
+            match cached_duration(&tools, &mut source_durations, &source_video) {
+                Some(duration) if start_sec >= duration => {
+                    skip_row(
+                        &progress,
+                        &mut row_results,
+                        &mut errors,
+                        &mut skipped,
+                        idx,
+                        &row.clip_name,
+                        &format!(
+                            "clip starts at {}, past the end of the video ({})",
+                            format_timestamp(start_sec),
+                            format_timestamp(duration)
+                        ),
+                    );
+                    continue;
+                }
+                Some(duration) if end_sec > duration => {
+                    warnings.push(format!(
+                        "Row {}: end time clamped to the video length {}",
+                        idx + 2,
+                        format_timestamp(duration)
+                    ));
+                    let clip_length = fixed_duration
+                        .unwrap_or(duration - start_sec)
+                        .min(duration - start_sec)
+                        + end_hold.unwrap_or(0.0);
+                    (start_sec, duration, clip_length)
+                }
+                Some(_) => (start_sec, end_sec, clip_length),
+                None => {
+                    warnings.push(format!(
+                        "Row {}: could not read the video length, so the clip was not clamped",
+                        idx + 2
+                    ));
+                    (start_sec, end_sec, clip_length)
+                }
+            }
+        } else {
+            (start_sec, end_sec, clip_length)
+        };
+
+        // Checked once the row's settings and clamped times are known, so a row is
+        // charged only what it will actually export.
+        if let Some(budget) = run_budget {
+            if budget_reached || budget_used + clip_length > budget {
+                if !budget_reached {
+                    warnings.push(format!(
+                        "max_total_duration of {budget}s reached at row {}; remaining rows were skipped",
+                        idx + 2
+                    ));
+                }
+                budget_reached = true;
+                skipped += 1;
+                row_results.push(RowResult::new(idx, &row.clip_name, "skipped (budget)"));
+                emit_row_progress(
+                    &progress,
+                    &row_results,
+                    idx,
+                    &row.clip_name,
+                    format!(
+                        "Row {} skipped: max_total_duration of {budget}s reached",
+                        idx + 2
+                    ),
+                    "skipped",
+                    None,
+                );
+                continue;
+            }
+        }
+
+        let crop = match row_crop(&row.overrides, source_frame_size) {
+            Ok(crop) => crop,
+            Err(reason) => {
+                skip_row(
+                    &progress,
+                    &mut row_results,
+                    &mut errors,
+                    &mut skipped,
+                    idx,
+                    &row.clip_name,
+                    &reason,
+                );
+                continue;
+            }
+        };
+        let audio_offset_ms = match row_audio_offset(&row.overrides, settings.audio_offset_ms) {
+            Ok(offset) => offset,
+            Err(reason) => {
+                skip_row(
+                    &progress,
+                    &mut row_results,
+                    &mut errors,
+                    &mut skipped,
+                    idx,
+                    &row.clip_name,
+                    &reason,
+                );
+                continue;
+            }
+        };
+        let audio_offset =
+            Some(audio_offset_ms).filter(|ms| *ms != 0 && settings.audio_codec != "none");
+        let loudness_target = match row_loudness_target(&row.overrides, settings.loudness_target) {
+            Ok(target) => target,
+            Err(reason) => {
+                skip_row(
+                    &progress,
+                    &mut row_results,
+                    &mut errors,
+                    &mut skipped,
+                    idx,
+                    &row.clip_name,
+                    &reason,
+                );
+                continue;
+            }
+        };
+
+        // Row `mode` cells can ask for Copy Streams on a run that crops, labels or fades.
+        let needs_reencode = crop.is_some()
+            || has_settings_crop(settings)
+            || settings.social_preset.is_some()
+            || settings.watermark_image.is_some()
+            || settings.burn_subtitle_track.is_some()
+            || settings.burn_label
+            || settings.fade_in_sec > 0.0
+            || settings.fade_out_sec > 0.0
+            || audio_offset.is_some();
+        let mode = if needs_reencode && settings.processing_mode == "copy_fast" {
+            "reencode_precise"
+        } else {
+            settings.processing_mode.as_str()
+        };
+        let muxer = settings
+            .muxer_format
+            .as_deref()
+            .filter(|_| mode != "webp")
+            .and_then(muxer_name);
+        let row_ext = match settings.output_extension.clone() {
+            Some(ext) if mode != "webp" => ext,
+            _ => output_extension(mode, &source_video, settings.output_format.as_deref()),
+        };
+        // movflags follow the muxer actually writing the file, not the extension.
+        let container = match muxer {
+            Some(_) => settings.muxer_format.clone().unwrap_or_default(),
+            None => row_ext.clone(),
+        };
+        let audio_only = is_audio_format(&row_ext);
+        let (encoder, audio_encoder) = container_encoders(&container, settings);
+        let loudness_target = match loudness_target {
+            Some(_)
+                if matches!(mode, "copy_fast" | "webp")
+                    || matches!(settings.audio_codec.as_str(), "copy" | "none") =>
+            {
+                warnings.push(format!(
+                    "Row {}: loudness target needs re-encoded AAC audio and was not applied",
+                    idx + 2
+                ));
+                None
+            }
+            target => target,
+        };
+
+        let cleaned_name = clean_filename(&row.clip_name);
+        let safe_name = if cleaned_name.is_empty() {
+            sanitize_filename(&fallback_clip_name(
+                &settings.fallback_naming,
+                first_index + idx,
+                &source_video,
+                start_sec,
+                end_sec,
+            ))
+        } else {
+            cleaned_name
+        };
+        let start_label = if times_swapped {
+            &row.end_time
+        } else {
+            &row.start_time
+        }
+        .replace(':', "");
+        let hash_suffix = settings
+            .unique_suffix
+            .map(|len| format!("-{}", clip_hash(&source_video, start_sec, end_sec, len)))
+            .unwrap_or_default();
+        let file_name = match settings.filename_template.as_deref() {
+            Some(template) => {
+                let end_label = if times_swapped {
+                    &row.start_time
+                } else {
+                    &row.end_time
+                }
+                .replace(':', "");
+                let tokens = FilenameTokens {
+                    index: first_index + idx,
+                    name: &safe_name,
+                    start: &start_label,
+                    end: &end_label,
+                    duration_sec: end_sec - start_sec,
+                    ext: &row_ext,
+                };
+                templated_file_name(template, &tokens, &hash_suffix)
+            }
+            None => format!(
+                "{:03}-{}-{}{}.{}",
+                first_index + idx,
+                safe_name,
+                start_label,
+                hash_suffix,
+                row_ext
+            ),
+        };
+        let mut destination = if joins_clips {
+            chapters_dir.join(file_name)
+        } else if featured {
+            featured_dir.join(file_name)
+        } else {
+            output_path.join(file_name)
+        };
+        // rename_on_collision predates on_conflict and keeps its own suffix.
+        if settings.on_conflict == "rename"
+            || (settings.rename_on_collision && settings.on_conflict != "skip")
+        {
+            let suffix = if settings.rename_on_collision {
+                settings.collision_suffix.as_str()
+            } else {
+                " ({n})"
+            };
+            destination = unique_destination(&destination, suffix);
+        }
+        if settings.on_conflict == "skip" && !joins_clips && destination.exists() {
+            skipped += 1;
+            let mut result = RowResult::new(idx, &row.clip_name, "skipped (exists)");
+            result.output_path = Some(destination.to_string_lossy().to_string());
+            row_results.push(result);
+            emit_row_progress(
+                &progress,
+                &row_results,
+                idx,
+                &row.clip_name,
+                format!(
+                    "Row {} skipped: {} already exists",
+                    idx + 2,
+                    destination.display()
+                ),
+                "skipped",
+                None,
+            );
+            continue;
+        }
+
+        if validate_only {
+            let source_duration = cached_duration(&tools, &mut source_durations, &source_video);
+            let mut result = RowResult::new(idx, &row.clip_name, "valid");
+            result.output_path = Some(destination.to_string_lossy().to_string());
+            result.featured = featured;
+            result.times_swapped = times_swapped;
+            let message = match source_duration.filter(|d| end_sec > *d) {
+                Some(duration) => {
+                    skipped += 1;
+                    result.status = "invalid".to_string();
+                    let err = format!(
+                        "Row {}: end time {} exceeds video length {}",
+                        idx + 2,
+                        row.end_time,
+                        format_timestamp(duration)
+                    );
+                    errors.push(err.clone());
+                    err
+                }
+                None => format!("Validated clip {} of {}", idx + 1, total),
+            };
+            let row_state = if result.status == "invalid" {
+                "failed"
+            } else {
+                "pending"
+            };
+            row_results.push(result);
+            emit_row_progress(
+                &progress,
+                &row_results,
+                idx,
+                &row.clip_name,
+                message,
+                row_state,
+                Some("validate"),
+            );
+            continue;
+        }
+
+        // A stream copy starts on a keyframe anyway; cutting there explicitly keeps
+        // the requested end and avoids frozen or black leading frames. File names
+        // still use the requested start.
+        let keyframe_start = if settings.snap_to_keyframe && mode == "copy_fast" {
+            cached_keyframe(&tools, &mut source_keyframes, &source_video, start_sec).unwrap_or_else(
+                |err| {
+                    warnings.push(format!(
+                        "Row {}: could not snap to a keyframe: {}",
+                        idx + 2,
+                        err
+                    ));
+                    None
+                },
+            )
+        } else {
+            None
+        };
+        let start_sec = keyframe_start.unwrap_or(start_sec);
+        // Exact-frame cuts decode from the keyframe before the clip rather than
+        // from the start of the video.
+        let exact_seek = if exact_fps.is_some()
+            && matches!(mode, "reencode_precise" | "reencode_fast_seek")
+        {
+            cached_keyframe(&tools, &mut source_keyframes, &source_video, start_sec)
+                .unwrap_or_else(|err| {
+                    warnings.push(format!(
+                        "Row {}: could not find the keyframe before the clip, so exact_frames decodes from the start of the video: {}",
+                        idx + 2,
+                        err
+                    ));
+                    None
+                })
+        } else {
+            None
+        };
+
+        let reencoded = !matches!(mode, "copy_fast" | "webp");
+        let source_info = if reencoded {
+            match source_infos.iter().find(|(s, _)| *s == source_video) {
+                Some((_, info)) => info.clone(),
+                None => {
+                    let info = probe_video(&tools, &source_video).ok();
+                    source_infos.push((source_video.clone(), info.clone()));
+                    info
+                }
+            }
+        } else {
+            None
+        };
+        let row_bumpers = bumpers
+            .as_ref()
+            .filter(|_| reencoded && !audio_only)
+            .map(|b| Bumpers {
+                encoder: encoder.to_string(),
+                audio_encoder: audio_encoder.to_string(),
+                preset: settings.preset.clone(),
+                crf: settings.crf,
+                audio_bitrate_kbps: settings.audio_bitrate_kbps,
+                movflags: movflags(&container, settings.fragmented_mp4).map(str::to_string),
+                ..b.clone()
+            });
+        let composed_of = row_bumpers.as_ref().map(|b| {
+            let clip = destination
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            b.intro
+                .iter()
+                .map(|part| part.path.to_string_lossy().to_string())
+                .chain(std::iter::once(clip))
+                .chain(
+                    b.outro
+                        .iter()
+                        .map(|part| part.path.to_string_lossy().to_string()),
+                )
+                .collect::<Vec<_>>()
+        });
+        let spec = ClipSpec {
+            settings,
+            tools: &tools,
+            mode,
+            source: &source_video,
+            clip_name: &row.clip_name,
+            start_sec,
+            end_sec,
+            exact_fps,
+            exact_seek,
+            fixed_duration,
+            end_hold,
+            crop: crop.as_ref(),
+            audio_offset,
+            source_info: source_info.as_ref(),
+            container: &container,
+            row_ext: &row_ext,
+            muxer,
+            encoder,
+            audio_encoder,
+            loudness_target,
+            loudnorm_measured: None,
+            replaygain: None,
+        };
+        // The row is in: its share of the budget is taken here, in row order, so
+        // which rows fit never depends on when other encodes finish or fail.
+        budget_used += clip_length;
+        budget_included += 1;
+
+        // Dry runs plan the requested cut: black/silence trimming and loudness
+        // measurements read the source and only happen during the export.
+        if dry_run {
+            let clip = clip_command(&spec);
+            warnings.extend(
+                clip.warnings
+                    .iter()
+                    .map(|warning| format!("Row {}: {}", idx + 2, warning)),
+            );
+            let planned = planned_command(idx, &destination, &clip, row_bumpers.as_ref(), &spec);
+            let output_path = planned.output_path.clone();
+            planned_commands.push(planned);
+            let mut result = RowResult::new(idx, &row.clip_name, "planned");
+            result.output_path = Some(output_path);
+            result.featured = featured;
+            result.times_swapped = times_swapped;
+            result.composed_of = composed_of;
+            row_results.push(result);
+            emit_row_progress(
+                &progress,
+                &row_results,
+                idx,
+                &row.clip_name,
+                format!("Planned clip {} of {}", idx + 1, total),
+                "pending",
+                Some("plan"),
+            );
+            continue;
+        }
+
+        emit_row_progress(
+            &progress,
+            &row_results,
+            idx,
+            &row.clip_name,
+            format!("Exporting clip {} of {}", idx + 1, total),
+            "running",
+            Some("encode"),
+        );
+
+        // Tighten the cut itself rather than filtering, so picture and sound stay
+        // in sync. Black frames are trimmed first, then edge silence.
+        let original_cut = (start_sec, end_sec);
+        let (start_sec, end_sec) = if settings.trim_black && reencoded {
+            match detect_black_periods(
+                &tools,
+                &child_state,
+                &source_video,
+                Some((start_sec, end_sec)),
+                settings.black_threshold,
+            ) {
+                Ok(periods) => {
+                    let (lead, trail) = edge_periods(&periods, end_sec - start_sec);
+                    tighten_cut(
+                        start_sec,
+                        end_sec,
+                        lead.min(settings.black_max_trim_sec),
+                        trail.min(settings.black_max_trim_sec),
+                    )
+                }
+                Err(err) => {
+                    warnings.push(format!(
+                        "Row {}: black detection failed, kept the original cut: {}",
+                        idx + 2,
+                        err
+                    ));
+                    (start_sec, end_sec)
+                }
+            }
+        } else {
+            (start_sec, end_sec)
+        };
+        let (start_sec, end_sec) = if settings.trim_edge_silence && reencoded {
+            match detect_silence_periods(
+                &tools,
+                &child_state,
+                &source_video,
+                start_sec,
+                end_sec,
+                settings.silence_threshold_db,
+            ) {
+                Ok(periods) => {
+                    let (lead, trail) = edge_periods(&periods, end_sec - start_sec);
+                    tighten_cut(
+                        start_sec,
+                        end_sec,
+                        lead.min(settings.silence_max_trim_sec),
+                        trail.min(settings.silence_max_trim_sec),
+                    )
+                }
+                Err(err) => {
+                    warnings.push(format!(
+                        "Row {}: silence detection failed, kept the original cut: {}",
+                        idx + 2,
+                        err
+                    ));
+                    (start_sec, end_sec)
+                }
+            }
+        } else {
+            (start_sec, end_sec)
+        };
+        let trimmed_duration =
+            ((start_sec, end_sec) != original_cut).then_some(end_sec - start_sec);
+        let duration = end_sec - start_sec;
+
+        // Measured on the cut itself so the tag describes the clip, not the source.
+        let replaygain = if settings.replaygain_tag && mode == "copy_fast" {
+            if !supports_gain_tags(&container) {
+                warnings.push(format!(
+                    "Row {}: .{} can't hold ReplayGain tags, so none were written",
+                    idx + 2,
+                    container
+                ));
+                None
+            } else {
+                match measure_replaygain(&tools, &child_state, &source_video, start_sec, duration) {
+                    Ok(gain) => Some(gain),
+                    Err(err) => {
+                        warnings.push(format!(
+                            "Row {}: loudness measurement failed, no ReplayGain tag written: {}",
+                            idx + 2,
+                            err
+                        ));
+                        None
+                    }
+                }
+            }
+        } else {
+            None
+        };
+        // The second loudnorm pass needs the clip's own measurements.
+        let loudnorm_measured = match loudness_target.filter(|_| settings.loudnorm_two_pass) {
+            Some(target) => match measure_loudnorm(
+                &tools,
+                &child_state,
+                &source_video,
+                start_sec,
+                duration,
+                &format!("loudnorm=I={target}:TP=-1.5:LRA=11:print_format=json"),
+            ) {
+                // Silent clips measure -inf, which the second pass can't take.
+                Ok(stats) if stats.input_i.parse::<f64>().is_ok_and(f64::is_finite) => Some(stats),
+                Ok(_) => None,
+                Err(err) => {
+                    warnings.push(format!(
+                        "Row {}: loudness measurement failed, normalized in one pass: {}",
+                        idx + 2,
+                        err
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let ClipCommand {
+            cmd,
+            fallbacks,
+            filters,
+            output_duration,
+            reports_progress,
+            fade_note,
+            warnings: clip_warnings,
+        } = clip_command(&ClipSpec {
+            start_sec,
+            end_sec,
+            loudnorm_measured: loudnorm_measured.as_ref(),
+            replaygain,
+            ..spec
+        });
+        warnings.extend(
+            clip_warnings
+                .iter()
+                .map(|warning| format!("Row {}: {}", idx + 2, warning)),
+        );
+        let segment = presplit
+            .get(idx)
+            .cloned()
+            .flatten()
+            .filter(|_| mode == "copy_fast");
+        let handle = if let Some(segment) = segment {
+            let destination = destination.clone();
+            std::thread::spawn(move || take_segment(&segment, destination))
+        } else {
+            let children = child_state.clone();
+            let stop_state = stop_state.clone();
+            let destination = destination.clone();
+            let locked_retries = settings.locked_retries;
+            let on_progress = reports_progress.then(|| {
+                let progress_log = progress_log.clone();
+                let finished_rows = finished_rows.clone();
+                let done_weight = done_weight.clone();
+                let clip_weight = row_weights[idx];
+                let clip_name = row.clip_name.clone();
+                Box::new(move |out_sec: f64| {
+                    let fraction = (out_sec / output_duration).clamp(0.0, 1.0);
+                    emit_progress(
+                        &progress_log,
+                        ProgressPayload {
+                            total,
+                            completed: finished_rows.load(Ordering::SeqCst),
+                            current_clip: clip_name.clone(),
+                            status: "running".to_string(),
+                            message: format!(
+                                "Exporting clip {} of {} ({:.0}%)",
+                                idx + 1,
+                                total,
+                                fraction * 100.0
+                            ),
+                            row_index: Some(idx),
+                            row_result: Some("running".to_string()),
+                            phase: Some("encode".to_string()),
+                            clip_progress: Some(fraction),
+                            weighted_percent: weighted_percent(
+                                f64::from_bits(done_weight.load(Ordering::SeqCst))
+                                    + fraction * clip_weight,
+                                total_weight,
+                            ),
+                        },
+                    );
+                }) as Box<dyn Fn(f64) + Send>
+            });
+            let tools = tools.clone();
+            std::thread::spawn(move || {
+                run_clip_job(
+                    &tools,
+                    cmd,
+                    &children,
+                    &stop_state,
+                    destination,
+                    fallbacks,
+                    row_bumpers,
+                    locked_retries,
+                    on_progress,
+                )
+            })
+        };
+        let job = ClipJob {
+            idx,
+            row: row.clone(),
+            settings: settings.clone(),
+            source_key: source_key(&source_video),
+            source_video,
+            destination,
+            row_ext,
+            start_sec,
+            end_sec,
+            audio_offset,
+            times_swapped,
+            featured,
+            loudness_target,
+            trimmed_duration,
+            extended_duration: end_hold.map(|_| output_duration),
+            fade_note,
+            keyframe_start,
+            replaygain_db: replaygain.map(|(gain, _)| gain),
+            rule: rule.map(|rule| rule.name.clone()),
+            composed_of,
+            filters,
+        };
+        pool.push(job, handle);
+    }
+    if settings.fast_segment_split && presplit_dir.exists() {
+        // Pieces between clips, and those of skipped rows, are never moved out.
+        let _ = std::fs::remove_dir_all(&presplit_dir);
+    }
+
+    let mut source_outputs = Vec::new();
+    if joins_clips && !stop_state.load(Ordering::SeqCst) {
+        // Per source, clips are joined in timeline order; concat joins everything
+        // into one file in CSV order.
+        let mut groups: Vec<(PathBuf, Vec<(f64, usize)>)> = Vec::new();
+        for (source, start_sec, result_idx) in chapter_clips {
+            let (source, order) = if concat_all {
+                (
+                    PathBuf::from(CONCAT_OUTPUT_STEM),
+                    row_results[result_idx].row_index as f64,
+                )
+            } else {
+                (source, start_sec)
+            };
+            match groups.iter_mut().find(|(s, _)| *s == source) {
+                Some((_, clips)) => clips.push((order, result_idx)),
+                None => groups.push((source, vec![(order, result_idx)])),
+            }
+        }
+
+        let mut join_failed = false;
+        for (source, mut clips) in groups {
+            clips.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let parts = clips
+                .iter()
+                .filter_map(|(_, i)| {
+                    let result = &row_results[*i];
+                    let overrides = &clip_rows.get(result.row_index)?.overrides;
+                    let path = PathBuf::from(result.output_path.as_deref()?);
+                    let title = overrides
+                        .title
+                        .clone()
+                        .or_else(|| Some(result.clip_name.clone()).filter(|n| !n.is_empty()))
+                        .or_else(|| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+                        .unwrap_or_default();
+                    Some(ChapterPart {
+                        path,
+                        title,
+                        description: overrides.description.clone(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let stem = source
+                .file_stem()
+                .map(|s| sanitize_filename(&s.to_string_lossy()))
+                .unwrap_or_else(|| "source".to_string());
+
+            emit_progress(
+                &progress_log,
+                ProgressPayload {
+                    completed: total,
+                    current_clip: stem.clone(),
+                    phase: Some("concat".to_string()),
+                    ..progress.payload(
+                        &row_results,
+                        format!("Joining {} clips from {}", parts.len(), stem),
+                    )
+                },
+            );
+
+            let ext = parts
+                .first()
+                .and_then(|p| p.path.extension())
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .filter(|e| concat_all || supports_chapters(e))
+                .unwrap_or_else(|| "mkv".to_string());
+            let destination = output_path.join(format!("{stem}.{ext}"));
+
+            match join_with_chapters(
+                &tools,
+                &child_state,
+                &parts,
+                &destination,
+                &chapters_dir,
+                &settings,
+            ) {
+                Ok(reencoded) => {
+                    for (_, i) in &clips {
+                        row_results[*i].output_path = None;
+                    }
+                    source_outputs.push(SourceOutput {
+                        source: source.to_string_lossy().to_string(),
+                        output_path: destination.to_string_lossy().to_string(),
+                        clip_count: parts.len(),
+                        reencoded,
+                    });
+                }
+                Err(err) => {
+                    join_failed = true;
+                    errors.push(format!("Joining clips from {} failed: {}", stem, err));
+                }
+            }
+        }
+
+        if join_failed {
+            warnings.push(format!(
+                "Cut clips were kept in {} because a join failed",
+                chapters_dir.display()
+            ));
+        } else {
+            let _ = std::fs::remove_dir_all(&chapters_dir);
+        }
+    }
+
+    let mut deleted_sources = Vec::new();
+    let all_succeeded = exported == total && skipped == 0 && failed == 0 && errors.is_empty();
+    if settings.delete_source_on_success
+        && settings.confirm_delete_source
+        && writes_outputs
+        && all_succeeded
+        && !stop_state.load(Ordering::SeqCst)
+    {
+        for source in &used_sources {
+            match std::fs::remove_file(source) {
+                Ok(()) => deleted_sources.push(source.to_string_lossy().to_string()),
+                Err(e) => warnings.push(format!(
+                    "Failed to delete source {}: {}",
+                    source.display(),
+                    e
+                )),
+            }
+        }
+    }
+
+    if writes_outputs {
+        if let Err(err) = update_manifest(
+            &output_path,
+            &clip_rows,
+            &row_results,
+            first_index + clip_rows.len(),
+            &progress_log.run_id,
+        ) {
+            warnings.push(err);
+        }
+    }
+
+    let status = if stop_state.load(Ordering::SeqCst) {
+        "stopped"
+    } else {
+        "done"
+    };
+
+    if let Some(path) = settings.report_path.as_deref() {
+        let report = ExportReport {
+            run_id: progress_log.run_id.clone(),
+            started_at_unix: started_at,
+            finished_at_unix: unix_seconds(),
+            status: status.to_string(),
+            total_rows: total,
+            exported,
+            skipped,
+            failed,
+            settings: settings.clone(),
+            rows: report_rows(&clip_rows, &row_results, &errors),
+            errors: errors.clone(),
+            warnings: warnings.clone(),
+        };
+        if let Err(err) = write_export_report(Path::new(path), &report) {
+            warnings.push(err);
+        }
+    }
+
+    emit_progress(
+        &progress_log,
+        ProgressPayload {
+            completed: exported + failed + skipped,
+            status: status.to_string(),
+            ..progress.payload(
+                &row_results,
+                format!(
+                    "Done. Exported: {}, Skipped: {}, Failed: {}",
+                    exported, skipped, failed
+                ),
+            )
+        },
+    );
+
+    row_results.sort_by_key(|r| r.row_index);
+    Ok(RunSummary {
+        run_id: progress_log.run_id.clone(),
+        total_rows: total,
+        exported,
+        skipped,
+        failed,
+        errors,
+        warnings,
+        merged,
+        rows: row_results,
+        source_outputs,
+        validate_only,
+        dry_run,
+        planned_commands,
+        deleted_sources,
+        budget_included: settings.max_total_duration.map(|_| budget_included),
+        budget_used_sec: settings.max_total_duration.map(|_| budget_used),
+    })
+}
+
+fn join_with_chapters(
+    tools: &Tools,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+    parts: &[ChapterPart],
+    destination: &Path,
+    work_dir: &Path,
+    settings: &ExportSettings,
+) -> Result<bool, String> {
+    let mut list = String::new();
+    let mut durations = Vec::new();
+    let mut infos = Vec::new();
+
+    for part in parts {
+        let path = &part.path;
+        // A newline in a path would start a new directive in the concat list.
+        check_filter_text(&path.to_string_lossy(), "Clip path")?;
+        let duration = probe_duration(tools, path)
+            .ok_or_else(|| format!("Could not read duration of {}", path.display()))?;
+        list.push_str(&format!(
+            "file '{}'\n",
+            path.to_string_lossy().replace('\'', "'\\''")
+        ));
+        durations.push(duration);
+        infos.push(probe_video(tools, path)?);
+    }
+
+    // The concat demuxer can only stream-copy parts with matching codec and
+    // frame size; anything else is re-encoded to the first part's size.
+    let first = infos.first().cloned().unwrap_or_default();
+    let reencode = infos.iter().any(|info| {
+        info.video_codec != first.video_codec
+            || info.width != first.width
+            || info.height != first.height
+            || info.audio_codec != first.audio_codec
+    });
+
+    let stem = destination
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let list_path = work_dir.join(format!("{stem}-concat.txt"));
+    let metadata_path = work_dir.join(format!("{stem}-chapters.txt"));
+    std::fs::write(&list_path, list).map_err(|e| format!("Failed to write concat list: {e}"))?;
+    std::fs::write(&metadata_path, chapter_metadata(parts, &durations))
+        .map_err(|e| format!("Failed to write chapter metadata: {e}"))?;
+
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-y")
+        .arg("-loglevel")
+        .arg(ffmpeg_loglevel(settings))
+        .arg("-f")
+        .arg("concat")
+        .arg("-safe")
+        .arg("0")
+        .arg("-i")
+        .arg(&list_path)
+        .arg("-i")
+        .arg(&metadata_path)
+        .arg("-map")
+        .arg("0")
+        .arg("-map_metadata")
+        .arg("1")
+        .arg("-map_chapters")
+        .arg("1");
+
+    if reencode {
+        if let (Some(w), Some(h)) = (first.width, first.height) {
+            cmd.arg("-vf").arg(format!(
+                "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1"
+            ));
+        }
+        let ext = destination
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let (encoder, audio_encoder) = container_encoders(&ext, settings);
+        cmd.args(video_encoder_args(encoder, &settings.preset, settings.crf));
+        if settings.audio_codec == "none" {
+            cmd.arg("-an");
+        } else {
+            cmd.arg("-c:a").arg(audio_encoder);
+            if !audio_encoder.starts_with("pcm_") {
+                cmd.arg("-b:a")
+                    .arg(format!("{}k", settings.audio_bitrate_kbps));
+            }
+        }
+    } else {
+        cmd.arg("-c").arg("copy");
+    }
+    cmd.arg(destination);
+
+    let (status, stderr) = run_ffmpeg(cmd, child_state, None)?;
+    if status.success() && destination.exists() {
+        Ok(reencode)
+    } else {
+        Err(stderr_tail(&stderr))
+    }
+}
+
+fn chapter_metadata(parts: &[ChapterPart], durations: &[f64]) -> String {
+    let mut metadata = String::from(";FFMETADATA1\n");
+    let mut offset_ms = 0u64;
+    for (part, duration) in parts.iter().zip(durations) {
+        let length_ms = (duration * 1000.0).round() as u64;
+        metadata.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            offset_ms,
+            offset_ms + length_ms,
+            escape_ffmetadata(&part.title)
+        ));
+        if let Some(description) = &part.description {
+            metadata.push_str(&format!("description={}\n", escape_ffmetadata(description)));
+        }
+        offset_ms += length_ms;
+    }
+    metadata
+}
+
+// mp4/mov write chapters as a QuickTime chapter track, mkv natively; other
+// containers drop them.
+fn supports_chapters(ext: &str) -> bool {
+    matches!(ext, "mp4" | "m4v" | "mov" | "mkv")
+}
+
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars().filter(|c| *c == '\n' || !c.is_control()) {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Adds or removes `pid` in the pidfile that `cleanup_orphans` reads after a crash.
+/// Failures are ignored; the pidfile is only a recovery aid.
+fn track_pid(pid: u32, running: bool) {
+    let Ok(_guard) = PID_FILE_LOCK.lock() else {
+        return;
+    };
+    let path = std::env::temp_dir().join(PID_FILE);
+    let mut pids = read_pids(&path);
+    pids.retain(|p| *p != pid);
+    if running {
+        pids.push(pid);
+    }
+    let _ = write_pids(&path, &pids);
+}
+
+fn read_pids(path: &Path) -> Vec<u32> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect()
+}
+
+fn write_pids(path: &Path, pids: &[u32]) -> Result<(), String> {
+    let text = pids
+        .iter()
+        .map(|pid| format!("{pid}\n"))
+        .collect::<String>();
+    std::fs::write(path, text).map_err(|e| format!("Failed to update {}: {e}", path.display()))
+}
+
+/// Whether `pid` is a running ffmpeg, so a recycled pid never gets killed.
+fn is_ffmpeg_process(pid: u32) -> bool {
+    let output = if cfg!(windows) {
+        Command::new("tasklist")
+            .arg("/FI")
+            .arg(format!("PID eq {pid}"))
+            .arg("/NH")
+            .arg("/FO")
+            .arg("CSV")
+            .stdin(Stdio::null())
+            .output()
+    } else {
+        Command::new("ps")
+            .arg("-p")
+            .arg(pid.to_string())
+            .arg("-o")
+            .arg("comm=")
+            .stdin(Stdio::null())
+            .output()
+    };
+    let Some(output) = output.ok().filter(|output| output.status.success()) else {
+        return false;
+    };
+    let text = String::from_utf8_lossy(&output.stdout).to_lowercase();
+    if cfg!(windows) {
+        text.starts_with("\"ffmpeg.exe\"")
+    } else {
+        Path::new(text.trim())
+            .file_name()
+            .is_some_and(|name| name == "ffmpeg")
+    }
+}
+
+fn kill_process(pid: u32) -> Result<(), String> {
+    let output = if cfg!(windows) {
+        Command::new("taskkill")
+            .arg("/PID")
+            .arg(pid.to_string())
+            .arg("/F")
+            .stdin(Stdio::null())
+            .output()
+    } else {
+        Command::new("kill")
+            .arg(pid.to_string())
+            .stdin(Stdio::null())
+            .output()
+    }
+    .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Runs ffmpeg until it exits and returns its stderr. With `on_progress`, the
+/// command must include `-progress pipe:1`; the encoded position in seconds is
+/// passed on at most every 250ms.
+fn run_ffmpeg(
+    mut cmd: Command,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+    on_progress: Option<&(dyn Fn(f64) + Send)>,
+) -> Result<(ExitStatus, String), String> {
+    let stdout = if on_progress.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg process: {e}"))?;
+
+    // Drain stderr on a thread so a chatty ffmpeg can't block on a full pipe.
+    let stderr_reader = child.stderr.take().map(|mut stderr| {
+        std::thread::spawn(move || {
+            let mut text = String::new();
+            let _ = stderr.read_to_string(&mut text);
+            text
+        })
+    });
+
+    // -progress writes key=value lines; out_time_us (out_time_ms on older builds,
+    // also in microseconds) is the position reached so far.
+    let (progress_tx, progress_rx) = std::sync::mpsc::channel();
+    let progress_reader = child.stdout.take().map(|stdout| {
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                let micros = line
+                    .strip_prefix("out_time_us=")
+                    .or_else(|| line.strip_prefix("out_time_ms="))
+                    .and_then(|v| v.trim().parse::<f64>().ok());
+                if let Some(micros) = micros.filter(|m| *m >= 0.0) {
+                    let _ = progress_tx.send(micros / 1_000_000.0);
+                }
+            }
+        })
+    });
+
+    let pid = child.id();
+    child_state.lock().map_err(|e| e.to_string())?.push(child);
+    track_pid(pid, true);
+    let mut last_report: Option<std::time::Instant> = None;
+
+    let status = loop {
+        let status = {
+            let mut guard = child_state.lock().map_err(|e| e.to_string())?;
+            let running = guard
+                .iter_mut()
+                .find(|child| child.id() == pid)
+                .ok_or_else(|| "Internal error: ffmpeg process missing".to_string())?;
+            running
+                .try_wait()
+                .map_err(|e| format!("Failed waiting on ffmpeg process: {e}"))?
+        };
+
+        if let Some(status) = status {
+            break status;
+        }
+
+        if let Some(on_progress) = on_progress {
+            let due = !matches!(last_report, Some(at) if at.elapsed() < std::time::Duration::from_millis(250));
+            if due {
+                if let Some(out_sec) = progress_rx.try_iter().last() {
+                    on_progress(out_sec);
+                    last_report = Some(std::time::Instant::now());
+                }
+            }
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(120));
+    };
+
+    child_state
+        .lock()
+        .map_err(|e| e.to_string())?
+        .retain(|child| child.id() != pid);
+    track_pid(pid, false);
+
+    if let Some(reader) = progress_reader {
+        let _ = reader.join();
+    }
+    let stderr = stderr_reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    Ok((status, stderr))
+}
+
+/// Everything that decides one clip's ffmpeg command.
+#[derive(Clone, Copy)]
+struct ClipSpec<'a> {
+    settings: &'a ExportSettings,
+    tools: &'a Tools,
+    mode: &'a str,
+    source: &'a Path,
+    clip_name: &'a str,
+    start_sec: f64,
+    end_sec: f64,
+    exact_fps: Option<f64>,
+    /// Keyframe at or before the clip that an `exact_frames` cut seeks the input to.
+    exact_seek: Option<f64>,
+    fixed_duration: Option<f64>,
+    end_hold: Option<f64>,
+    crop: Option<&'a CropRect>,
+    audio_offset: Option<i32>,
+    source_info: Option<&'a VideoInfo>,
+    container: &'a str,
+    row_ext: &'a str,
+    muxer: Option<&'static str>,
+    encoder: &'a str,
+    audio_encoder: &'a str,
+    loudness_target: Option<f64>,
+    loudnorm_measured: Option<&'a LoudnormStats>,
+    replaygain: Option<(f64, f64)>,
+}
+
+/// A clip's ffmpeg command (all arguments but the output path) and what the
+/// export needs alongside it.
+struct ClipCommand {
+    cmd: Command,
+    fallbacks: ClipFallbacks,
+    filters: Vec<String>,
+    output_duration: f64,
+    reports_progress: bool,
+    fade_note: Option<String>,
+    warnings: Vec<String>,
+}
+
+/// Builds the command for one clip, shared by exports and dry runs.
+fn clip_command(spec: &ClipSpec) -> ClipCommand {
+    let ClipSpec {
+        settings,
+        tools,
+        mode,
+        source: source_video,
+        clip_name,
+        start_sec,
+        end_sec,
+        exact_fps,
+        exact_seek,
+        fixed_duration,
+        end_hold,
+        crop,
+        audio_offset,
+        source_info,
+        container,
+        row_ext,
+        muxer,
+        encoder,
+        audio_encoder,
+        loudness_target,
+        loudnorm_measured,
+        replaygain,
+    } = *spec;
+    let duration = end_sec - start_sec;
+    let audio_only = is_audio_format(row_ext);
+    let mut warnings = Vec::new();
+
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-y")
+        .arg("-loglevel")
+        .arg(ffmpeg_loglevel(settings))
+        .arg("-nostats");
+    // Stream copies finish almost at once; only encodes report their position.
+    let reports_progress = mode != "copy_fast";
+    if reports_progress {
+        cmd.arg("-progress").arg("pipe:1");
+    }
+
+    let mut filters = video_filters(settings, crop);
+    let color_range = if matches!(mode, "copy_fast" | "webp") {
+        None
+    } else {
+        let source_range = source_info.and_then(|info| info.color_range.clone());
+        let (range_filter, range) = color_range_conversion(&settings.color_range, source_range);
+        filters.extend(range_filter);
+        range
+    };
+    if let Some(track) = settings.burn_subtitle_track {
+        // Only plain Precise cuts seek on the output side and keep source timestamps.
+        let offset = if mode == "reencode_precise" && exact_fps.is_none() {
+            0.0
+        } else {
+            start_sec
+        };
+        filters.push(subtitle_filter(source_video, track, offset));
+    }
+    if settings.burn_label && !audio_only {
+        filters.push(label_filter(clip_name, settings));
+    }
+    let downmix = if settings.downmix == "stereo" && source_info.is_some() {
+        match source_info.and_then(|info| info.audio_channels) {
+            Some(channels) if channels > 2 => Some(downmix_filter(channels)),
+            Some(_) => None,
+            None => {
+                warnings.push("source has no audio channels to downmix".to_string());
+                None
+            }
+        }
+    } else {
+        None
+    };
+    // Source seconds in the output; fixed_duration padding and end_hold_sec
+    // both extend it by holding the last frame.
+    let content_sec = fixed_duration.map_or(duration, |target| target.min(duration));
+    let pad_sec = Some(
+        fixed_duration.map_or(0.0, |target| (target - duration).max(0.0)) + end_hold.unwrap_or(0.0),
+    )
+    .filter(|pad| *pad > 0.0);
+    let output_duration = fixed_duration.unwrap_or(duration) + end_hold.unwrap_or(0.0);
+    let mut audio_filters = Vec::new();
+    let fades_apply = audio_only || !matches!(mode, "copy_fast" | "webp");
+    let fade_length = if audio_only {
+        duration
+    } else {
+        output_duration
+    };
+    let (fade_in, fade_out, fades_shortened) =
+        fit_fades(settings.fade_in_sec, settings.fade_out_sec, fade_length);
+    // Plain Precise cuts seek on the output side, so filters still see source
+    // timestamps and the fades have to start from the cut point.
+    let fade_base = if mode == "reencode_precise" && exact_fps.is_none() && !audio_only {
+        start_sec
+    } else {
+        0.0
+    };
+    let video_fades = if fades_apply && !audio_only {
+        fade_filters("fade", fade_in, fade_out, fade_base, fade_length, settings)
+    } else {
+        Vec::new()
+    };
+    let fade_note = (fades_apply && fades_shortened).then(|| {
+        format!(
+            " (fades shortened to {fade_in:.2}s in and {fade_out:.2}s out to fit the {fade_length:.2}s clip)"
+        )
+    });
+
+    match mode {
+        // Audio-only outputs ignore every video setting.
+        _ if audio_only => {
+            cmd.args(input_seek_args(start_sec, settings))
+                .arg("-i")
+                .arg(source_video)
+                .arg("-t")
+                .arg(time_arg(duration, settings))
+                .arg("-vn");
+        }
+        "copy_fast" => {
+            cmd.args(copy_cut_args(source_video, start_sec, duration, settings));
+        }
+        "webp" => {
+            cmd.args(input_seek_args(start_sec, settings))
+                .arg("-i")
+                .arg(source_video)
+                .arg("-t")
+                .arg(time_arg(duration, settings));
+            cmd.args(webp_preview_args(settings, &filters));
+        }
+        "reencode_precise" | "reencode_fast_seek" if exact_fps.is_some() => {
+            let fps = exact_fps.unwrap_or(30.0);
+            // Frame numbers count source frames from the seek point; the output is
+            // then written at `fps`.
+            let source_fps = source_info.and_then(|info| info.fps).unwrap_or(fps);
+            let seek_sec = exact_seek.unwrap_or(0.0).min(start_sec);
+            let (first_frame, last_frame) =
+                frame_range(start_sec - seek_sec, end_sec - seek_sec, source_fps);
+            let selected = (last_frame - first_frame + 1) as f64;
+            let output_frames = (selected / source_fps * fps).round().max(1.0) as u64;
+            let mut exact_filters = vec![exact_frame_filter(first_frame, last_frame)];
+            exact_filters.extend(filters.iter().cloned());
+            exact_filters.extend(video_fades.iter().cloned());
+
+            // The exact keyframe time, unrounded: -ss past it would drop its frame.
+            let seek = exact_seek
+                .filter(|sec| *sec > 0.0)
+                .map(|sec| vec!["-ss".to_string(), sec.to_string()])
+                .unwrap_or_default();
+            cmd.args(&seek).arg("-i").arg(source_video);
+            if let Some(offset) = audio_offset {
+                cmd.args(audio_offset_args(source_video, &seek, offset));
+            }
+            cmd.arg("-vf")
+                .arg(filter_graph(&exact_filters, settings).unwrap_or_default())
+                .arg("-frames:v")
+                .arg(output_frames.to_string())
+                .arg("-r")
+                .arg(fps.to_string())
+                .args(video_encoder_args(encoder, &settings.preset, settings.crf));
+
+            if settings.audio_codec == "aac" {
+                audio_filters.push(format!(
+                    "atrim=start={}:end={},asetpts=PTS-STARTPTS",
+                    first_frame as f64 / source_fps,
+                    (last_frame + 1) as f64 / source_fps
+                ));
+            }
+        }
+        "reencode_fast_seek" => {
+            let mut seek = input_seek_args(start_sec, settings);
+            if pad_sec.is_some() {
+                seek.extend(["-t".to_string(), time_arg(content_sec, settings)]);
+            }
+            cmd.args(&seek).arg("-i").arg(source_video);
+            if let Some(offset) = audio_offset {
+                cmd.args(audio_offset_args(source_video, &seek, offset));
+            }
+            cmd.arg("-t")
+                .arg(time_arg(output_duration, settings))
+                .args(video_encoder_args(encoder, &settings.preset, settings.crf));
+
+            let chain = [end_pad_filters(&filters, pad_sec), video_fades.clone()].concat();
+            if let Some(graph) = filter_graph(&chain, settings) {
+                cmd.arg("-vf").arg(graph);
+            }
+
+            if let Some(fps) = settings.fps {
+                cmd.arg("-r").arg(fps.to_string());
+            }
+        }
+        _ => {
+            // When padding, stop reading at the clip end so tpad/apad extend the
+            // clip itself rather than the end of the source.
+            let input_limit = match pad_sec {
+                Some(_) => vec![
+                    "-to".to_string(),
+                    time_arg(start_sec + content_sec, settings),
+                ],
+                None => Vec::new(),
+            };
+            cmd.args(&input_limit).arg("-i").arg(source_video);
+            if let Some(offset) = audio_offset {
+                cmd.args(audio_offset_args(source_video, &input_limit, offset));
+            }
+            cmd.arg("-ss").arg(time_arg(start_sec, settings));
+            if fixed_duration.is_some() || end_hold.is_some() {
+                cmd.arg("-t").arg(time_arg(output_duration, settings));
+            } else {
+                cmd.arg("-to").arg(time_arg(end_sec, settings));
+            }
+            cmd.args(video_encoder_args(encoder, &settings.preset, settings.crf));
+
+            let chain = [end_pad_filters(&filters, pad_sec), video_fades.clone()].concat();
+            if let Some(graph) = filter_graph(&chain, settings) {
+                cmd.arg("-vf").arg(graph);
+            }
+
+            if let Some(fps) = settings.fps {
+                cmd.arg("-r").arg(fps.to_string());
+            }
+        }
+    }
+
+    if (mode != "copy_fast" && mode != "webp") || audio_only {
+        // A keyframe time list of "0" makes only the first output frame an IDR;
+        // an expression like gte(t,0) would force every frame.
+        if let Some(range) = color_range.as_ref().filter(|_| !audio_only) {
+            cmd.arg("-color_range").arg(range);
+        }
+        if settings.force_keyframe_at_start && !audio_only {
+            cmd.arg("-force_key_frames").arg("0");
+        }
+
+        match settings.audio_codec.as_str() {
+            "none" => {
+                cmd.arg("-an");
+            }
+            "copy" => {
+                cmd.arg("-c:a").arg("copy");
+            }
+            _ => {
+                cmd.arg("-c:a").arg(audio_encoder);
+                // PCM has a fixed bitrate.
+                if !audio_encoder.starts_with("pcm_") {
+                    cmd.arg("-b:a")
+                        .arg(format!("{}k", settings.audio_bitrate_kbps));
+                }
+                match &downmix {
+                    Some(Some(pan)) => audio_filters.push(pan.clone()),
+                    Some(None) => {
+                        cmd.arg("-ac").arg("2");
+                    }
+                    None => {}
+                }
+                if let Some(target) = loudness_target {
+                    audio_filters.push(loudnorm_filter(target, loudnorm_measured));
+                }
+                if pad_sec.is_some() && !audio_only {
+                    audio_filters.push("apad".to_string());
+                }
+                if fades_apply && settings.fade_audio {
+                    audio_filters.extend(fade_filters(
+                        "afade",
+                        fade_in,
+                        fade_out,
+                        fade_base,
+                        fade_length,
+                        settings,
+                    ));
+                }
+                if !audio_filters.is_empty() {
+                    cmd.arg("-af").arg(audio_filters.join(","));
+                }
+            }
+        }
+    }
+
+    let mut flags = movflags(container, settings.fragmented_mp4).map(str::to_string);
+    if let Some((gain, peak)) = replaygain {
+        cmd.args(replaygain_args(gain, peak));
+        // The mp4 muxer drops tags it doesn't know unless told to keep them.
+        if matches!(container, "mp4" | "m4v" | "m4a" | "mov") {
+            flags = Some(format!("{}+use_metadata_tags", flags.unwrap_or_default()));
+        }
+    }
+    if let Some(flags) = flags {
+        cmd.arg("-movflags").arg(flags);
+    }
+    if let Some(muxer) = muxer {
+        cmd.arg("-f").arg(muxer);
+    }
+
+    let fallbacks = ClipFallbacks {
+        container: (mode == "copy_fast"
+            && !audio_only
+            && settings.copy_container_fallback
+            && muxer.is_none()
+            && row_ext != "mkv")
+            .then(|| copy_cut_args(source_video, start_sec, duration, settings)),
+        reencode: (mode == "copy_fast" && !audio_only && settings.fallback_reencode).then(|| {
+            let mut args = reencode_cut_args(
+                source_video,
+                start_sec,
+                duration,
+                video_encoder_args(encoder, &settings.preset, settings.crf),
+                audio_encoder,
+                settings,
+            );
+            if let Some(flags) = movflags(container, settings.fragmented_mp4) {
+                args.extend(["-movflags".into(), flags.into()]);
+            }
+            if let Some(muxer) = muxer {
+                args.extend(["-f".into(), muxer.into()]);
+            }
+            args
+        }),
+        loglevel: ffmpeg_loglevel(settings),
+    };
+    ClipCommand {
+        cmd,
+        fallbacks,
+        filters,
+        output_duration,
+        reports_progress,
+        fade_note,
+        warnings,
+    }
+}
+
+/// Runs one clip's ffmpeg command (all arguments but the output path) on a
+/// worker thread. The clip is written to a `.partial` name and renamed to
+/// `destination` only once it finished with a non-empty file; otherwise the
+/// partial file is deleted, so a stopped or crashed run never leaves a truncated
+/// clip that looks complete.
+fn run_clip_job(
+    tools: &Tools,
+    cmd: Command,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+    stop_state: &AtomicBool,
+    destination: PathBuf,
+    fallbacks: ClipFallbacks,
+    bumpers: Option<Bumpers>,
+    locked_retries: u32,
+    on_progress: Option<Box<dyn Fn(f64) + Send>>,
+) -> Result<ClipOutcome, String> {
+    let partial = partial_path(&destination);
+    let result = encode_clip(
+        tools,
+        cmd,
+        child_state,
+        stop_state,
+        partial.clone(),
+        fallbacks,
+        bumpers,
+        locked_retries,
+        on_progress,
+    );
+    let mut outcome = match result {
+        Ok(outcome) => outcome,
+        Err(err) => {
+            return Err(match remove_partials(&partial) {
+                Some(path) => format!("{err} (removed partial output {})", path.display()),
+                None => err,
+            });
+        }
+    };
+
+    let produced = outcome.status.success()
+        && matches!(std::fs::metadata(&outcome.destination), Ok(m) if m.len() > 0);
+    if produced {
+        let finished = match outcome.destination.extension() {
+            Some(ext) => destination.with_extension(ext),
+            None => destination,
+        };
+        std::fs::rename(&outcome.destination, &finished).map_err(|e| {
+            let _ = std::fs::remove_file(&outcome.destination);
+            format!(
+                "Failed to move finished clip to {}: {e}",
+                finished.display()
+            )
+        })?;
+        outcome.destination = finished;
+        outcome.finished = true;
+    } else {
+        outcome.removed_partial = remove_partials(&partial);
+        outcome.destination = destination;
+    }
+    Ok(outcome)
+}
+
+/// Deletes what an unfinished encode left behind, including a container
+/// fallback's .mkv, and returns the first path removed.
+fn remove_partials(partial: &Path) -> Option<PathBuf> {
+    [partial.to_path_buf(), partial.with_extension("mkv")]
+        .into_iter()
+        .filter(|path| std::fs::remove_file(path).is_ok())
+        // `reduce` still visits both paths, so the .mkv goes too.
+        .reduce(|first, _| first)
+}
+
+/// `clip.partial.mp4` for `clip.mp4`; the extension stays last so ffmpeg still
+/// picks the muxer from it.
+fn partial_path(destination: &Path) -> PathBuf {
+    let stem = destination
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match destination.extension() {
+        Some(ext) => {
+            destination.with_file_name(format!("{stem}.partial.{}", ext.to_string_lossy()))
+        }
+        None => destination.with_file_name(format!("{stem}.partial")),
+    }
+}
+
+/// Encodes one clip to `destination`. A source locked by another process is
+/// retried up to `locked_retries` times; a container error is retried as a
+/// stream copy into .mkv, and a failed or empty copy as a re-encode, when
+/// `fallbacks` provides the arguments.
+fn encode_clip(
+    tools: &Tools,
+    cmd: Command,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+    stop_state: &AtomicBool,
+    destination: PathBuf,
+    fallbacks: ClipFallbacks,
+    bumpers: Option<Bumpers>,
+    locked_retries: u32,
+    on_progress: Option<Box<dyn Fn(f64) + Send>>,
+) -> Result<ClipOutcome, String> {
+    let mut attempt = 0;
+    let (status, stderr) = loop {
+        let mut run = Command::new(cmd.get_program());
+        run.args(cmd.get_args()).arg(&destination);
+        let result = run_ffmpeg(run, child_state, on_progress.as_deref());
+        let locked = match &result {
+            Ok((status, stderr)) => !status.success() && is_locked_file_error(stderr),
+            Err(err) => is_locked_file_error(err),
+        };
+        if !locked || stop_state.load(Ordering::SeqCst) {
+            break result?;
+        }
+        if attempt >= locked_retries {
+            return Err(format!(
+                "source file is in use by another process (tried {} time(s))",
+                attempt + 1
+            ));
+        }
+        attempt += 1;
+        std::thread::sleep(std::time::Duration::from_millis(LOCKED_RETRY_DELAY_MS));
+    };
+    let mut outcome = ClipOutcome {
+        status,
+        destination,
+        container_fallback: false,
+        reencode_fallback: false,
+        stderr,
+        finished: false,
+        removed_partial: None,
+    };
+
+    if let Some(args) = fallbacks.container.filter(|_| {
+        !status.success()
+            && is_container_error(&outcome.stderr)
+            && !stop_state.load(Ordering::SeqCst)
+    }) {
+        let _ = std::fs::remove_file(&outcome.destination);
+        let fallback = outcome.destination.with_extension("mkv");
+        let (status, stderr) = run_ffmpeg(
+            ffmpeg_with_args(tools, args, &fallback, fallbacks.loglevel),
+            child_state,
+            None,
+        )?;
+        outcome.status = status;
+        outcome.stderr = stderr;
+        if status.success() && fallback.exists() {
+            outcome.destination = fallback;
+            outcome.container_fallback = true;
+        }
+    }
+
+    let produced = |outcome: &ClipOutcome| {
+        outcome.status.success()
+            && matches!(std::fs::metadata(&outcome.destination), Ok(m) if m.len() > 0)
+    };
+    if let Some(args) = fallbacks
+        .reencode
+        .filter(|_| !produced(&outcome) && !stop_state.load(Ordering::SeqCst))
+    {
+        let _ = std::fs::remove_file(&outcome.destination);
+        let (status, stderr) = run_ffmpeg(
+            ffmpeg_with_args(tools, args, &outcome.destination, fallbacks.loglevel),
+            child_state,
+            None,
+        )?;
+        outcome.status = status;
+        outcome.stderr = stderr;
+        outcome.reencode_fallback = produced(&outcome);
+    }
+
+    if let Some(bumpers) = bumpers {
+        if outcome.status.success()
+            && outcome.destination.exists()
+            && !stop_state.load(Ordering::SeqCst)
+        {
+            outcome.status = add_bumpers(tools, &outcome.destination, &bumpers, child_state)?;
+        }
+    }
+    Ok(outcome)
+}
+
+/// The video and audio encoders a re-encode into `container` uses.
+fn container_encoders<'a>(container: &str, settings: &'a ExportSettings) -> (&'a str, &'a str) {
+    // WebM only holds VP8/VP9/AV1 video and Vorbis/Opus audio.
+    match container {
+        "webm" => ("libvpx-vp9", "libopus"),
+        "mp3" => (settings.encoder.as_str(), "libmp3lame"),
+        "wav" => (settings.encoder.as_str(), "pcm_s16le"),
+        _ => (settings.encoder.as_str(), "aac"),
+    }
+}
+
+/// Clip encodes log at `info` with `verbose` so failures carry more context.
+fn ffmpeg_loglevel(settings: &ExportSettings) -> &'static str {
+    if settings.verbose {
+        "info"
+    } else {
+        "error"
+    }
+}
+
+/// The last `STDERR_TAIL_LINES` non-empty lines of ffmpeg's stderr.
+fn stderr_tail(stderr: &str) -> String {
+    let lines = stderr
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>();
+    lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n")
+}
+
+fn ffmpeg_with_args(
+    tools: &Tools,
+    args: Vec<std::ffi::OsString>,
+    destination: &Path,
+    loglevel: &str,
+) -> Command {
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-y")
+        .arg("-loglevel")
+        .arg(loglevel)
+        .arg("-nostats")
+        .args(args)
+        .arg(destination);
+    cmd
+}
+
+/// Split points and, per row, the index of the piece that is exactly that clip,
+/// for cutting every row out of one segment-muxer pass. Errors name the reason
+/// the rows need separate cuts.
+fn segment_split_plan(
+    rows: &[ClipRow],
+    settings: &ExportSettings,
+    rules: &[SettingsRule],
+    timecode_offset: f64,
+    fps: f64,
+    video_duration: Option<f64>,
+    joins_clips: bool,
+) -> Result<(Vec<f64>, Vec<usize>), String> {
+    if settings.processing_mode != "copy_fast" {
+        return Err("it only applies to Copy Streams mode".to_string());
+    }
+    let per_clip = [
+        (!rules.is_empty(), "rules_file"),
+        (joins_clips, "concat/chapters_per_source"),
+        (settings.snap_to_keyframe, "snap_to_keyframe"),
+        (settings.accurate_copy, "accurate_copy"),
+        (settings.replaygain_tag, "replaygain_tag"),
+        (settings.audio_offset_ms != 0, "audio_offset_ms"),
+        (settings.muxer_format.is_some(), "muxer_format"),
+        (settings.output_extension.is_some(), "output_extension"),
+        (settings.fragmented_mp4, "fragmented_mp4"),
+        (
+            settings
+                .output_format
+                .as_deref()
+                .is_some_and(is_audio_format),
+            "audio-only output_format",
+        ),
+        (
+            settings.watermark_image.is_some()
+                || settings.burn_subtitle_track.is_some()
+                || settings.burn_label
+                || settings.social_preset.is_some()
+                || has_settings_crop(settings),
+            "re-encoding filters",
+        ),
+    ];
+    if let Some((_, option)) = per_clip.iter().find(|(set, _)| *set) {
+        return Err(format!("{option} needs each clip cut on its own"));
+    }
+
+    let mut clips = Vec::with_capacity(rows.len());
+    for (idx, row) in rows.iter().enumerate() {
+        let overrides = &row.overrides;
+        let own_settings = [
+            &overrides.mode,
+            &overrides.crf,
+            &overrides.preset,
+            &overrides.resolution,
+            &overrides.source,
+        ]
+        .iter()
+        .any(|cell| cell.is_some())
+            || overrides.has_crop()
+            || overrides.audio_offset_ms.is_some()
+            || overrides.lufs.is_some()
+            || row_featured(overrides) != Ok(false);
+        if own_settings {
+            return Err(format!("row {} has its own settings", idx + 2));
+        }
+        let time = |ts: &str| clip_time_seconds(ts, timecode_offset, fps, video_duration).ok();
+        let times = time(&row.start_time)
+            .zip(time(&row.end_time))
+            .map(|(start, end)| {
+                if settings.autoswap_times && end < start {
+                    (end, start)
+                } else {
+                    (start, end)
+                }
+            })
+            .filter(|(start, end)| end > start);
+        let Some((start, end)) = times else {
+            return Err(format!("row {} has invalid times", idx + 2));
+        };
+        clips.push((start, end, idx));
+    }
+
+    clips.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if clips.windows(2).any(|pair| pair[1].0 < pair[0].1) {
+        return Err("clips overlap".to_string());
+    }
+    let mut boundaries: Vec<f64> = clips
+        .iter()
+        .flat_map(|(start, end, _)| [*start, *end])
+        .filter(|t| *t > 0.0)
+        .collect();
+    boundaries.dedup();
+    // Piece 0 runs up to the first boundary, piece k from boundary k-1 to k.
+    let mut segments = vec![0; rows.len()];
+    for (start, _, idx) in &clips {
+        segments[*idx] = boundaries.iter().take_while(|t| *t <= start).count();
+    }
+    Ok((boundaries, segments))
+}
+
+/// Stream-copies the whole source into numbered pieces split at `boundaries`
+/// and returns each piece's start time. The segment muxer starts each piece on
+/// the first keyframe at or after its time.
+fn split_at_boundaries(
+    tools: &Tools,
+    source: &Path,
+    dir: &Path,
+    boundaries: &[f64],
+    ext: &str,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+) -> Result<Vec<f64>, String> {
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let list = dir.join("segments.csv");
+    let times = boundaries
+        .iter()
+        .map(|t| format!("{t:.3}"))
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-nostats")
+        .arg("-i")
+        .arg(source)
+        .arg("-map")
+        .arg("0:v:0")
+        .arg("-map")
+        .arg("0:a?")
+        .arg("-c")
+        .arg("copy")
+        .arg("-f")
+        .arg("segment")
+        .arg("-segment_times")
+        .arg(times)
+        .arg("-reset_timestamps")
+        .arg("1")
+        .arg("-segment_list")
+        .arg(&list)
+        .arg("-segment_list_type")
+        .arg("csv")
+        .arg(dir.join(format!("%05d.{ext}")));
+    let (status, stderr) = run_ffmpeg(cmd, child_state, None)?;
+    if !status.success() {
+        return Err(format!("the split failed: {}", stderr.trim()));
+    }
+    let listing = std::fs::read_to_string(&list)
+        .map_err(|e| format!("the split wrote no segment list: {e}"))?;
+    Ok(segment_list_starts(&listing))
+}
+
+/// Start times from a csv `-segment_list` (`name,start,end` per piece).
+fn segment_list_starts(listing: &str) -> Vec<f64> {
+    listing
+        .lines()
+        .filter_map(|line| line.rsplit(',').nth(1)?.trim().parse().ok())
+        .collect()
+}
+
+/// Checks that the segment muxer produced one piece per planned range, each
+/// starting within a frame of its boundary; a late keyframe or a merged piece
+/// would otherwise hand rows the wrong footage.
+fn check_segments(starts: &[f64], boundaries: &[f64], fps: f64) -> Result<(), String> {
+    if starts.len() != boundaries.len() + 1 {
+        return Err(format!(
+            "the split produced {} pieces instead of {}",
+            starts.len(),
+            boundaries.len() + 1
+        ));
+    }
+    let tolerance = 1.0 / fps;
+    for (start, boundary) in starts[1..].iter().zip(boundaries) {
+        if (start - boundary).abs() > tolerance {
+            return Err(format!(
+                "a piece starts at {start:.3}s instead of {boundary:.3}s (no keyframe there)"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Moves a pre-split piece to the row's destination in place of an ffmpeg cut.
+fn take_segment(segment: &Path, destination: PathBuf) -> Result<ClipOutcome, String> {
+    std::fs::rename(segment, &destination)
+        .or_else(|_| std::fs::copy(segment, &destination).map(|_| ()))
+        .map_err(|e| {
+            format!(
+                "Failed to move split clip to {}: {e}",
+                destination.display()
+            )
+        })?;
+    Ok(ClipOutcome {
+        status: ExitStatus::default(),
+        destination,
+        container_fallback: false,
+        reencode_fallback: false,
+        stderr: String::new(),
+        finished: true,
+        removed_partial: None,
+    })
+}
+
+fn segment_command(
+    tools: &Tools,
+    source: &Path,
+    pattern: &Path,
+    segment_seconds: f64,
+    settings: &ExportSettings,
+    ext: &str,
+) -> Command {
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-nostats")
+        .arg("-i")
+        .arg(source)
+        .arg("-map")
+        .arg("0:v:0")
+        .arg("-map")
+        .arg("0:a?");
+
+    if settings.processing_mode == "copy_fast" {
+        cmd.arg("-c").arg("copy");
+    } else {
+        let (encoder, audio_encoder) = container_encoders(ext, settings);
+        if let Some(graph) = filter_graph(&video_filters(settings, None), settings) {
+            cmd.arg("-vf").arg(graph);
+        }
+        if let Some(fps) = settings.fps {
+            cmd.arg("-r").arg(fps.to_string());
+        }
+        cmd.args(video_encoder_args(encoder, &settings.preset, settings.crf))
+            .arg("-force_key_frames")
+            .arg(format!("expr:gte(t,n_forced*{segment_seconds})"));
+        match settings.audio_codec.as_str() {
+            "none" => {
+                cmd.arg("-an");
+            }
+            "copy" => {
+                cmd.arg("-c:a").arg("copy");
+            }
+            _ => {
+                cmd.arg("-c:a")
+                    .arg(audio_encoder)
+                    .arg("-b:a")
+                    .arg(format!("{}k", settings.audio_bitrate_kbps));
+            }
+        }
+    }
+
+    cmd.arg("-f")
+        .arg("segment")
+        .arg("-segment_time")
+        .arg(segment_seconds.to_string())
+        .arg("-reset_timestamps")
+        .arg("1");
+    if let Some(format) = settings.muxer_format.as_deref().and_then(muxer_name) {
+        cmd.arg("-segment_format").arg(format);
+    }
+    cmd.arg(pattern);
+    cmd
+}
+
+fn load_bumpers(tools: &Tools, settings: &ExportSettings) -> Result<Option<Bumpers>, String> {
+    let load = |path: Option<&str>, label: &str| -> Result<Option<Bumper>, String> {
+        let Some(path) = path else {
+            return Ok(None);
+        };
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            return Err(format!("{label} file not found: {}", path.display()));
+        }
+        let info = probe_video(tools, &path)?;
+        let duration = info
+            .duration_sec
+            .ok_or_else(|| format!("Could not read the duration of the {label} file"))?;
+        Ok(Some(Bumper {
+            path,
+            duration,
+            has_audio: info.audio_codec.is_some(),
+        }))
+    };
+    let intro = load(settings.intro_file.as_deref(), "Intro")?;
+    let outro = load(settings.outro_file.as_deref(), "Outro")?;
+    if intro.is_none() && outro.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(Bumpers {
+        intro,
+        outro,
+        encoder: settings.encoder.clone(),
+        audio_encoder: "aac".to_string(),
+        preset: settings.preset.clone(),
+        crf: settings.crf,
+        audio_bitrate_kbps: settings.audio_bitrate_kbps,
+        movflags: None,
+    }))
+}
+
+/// Filtergraph joining `parts` (duration, has audio) into `[v]`/`[a]`, each
+/// scaled and padded to the clip's frame size and rate. Silent parts get generated
+/// silence when the output has audio.
+fn bumper_filter_graph(
+    parts: &[(f64, bool)],
+    width: u32,
+    height: u32,
+    fps: f64,
+    with_audio: bool,
+) -> String {
+    let mut chains = Vec::new();
+    let mut concat_inputs = String::new();
+    for (i, (duration, has_audio)) in parts.iter().enumerate() {
+        chains.push(format!(
+            "[{i}:v]scale={width}:{height}:force_original_aspect_ratio=decrease,pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={fps},format=yuv420p[v{i}]"
+        ));
+        concat_inputs.push_str(&format!("[v{i}]"));
+        if with_audio {
+            if *has_audio {
+                chains.push(format!(
+                    "[{i}:a]aformat=sample_rates=48000:channel_layouts=stereo[a{i}]"
+                ));
+            } else {
+                chains.push(format!(
+                    "anullsrc=r=48000:cl=stereo,atrim=duration={duration}[a{i}]"
+                ));
+            }
+            concat_inputs.push_str(&format!("[a{i}]"));
+        }
+    }
+    chains.push(format!(
+        "{concat_inputs}concat=n={}:v=1:a={}[v]{}",
+        parts.len(),
+        u8::from(with_audio),
+        if with_audio { "[a]" } else { "" }
+    ));
+    chains.join(";")
+}
+
+/// Re-encodes `destination` with the intro/outro joined around it, matching the
+/// clip's frame size and rate.
+fn add_bumpers(
+    tools: &Tools,
+    destination: &Path,
+    bumpers: &Bumpers,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+) -> Result<ExitStatus, String> {
+    let ext = destination
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_else(|| "mp4".to_string());
+    let body = sidecar_path(destination, "body", &ext);
+    std::fs::rename(destination, &body)
+        .map_err(|e| format!("Failed to stage clip for intro/outro: {e}"))?;
+
+    let info = probe_video(tools, &body)?;
+    let (Some(width), Some(height)) = (info.width, info.height) else {
+        let _ = std::fs::remove_file(&body);
+        return Err("Could not read the clip's frame size for intro/outro".to_string());
+    };
+    let fps = info.fps.unwrap_or(DEFAULT_TIMECODE_FPS);
+    let with_audio = info.audio_codec.is_some();
+    let body_part = Bumper {
+        path: body.clone(),
+        duration: info.duration_sec.unwrap_or_default(),
+        has_audio: with_audio,
+    };
+    let parts = bumpers
+        .intro
+        .iter()
+        .chain(std::iter::once(&body_part))
+        .chain(bumpers.outro.iter())
+        .collect::<Vec<_>>();
+
+    let cmd = bumper_command(
+        tools,
+        &parts,
+        destination,
+        bumpers,
+        width,
+        height,
+        fps,
+        with_audio,
+    );
+    let result = run_ffmpeg(cmd, child_state, None);
+    let _ = std::fs::remove_file(&body);
+    result.map(|(status, _)| status)
+}
+
+fn bumper_command(
+    tools: &Tools,
+    parts: &[&Bumper],
+    destination: &Path,
+    bumpers: &Bumpers,
+    width: u32,
+    height: u32,
+    fps: f64,
+    with_audio: bool,
+) -> Command {
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-y").arg("-loglevel").arg("error").arg("-nostats");
+    for part in parts {
+        cmd.arg("-i").arg(&part.path);
+    }
+    let layout = parts
+        .iter()
+        .map(|part| (part.duration, part.has_audio))
+        .collect::<Vec<_>>();
+    cmd.arg("-filter_complex")
+        .arg(bumper_filter_graph(&layout, width, height, fps, with_audio))
+        .arg("-map")
+        .arg("[v]")
+        .args(video_encoder_args(
+            &bumpers.encoder,
+            &bumpers.preset,
+            bumpers.crf,
+        ));
+    if with_audio {
+        cmd.arg("-map")
+            .arg("[a]")
+            .arg("-c:a")
+            .arg(&bumpers.audio_encoder)
+            .arg("-b:a")
+            .arg(format!("{}k", bumpers.audio_bitrate_kbps));
+    }
+    if let Some(flags) = &bumpers.movflags {
+        cmd.arg("-movflags").arg(flags);
+    }
+    cmd.arg(destination);
+    cmd
+}
+
+/// The commands `run_clip_job` would run for a clip bound for `destination`.
+fn planned_command(
+    idx: usize,
+    destination: &Path,
+    clip: &ClipCommand,
+    bumpers: Option<&Bumpers>,
+    spec: &ClipSpec,
+) -> PlannedCommand {
+    let argv = |cmd: &Command| {
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+    };
+    let partial = partial_path(destination);
+    let mut args = argv(&clip.cmd);
+    args.push(partial.to_string_lossy().to_string());
+    let loglevel = clip.fallbacks.loglevel;
+    let mut fallback_args = Vec::new();
+    if let Some(container) = &clip.fallbacks.container {
+        let mkv = partial.with_extension("mkv");
+        fallback_args.push(argv(&ffmpeg_with_args(
+            spec.tools,
+            container.clone(),
+            &mkv,
+            loglevel,
+        )));
+    }
+    if let Some(reencode) = &clip.fallbacks.reencode {
+        fallback_args.push(argv(&ffmpeg_with_args(
+            spec.tools,
+            reencode.clone(),
+            &partial,
+            loglevel,
+        )));
+    }
+    // The real pass probes the finished clip; the plan uses the size and rate the
+    // filters and settings produce.
+    let bumper_args = bumpers.and_then(|bumpers| {
+        let (width, height, fps) = planned_frame(spec)?;
+        let with_audio = spec.settings.audio_codec != "none"
+            && !matches!(spec.source_info, Some(info) if info.audio_codec.is_none());
+        let body = Bumper {
+            path: sidecar_path(&partial, "body", spec.row_ext),
+            duration: clip.output_duration,
+            has_audio: with_audio,
+        };
+        let parts = bumpers
+            .intro
+            .iter()
+            .chain(std::iter::once(&body))
+            .chain(bumpers.outro.iter())
+            .collect::<Vec<_>>();
+        let cmd = bumper_command(
+            spec.tools, &parts, &partial, bumpers, width, height, fps, with_audio,
+        );
+        Some(argv(&cmd))
+    });
+    PlannedCommand {
+        row_index: idx,
+        output_path: destination.to_string_lossy().to_string(),
+        args,
+        fallback_args,
+        bumper_args,
+    }
+}
+
+/// The frame size and rate a re-encoded clip comes out at.
+fn planned_frame(spec: &ClipSpec) -> Option<(u32, u32, f64)> {
+    let settings = spec.settings;
+    let source = spec.source_info;
+    let (width, height) = resolution_size(&settings.resolution)
+        .or_else(|| spec.crop.map(|crop| (crop.w, crop.h)))
+        .or_else(|| {
+            let width = settings.crop_w.or_else(|| source?.width)?;
+            let height = settings.crop_h.or_else(|| source?.height)?;
+            Some((width, height))
+        })?;
+    let fps = spec
+        .exact_fps
+        .or(settings.fps)
+        .or_else(|| source?.fps)
+        .unwrap_or(DEFAULT_TIMECODE_FPS);
+    Some((width, height, fps))
+}
+
+fn source_key(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Whether ffmpeg (or the spawn itself) failed because another process holds the
+/// file open, as happens on Windows while a recording is still being written.
+fn is_locked_file_error(text: &str) -> bool {
+    let text = text.to_lowercase();
+    text.contains("being used by another process")
+        || text.contains("sharing violation")
+        || text.contains("os error 32")
+        || (cfg!(windows) && text.contains("permission denied"))
+}
+
+fn is_container_error(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    [
+        "not currently supported in container",
+        "could not find tag for codec",
+        "could not write header",
+        "muxer does not support",
+    ]
+    .iter()
+    .any(|pattern| stderr.contains(pattern))
+}
+
+fn run_qc_report(
+    tools: &Tools,
+    app: &AppHandle,
+    output_dir: &Path,
+    expected_video_codec: Option<&str>,
+) -> Result<QcSummary, String> {
+    if !output_dir.is_dir() {
+        return Err(format!(
+            "Output directory not found: {}",
+            output_dir.display()
+        ));
+    }
+
+    let file_names = match read_manifest(output_dir) {
+        Some(manifest) => manifest.clips.into_iter().map(|c| c.file_name).collect(),
+        None => list_media_files(output_dir)?,
+    };
+    let total = file_names.len();
+    let mut files = Vec::new();
+
+    for (idx, file_name) in file_names.iter().enumerate() {
+        emit_qc_progress(
+            app,
+            ProgressPayload {
+                total,
+                completed: idx,
+                current_clip: file_name.clone(),
+                status: "running".to_string(),
+                message: format!("Probing {} of {}", idx + 1, total),
+                row_index: Some(idx),
+                row_result: Some("running".to_string()),
+                phase: Some("probe".to_string()),
+                clip_progress: None,
+                weighted_percent: None,
+            },
+        );
+
+        let path = output_dir.join(file_name);
+        let mut issues = Vec::new();
+        let size_bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let info = if path.is_file() {
+            probe_video(tools, &path).unwrap_or_else(|err| {
+                issues.push(err);
+                VideoInfo::default()
+            })
+        } else {
+            issues.push("file missing".to_string());
+            VideoInfo::default()
+        };
+
+        if info.duration_sec.unwrap_or(0.0) < 0.05 {
+            issues.push("zero duration".to_string());
+        }
+        if size_bytes < QC_MIN_SIZE_BYTES {
+            issues.push(format!("tiny file ({size_bytes} bytes)"));
+        }
+        if let (Some(expected), Some(actual)) = (expected_video_codec, info.video_codec.as_deref())
+        {
+            if !expected.eq_ignore_ascii_case(actual) {
+                issues.push(format!("unexpected codec {actual} (expected {expected})"));
+            }
+        }
+
+        files.push(QcEntry {
+            file_name: file_name.clone(),
+            info,
+            size_bytes,
+            passed: issues.is_empty(),
+            issues,
+        });
+    }
+
+    let report_path = output_dir.join("qc_report.csv");
+    write_qc_csv(&report_path, &files)?;
+
+    let passed = files.iter().filter(|f| f.passed).count();
+    emit_qc_progress(
+        app,
+        ProgressPayload {
+            total,
+            completed: total,
+            current_clip: String::new(),
+            status: "done".to_string(),
+            message: format!("QC done. Passed: {}, Failed: {}", passed, total - passed),
+            row_index: None,
+            row_result: None,
+            phase: None,
+            clip_progress: None,
+            weighted_percent: None,
+        },
+    );
+
+    Ok(QcSummary {
+        total,
+        passed,
+        failed: total - passed,
+        report_path: report_path.to_string_lossy().to_string(),
+        files,
+    })
+}
+
+fn write_qc_csv(path: &Path, files: &[QcEntry]) -> Result<(), String> {
+    let mut writer =
+        csv::Writer::from_path(path).map_err(|e| format!("Failed to write QC report: {e}"))?;
+    writer
+        .write_record([
+            "file",
+            "result",
+            "duration_sec",
+            "width",
+            "height",
+            "video_codec",
+            "audio_codec",
+            "audio_channels",
+            "bit_rate",
+            "size_bytes",
+            "issues",
+        ])
+        .map_err(|e| format!("Failed to write QC report: {e}"))?;
+
+    let opt = |v: Option<String>| v.unwrap_or_default();
+    for entry in files {
+        writer
+            .write_record([
+                entry.file_name.clone(),
+                if entry.passed { "pass" } else { "fail" }.to_string(),
+                opt(entry.info.duration_sec.map(|d| format!("{d:.3}"))),
+                opt(entry.info.width.map(|w| w.to_string())),
+                opt(entry.info.height.map(|h| h.to_string())),
+                opt(entry.info.video_codec.clone()),
+                opt(entry.info.audio_codec.clone()),
+                opt(entry.info.audio_channels.map(|c| c.to_string())),
+                opt(entry.info.bit_rate.map(|b| b.to_string())),
+                entry.size_bytes.to_string(),
+                entry.issues.join("; "),
+            ])
+            .map_err(|e| format!("Failed to write QC report: {e}"))?;
+    }
+
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write QC report: {e}"))
+}
+
+/// One report row per clip row; rows never reached (stopped runs) are "not run".
+fn report_rows(clip_rows: &[ClipRow], results: &[RowResult], errors: &[String]) -> Vec<ReportRow> {
+    clip_rows
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| {
+            let result = results.iter().find(|r| r.row_index == idx);
+            let label = format!("Row {}", idx + 2);
+            let error = errors
+                .iter()
+                .filter(|err| {
+                    err.strip_prefix(&label)
+                        .is_some_and(|rest| rest.starts_with([' ', ':']))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            ReportRow {
+                row_index: idx,
+                clip_name: row.clip_name.clone(),
+                start_time: row.start_time.clone(),
+                end_time: row.end_time.clone(),
+                output_file: result.and_then(|r| r.output_path.clone()),
+                status: result.map_or_else(|| "not run".to_string(), |r| r.status.clone()),
+                error: (!error.is_empty()).then(|| error.join("; ")),
+            }
+        })
+        .collect()
+}
+
+/// Writes the report as JSON at `path` plus a CSV of its rows next to it.
+fn write_export_report(path: &Path, report: &ExportReport) -> Result<(), String> {
+    let json_path = if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+    {
+        path.with_extension("json")
+    } else {
+        path.to_path_buf()
+    };
+    let json = serde_json::to_vec_pretty(report)
+        .map_err(|e| format!("Failed to serialize export report: {e}"))?;
+    std::fs::write(&json_path, json)
+        .map_err(|e| format!("Failed to write export report {}: {e}", json_path.display()))?;
+
+    let csv_path = json_path.with_extension("csv");
+    let err = |e: csv::Error| format!("Failed to write export report {}: {e}", csv_path.display());
+    let mut writer = csv::Writer::from_path(&csv_path).map_err(err)?;
+    writer
+        .write_record([
+            "row",
+            "clip_name",
+            "start_time",
+            "end_time",
+            "output_file",
+            "status",
+            "error",
+        ])
+        .map_err(err)?;
+    for row in &report.rows {
+        writer
+            .write_record([
+                (row.row_index + 2).to_string(),
+                row.clip_name.clone(),
+                row.start_time.clone(),
+                row.end_time.clone(),
+                row.output_file.clone().unwrap_or_default(),
+                row.status.clone(),
+                row.error.clone().unwrap_or_default(),
+            ])
+            .map_err(err)?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write export report {}: {e}", csv_path.display()))
+}
+
+fn unix_seconds() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn emit_qc_progress(app: &AppHandle, payload: ProgressPayload) {
+    let _ = app.emit("qc-progress", payload);
+}
+
+/// Summed clip length of the rows that already have a result.
+fn finished_weight(row_weights: &[f64], row_results: &[RowResult]) -> f64 {
+    row_results
+        .iter()
+        .filter_map(|result| row_weights.get(result.row_index))
+        .sum()
+}
+
+/// Duration-weighted progress in percent, or `None` when no clip length is known
+/// and the count-based `completed`/`total` has to do.
+fn weighted_percent(done: f64, total: f64) -> Option<f64> {
+    (total > 0.0).then(|| (done / total * 100.0).clamp(0.0, 100.0))
+}
+
+fn emit_progress(log: &ProgressLog, payload: ProgressPayload) {
+    let line = ProgressLogLine {
+        run_id: log.run_id.clone(),
+        payload,
+    };
+    if let Some(file) = &log.file {
+        if let (Ok(json), Ok(mut file)) = (serde_json::to_string(&line), file.lock()) {
+            let _ = writeln!(file, "{json}").and_then(|_| file.flush());
+        }
+    }
+    (log.emit)(&line);
+}
+
+fn emit_row_progress(
+    progress: &RunProgress,
+    row_results: &[RowResult],
+    idx: usize,
+    clip_name: &str,
+    message: String,
+    row_result: &str,
+    phase: Option<&str>,
+) {
+    emit_progress(
+        progress.log,
+        ProgressPayload {
+            current_clip: clip_name.to_string(),
+            row_index: Some(idx),
+            row_result: Some(row_result.to_string()),
+            phase: phase.map(str::to_string),
+            ..progress.payload(row_results, message)
+        },
+    );
+}
+
+/// Counts row `idx` as skipped before any ffmpeg run and reports `reason`.
+fn skip_row(
+    progress: &RunProgress,
+    row_results: &mut Vec<RowResult>,
+    errors: &mut Vec<String>,
+    skipped: &mut usize,
+    idx: usize,
+    clip_name: &str,
+    reason: &str,
+) {
+    *skipped += 1;
+    row_results.push(RowResult::new(idx, clip_name, "skipped"));
+    let err = format!("Row {} skipped: {}", idx + 2, reason);
+    errors.push(err.clone());
+    emit_row_progress(progress, row_results, idx, clip_name, err, "failed", None);
+}
+
+fn open_progress_log(path: &Path) -> Result<std::fs::File, String> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open progress log {}: {e}", path.display()))
+}
+
+/// Identifies one export run across its progress events, logs, manifest
+/// entries and summary: a random (version 4) UUID. The bits come from std's
+/// randomly keyed `RandomState` hasher, so no RNG crate is needed.
+fn new_run_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let call = CALLS.fetch_add(1, Ordering::Relaxed);
+    let half = |part: u64| {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        hasher.write_u64(call);
+        hasher.write_u64(part);
+        hasher.finish()
+    };
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&half(0).to_be_bytes());
+    bytes[8..].copy_from_slice(&half(1).to_be_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// The ffmpeg and ffprobe a command runs. `None` means look the tool up on PATH.
+#[derive(Clone, Default)]
+struct Tools {
+    ffmpeg: Option<PathBuf>,
+    ffprobe: Option<PathBuf>,
+}
+
+impl Tools {
+    /// Resolves each tool from the given path, then the
+    /// `CLIPCHOP_FFMPEG`/`CLIPCHOP_FFPROBE` variable, then (for ffprobe) the one
+    /// next to a chosen ffmpeg.
+    fn new(ffmpeg_path: Option<&str>, ffprobe_path: Option<&str>) -> Self {
+        let from_env = |env: &str| {
+            std::env::var_os(env)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+        let ffmpeg = ffmpeg_path
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| from_env(FFMPEG_ENV));
+        let ffprobe = ffprobe_path
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| from_env(FFPROBE_ENV))
+            .or_else(|| {
+                let sibling = ffmpeg
+                    .as_ref()?
+                    .with_file_name(format!("ffprobe{}", std::env::consts::EXE_SUFFIX));
+                Some(sibling).filter(|sibling| sibling.is_file())
+            });
+        Tools { ffmpeg, ffprobe }
+    }
+
+    fn from_settings(settings: &ExportSettings) -> Self {
+        Self::new(
+            settings.ffmpeg_path.as_deref(),
+            settings.ffprobe_path.as_deref(),
+        )
+    }
+
+    fn ffmpeg(&self) -> Command {
+        Command::new(self.ffmpeg.as_deref().unwrap_or(Path::new("ffmpeg")))
+    }
+
+    fn ffprobe(&self) -> Command {
+        Command::new(self.ffprobe.as_deref().unwrap_or(Path::new("ffprobe")))
+    }
+
+    fn status(&self, name: &str) -> ToolStatus {
+        let configured = if name == "ffprobe" {
+            &self.ffprobe
+        } else {
+            &self.ffmpeg
+        };
+        let path = match configured {
+            Some(path) => Some(path.clone()).filter(|path| is_executable(path)),
+            None => which::which(name).ok(),
+        };
+        let Some(path) = path else {
+            return ToolStatus {
+                found: false,
+                path: None,
+                version: None,
+            };
+        };
+
+        let version = Command::new(&path)
+            .arg("-version")
+            .stdin(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .next()
+                    .map(|line| line.trim().to_string())
+            });
+
+        ToolStatus {
+            found: true,
+            path: Some(path.to_string_lossy().to_string()),
+            version,
+        }
+    }
+
+    fn ensure_ffmpeg(&self) -> Result<(), String> {
+        match &self.ffmpeg {
+            Some(path) if is_executable(path) => Ok(()),
+            Some(path) => Err(format!(
+                "ffmpeg at {} is missing or not executable. Check ffmpeg_path or {FFMPEG_ENV}.",
+                path.display()
+            )),
+            None => which::which("ffmpeg").map(|_| ()).map_err(|_| {
+                "ffmpeg not found in PATH. Install ffmpeg before running exports.".to_string()
+            }),
+        }
+    }
+}
+
+fn ffmpeg_install_hint() -> Option<&'static str> {
+    if cfg!(target_os = "macos") {
+        Some("brew install ffmpeg")
+    } else if cfg!(target_os = "windows") {
+        Some("winget install --id Gyan.FFmpeg")
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// `ExportSettings::timecode_fps` for `video`, probing its rate only when no
+/// output FPS is set. Rows with their own `source` are timed at this rate too;
+/// their videos are not probed.
+fn resolve_timecode_fps(tools: &Tools, settings: &ExportSettings, video: Option<&Path>) -> f64 {
+    let source_fps = video
+        .filter(|_| settings.fps.is_none())
+        .and_then(|video| probe_video_fps(tools, video));
+    settings.timecode_fps(source_fps)
+}
+
+fn resolve_timecode_offset(
+    tools: &Tools,
+    settings: &ExportSettings,
+    fps: f64,
+    video: Option<&Path>,
+) -> f64 {
+    if let Some(offset) = settings
+        .source_timecode_offset
+        .as_deref()
+        .and_then(|tc| convert_to_seconds(tc, fps))
+    {
+        return offset;
+    }
+
+    if settings.probe_start_timecode {
+        if let Some(offset) = video
+            .and_then(|video| probe_start_timecode(tools, video))
+            .as_deref()
+            .and_then(|tc| convert_to_seconds(tc, fps))
+        {
+            return offset;
+        }
+    }
+
+    0.0
+}
+
+fn probe_start_timecode(tools: &Tools, video: &Path) -> Option<String> {
+    let output = tools
+        .ffprobe()
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("format_tags=timecode:stream_tags=timecode")
+        .arg("-of")
+        .arg("default=noprint_wrappers=1:nokey=1")
+        .arg(video)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().replace(';', ":"))
+        .find(|line| !line.is_empty())
+}
+
+fn env_output_dir() -> Result<Option<PathBuf>, String> {
+    let Ok(raw) = std::env::var(OUTPUT_DIR_ENV) else {
+        return Ok(None);
+    };
+    if raw.trim().is_empty() {
+        return Ok(None);
+    }
+    let path = PathBuf::from(raw.trim());
+    check_writable_dir(&path).map_err(|err| format!("{OUTPUT_DIR_ENV} is ignored: {err}"))?;
+    Ok(Some(path))
+}
+
+fn check_writable_dir(path: &Path) -> Result<(), String> {
+    if !path.is_dir() {
+        return Err(format!("{} is not a directory", path.display()));
+    }
+
+    let probe = path.join(".clipchop-write-test");
+    std::fs::write(&probe, b"").map_err(|e| format!("{} is not writable: {e}", path.display()))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+fn silence_detect_filter(threshold_db: f64) -> String {
+    format!("silencedetect=noise={threshold_db}dB:d=0.1")
+}
+
+fn black_detect_filter(threshold: f64) -> String {
+    format!("blackdetect=d=0.1:pix_th={threshold}")
+}
+
+/// Reads a numeric `key:value` field from an ffmpeg filter log line, with or
+/// without a space after the colon.
+fn log_value(line: &str, key: &str) -> Option<f64> {
+    line.split(key)
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| v.is_finite())
+}
+
+/// Collects `(start, end)` periods from filter log lines. A period still open at
+/// EOF may never get an end line, so its end stays `None`.
+fn log_periods(stderr: &str, start_key: &str, end_key: &str) -> Vec<(f64, Option<f64>)> {
+    let mut periods: Vec<(f64, Option<f64>)> = Vec::new();
+    for line in stderr.lines() {
+        // blackdetect reports start and end on one line, silencedetect on two.
+        if let Some(start) = log_value(line, start_key) {
+            periods.push((start.max(0.0), None));
+        }
+        if let Some(end) = log_value(line, end_key) {
+            if let Some(period) = periods.last_mut().filter(|p| p.1.is_none()) {
+                period.1 = Some(end);
+            }
+        }
+    }
+    periods
+}
+
+/// Seconds covered by a period touching the start and one touching the end of a
+/// `duration`-long range.
+fn edge_periods(periods: &[(f64, Option<f64>)], duration: f64) -> (f64, f64) {
+    let lead = periods
+        .first()
+        .filter(|(start, _)| *start <= 0.05)
+        .map(|(_, end)| end.unwrap_or(duration).min(duration))
+        .unwrap_or(0.0);
+    let trail = periods
+        .last()
+        .filter(|(_, end)| !matches!(end, Some(end) if *end < duration - 0.05))
+        .map(|(start, _)| (duration - start).max(0.0))
+        .unwrap_or(0.0);
+    (lead, trail)
+}
+
+/// Moves a cut's edges in by `lead`/`trail`, keeping the original cut when too
+/// little (under 0.1s) would be left.
+fn tighten_cut(start_sec: f64, end_sec: f64, lead: f64, trail: f64) -> (f64, f64) {
+    if lead + trail > 0.0 && end_sec - trail - (start_sec + lead) >= 0.1 {
+        (start_sec + lead, end_sec - trail)
+    } else {
+        (start_sec, end_sec)
+    }
+}
+
+fn detect_silence_periods(
+    tools: &Tools,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+    source: &Path,
+    start_sec: f64,
+    end_sec: f64,
+    threshold_db: f64,
+) -> Result<Vec<(f64, Option<f64>)>, String> {
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-nostats")
+        .arg("-ss")
+        .arg(start_sec.to_string())
+        .arg("-t")
+        .arg((end_sec - start_sec).to_string())
+        .arg("-i")
+        .arg(source)
+        .arg("-vn")
+        .arg("-af")
+        .arg(silence_detect_filter(threshold_db))
+        .arg("-f")
+        .arg("null")
+        .arg("-");
+    let (status, stderr) = run_ffmpeg(cmd, child_state, None)?;
+    if !status.success() {
+        return Err(stderr.trim().to_string());
+    }
+
+    Ok(log_periods(&stderr, "silence_start:", "silence_end:"))
+}
+
+#[derive(Deserialize)]
+struct LoudnormStats {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+/// ReplayGain 2.0 track gain (dB, against a -18 LUFS reference) and linear peak
+/// of a range, from a loudnorm analysis pass.
+fn measure_replaygain(
+    tools: &Tools,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+    source: &Path,
+    start_sec: f64,
+    duration: f64,
+) -> Result<(f64, f64), String> {
+    let stats = measure_loudnorm(
+        tools,
+        child_state,
+        source,
+        start_sec,
+        duration,
+        "loudnorm=print_format=json",
+    )?;
+    let integrated = stats
+        .input_i
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| "the clip has no measurable audio".to_string())?;
+    let true_peak = stats.input_tp.parse::<f64>().unwrap_or(0.0);
+    Ok((-18.0 - integrated, 10f64.powf(true_peak / 20.0)))
+}
+
+/// Single-pass `loudnorm`, or the linear second pass when the first pass's
+/// measurements are known.
+fn loudnorm_filter(target: f64, measured: Option<&LoudnormStats>) -> String {
+    match measured {
+        Some(stats) => format!(
+            "loudnorm=I={target}:TP=-1.5:LRA=11:measured_I={}:measured_TP={}:measured_LRA={}:\
+             measured_thresh={}:offset={}:linear=true",
+            stats.input_i, stats.input_tp, stats.input_lra, stats.input_thresh, stats.target_offset
+        ),
+        None => format!("loudnorm=I={target}:TP=-1.5:LRA=11"),
+    }
+}
+
+/// Runs a loudnorm analysis pass over a range and reads back its JSON stats.
+/// `filter` must include `print_format=json`.
+fn measure_loudnorm(
+    tools: &Tools,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+    source: &Path,
+    start_sec: f64,
+    duration: f64,
+    filter: &str,
+) -> Result<LoudnormStats, String> {
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-nostats")
+        .arg("-ss")
+        .arg(start_sec.to_string())
+        .arg("-t")
+        .arg(duration.to_string())
+        .arg("-i")
+        .arg(source)
+        .arg("-vn")
+        .arg("-af")
+        .arg(filter)
+        .arg("-f")
+        .arg("null")
+        .arg("-");
+    let (status, stderr) = run_ffmpeg(cmd, child_state, None)?;
+    if !status.success() {
+        return Err(stderr.trim().to_string());
+    }
+
+    // loudnorm prints its stats as the last JSON object on stderr.
+    let json = stderr
+        .rfind('{')
+        .zip(stderr.rfind('}'))
+        .filter(|(open, close)| open < close)
+        .map(|(open, close)| &stderr[open..=close])
+        .ok_or_else(|| "loudnorm printed no measurement".to_string())?;
+    serde_json::from_str(json).map_err(|e| format!("Failed to read loudnorm output: {e}"))
+}
+
+fn replaygain_args(gain_db: f64, peak: f64) -> Vec<String> {
+    vec![
+        "-metadata".to_string(),
+        format!("replaygain_track_gain={gain_db:.2} dB"),
+        "-metadata".to_string(),
+        format!("replaygain_track_peak={peak:.6}"),
+    ]
+}
+
+// Containers whose muxer writes arbitrary global tags.
+fn supports_gain_tags(ext: &str) -> bool {
+    matches!(
+        ext,
+        "mkv" | "mka" | "webm" | "mp4" | "m4v" | "m4a" | "mov" | "mp3" | "flac" | "ogg" | "opus"
+    )
+}
+
+/// Black periods of a whole video, or of a `(start, end)` range with times
+/// relative to the range start.
+fn detect_black_periods(
+    tools: &Tools,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+    source: &Path,
+    range: Option<(f64, f64)>,
+    threshold: f64,
+) -> Result<Vec<(f64, Option<f64>)>, String> {
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-nostats");
+    if let Some((start_sec, end_sec)) = range {
+        cmd.arg("-ss")
+            .arg(start_sec.to_string())
+            .arg("-t")
+            .arg((end_sec - start_sec).to_string());
+    }
+    cmd.arg("-i")
+        .arg(source)
+        .arg("-an")
+        .arg("-vf")
+        .arg(black_detect_filter(threshold))
+        .arg("-f")
+        .arg("null")
+        .arg("-");
+    let (status, stderr) = run_ffmpeg(cmd, child_state, None)?;
+    if !status.success() {
+        return Err(stderr.trim().to_string());
+    }
+
+    Ok(log_periods(&stderr, "black_start:", "black_end:"))
+}
+
+fn probe_duration(tools: &Tools, video: &Path) -> Option<f64> {
+    let output = tools
+        .ffprobe()
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("format=duration")
+        .arg("-of")
+        .arg("default=noprint_wrappers=1:nokey=1")
+        .arg(video)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|d| d.is_finite() && *d > 0.0)
+}
+
+/// Probed length of `source`, remembered so each source is only probed once per run.
+fn cached_duration(
+    tools: &Tools,
+    cache: &mut Vec<(PathBuf, Option<f64>)>,
+    source: &Path,
+) -> Option<f64> {
+    if let Some((_, duration)) = cache.iter().find(|(s, _)| s == source) {
+        return *duration;
+    }
+    let duration = probe_duration(tools, source);
+    cache.push((source.to_path_buf(), duration));
+    duration
+}
+
+/// The last keyframe of `source` at or before `time`, probing each source once.
+fn cached_keyframe(
+    tools: &Tools,
+    cache: &mut Vec<(PathBuf, Result<Vec<f64>, String>)>,
+    source: &Path,
+    time: f64,
+) -> Result<Option<f64>, String> {
+    let at_or_before = |keyframes: &Result<Vec<f64>, String>| {
+        keyframes
+            .as_ref()
+            .map(|keyframes| keyframe_at_or_before(keyframes, time))
+            .map_err(Clone::clone)
+    };
+    if let Some((_, keyframes)) = cache.iter().find(|(s, _)| s == source) {
+        return at_or_before(keyframes);
+    }
+    let keyframes = probe_keyframes(tools, source);
+    let keyframe = at_or_before(&keyframes);
+    cache.push((source.to_path_buf(), keyframes));
+    keyframe
+}
+
+fn probe_video_fps(tools: &Tools, video: &Path) -> Option<f64> {
+    probe_video(tools, video)
+        .ok()
+        .and_then(|info| info.fps)
+        .filter(|fps| fps.is_finite() && *fps > 0.0)
+}
+
+fn probe_frame_size(tools: &Tools, video: &Path) -> Option<(u32, u32)> {
+    let output = tools
+        .ffprobe()
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("v:0")
+        .arg("-show_entries")
+        .arg("stream=width,height")
+        .arg("-of")
+        .arg("csv=p=0:s=x")
+        .arg(video)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    let (w, h) = text.trim().split_once('x')?;
+    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+}
+
+fn probe_keyframes(tools: &Tools, video: &Path) -> Result<Vec<f64>, String> {
+    let output = tools
+        .ffprobe()
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("v:0")
+        .arg("-show_entries")
+        .arg("packet=pts_time,flags")
+        .arg("-of")
+        .arg("csv=p=0")
+        .arg(video)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe could not read keyframes: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let mut keyframes = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (pts, flags) = line.trim().split_once(',')?;
+            if flags.contains('K') {
+                pts.parse::<f64>().ok()
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    keyframes.sort_by(f64::total_cmp);
+
+    if keyframes.is_empty() {
+        return Err("No keyframes found in the video stream".to_string());
+    }
+    Ok(keyframes)
+}
+
+fn keyframe_at_or_before(keyframes: &[f64], time: f64) -> Option<f64> {
+    keyframes.iter().copied().take_while(|k| *k <= time).last()
+}
+
+fn probe_subtitle_tracks(tools: &Tools, video: &Path) -> Result<Vec<SubtitleTrack>, String> {
+    let output = tools
+        .ffprobe()
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("s")
+        .arg("-show_entries")
+        .arg("stream=codec_name:stream_tags=language,title")
+        .arg("-of")
+        .arg("json")
+        .arg(video)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let parsed: FfprobeSubtitleOutput = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {e}"))?;
+    Ok(parsed
+        .streams
+        .into_iter()
+        .enumerate()
+        .map(|(index, stream)| SubtitleTrack {
+            index,
+            // The subtitles filter renders text tracks; bitmap tracks (PGS, DVD)
+            // would need an overlay instead.
+            burnable: matches!(
+                stream.codec_name.as_deref(),
+                Some("subrip" | "ass" | "ssa" | "mov_text" | "webvtt" | "text")
+            ),
+            codec: stream.codec_name,
+            language: stream.tags.language,
+            title: stream.tags.title,
+        })
+        .collect())
+}
+
+/// Burns embedded subtitle track `track` of `source`. The filter matches subtitle
+/// times against frame timestamps, so frames from an input-side seek (which start
+/// at zero) are shifted to source time by `offset_sec` while it runs.
+fn subtitle_filter(source: &Path, track: usize, offset_sec: f64) -> String {
+    let burn = format!(
+        "subtitles=filename={}:si={track}",
+        escape_filter_path(&source.to_string_lossy())
+    );
+    if offset_sec > 0.0 {
+        format!("setpts=PTS+{offset_sec}/TB,{burn},setpts=PTS-STARTPTS")
+    } else {
+        burn
+    }
+}
+
+fn probe_video(tools: &Tools, video: &Path) -> Result<VideoInfo, String> {
+    let output = tools.ffprobe()
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("format=duration,bit_rate:stream=codec_type,codec_name,width,height,channels,r_frame_rate,color_range")
+        .arg("-of")
+        .arg("json")
+        .arg(video)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let parsed: FfprobeOutput = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {e}"))?;
+    let video_stream = parsed
+        .streams
+        .iter()
+        .find(|s| s.codec_type.as_deref() == Some("video"));
+    let audio_stream = parsed
+        .streams
+        .iter()
+        .find(|s| s.codec_type.as_deref() == Some("audio"));
+    let format = parsed.format.as_ref();
+
+    Ok(VideoInfo {
+        duration_sec: format
+            .and_then(|f| f.duration.as_deref())
+            .and_then(|d| d.parse().ok()),
+        bit_rate: format
+            .and_then(|f| f.bit_rate.as_deref())
+            .and_then(|b| b.parse().ok()),
+        width: video_stream.and_then(|s| s.width),
+        height: video_stream.and_then(|s| s.height),
+        fps: video_stream
+            .and_then(|s| s.r_frame_rate.as_deref())
+            .and_then(parse_frame_rate),
+        video_codec: video_stream.and_then(|s| s.codec_name.clone()),
+        audio_codec: audio_stream.and_then(|s| s.codec_name.clone()),
+        audio_channels: audio_stream.and_then(|s| s.channels),
+        color_range: video_stream
+            .and_then(|s| s.color_range.clone())
+            .filter(|r| r == "tv" || r == "pc"),
+    })
+}
+
+fn parse_frame_rate(raw: &str) -> Option<f64> {
+    let fps = match raw.split_once('/') {
+        Some((num, den)) => num.trim().parse::<f64>().ok()? / den.trim().parse::<f64>().ok()?,
+        None => raw.trim().parse::<f64>().ok()?,
+    };
+    Some(fps).filter(|f| f.is_finite() && *f > 0.0)
+}
+
+/// Loads a rules file and merges each rule's settings over the run settings.
+/// Rules match clip names case-insensitively by substring.
+fn load_rules(path: &Path, base: &ExportSettings) -> Result<Vec<SettingsRule>, String> {
+    let raw = std::fs::read(path).map_err(|e| format!("Failed to read rules file: {e}"))?;
+    let file: RulesFile =
+        serde_json::from_slice(&raw).map_err(|e| format!("Invalid rules file: {e}"))?;
+    let base_value =
+        serde_json::to_value(base).map_err(|e| format!("Failed to serialize settings: {e}"))?;
+    let serde_json::Value::Object(base_map) = base_value else {
+        return Err("Failed to serialize settings".to_string());
+    };
+
+    file.rules
+        .into_iter()
+        .enumerate()
+        .map(|(i, rule)| {
+            let name = rule
+                .name
+                .filter(|n| !n.trim().is_empty())
+                .unwrap_or_else(|| format!("rule {}", i + 1));
+            let pattern = rule.pattern.trim().to_lowercase();
+            if pattern.is_empty() {
+                return Err(format!("Rule '{name}' has an empty match pattern"));
+            }
+            let mut merged = base_map.clone();
+            for (key, value) in rule.settings {
+                if key == "rules_file" || !merged.contains_key(&key) {
+                    return Err(format!("Rule '{name}' has an unknown setting: {key}"));
+                }
+                merged.insert(key, value);
+            }
+            let settings = serde_json::from_value(serde_json::Value::Object(merged))
+                .map_err(|e| format!("Rule '{name}' has invalid settings: {e}"))?;
+            Ok(SettingsRule {
+                name,
+                pattern,
+                settings: normalize_settings(settings),
+            })
+        })
+        .collect()
+}
+
+/// The first rule whose pattern appears in the clip name, ignoring case.
+fn matching_rule<'a>(rules: &'a [SettingsRule], clip_name: &str) -> Option<&'a SettingsRule> {
+    let clip_name = clip_name.to_lowercase();
+    rules.iter().find(|rule| clip_name.contains(&rule.pattern))
+}
+
+fn read_manifest(output_dir: &Path) -> Option<ExportManifest> {
+    let raw = std::fs::read(output_dir.join(MANIFEST_FILE)).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+fn write_manifest(output_dir: &Path, manifest: &ExportManifest) -> Result<(), String> {
+    let json = serde_json::to_vec_pretty(manifest)
+        .map_err(|e| format!("Failed to serialize manifest: {e}"))?;
+    std::fs::write(output_dir.join(MANIFEST_FILE), json)
+        .map_err(|e| format!("Failed to write manifest: {e}"))
+}
+
+fn update_manifest(
+    output_dir: &Path,
+    clip_rows: &[ClipRow],
+    row_results: &[RowResult],
+    next_index: usize,
+    run_id: &str,
+) -> Result<(), String> {
+    let mut manifest = read_manifest(output_dir).unwrap_or_default();
+    manifest.next_index = Some(manifest.next_index.unwrap_or(0).max(next_index));
+    for result in row_results.iter().filter(|r| r.status == "success") {
+        let (Some(row), Some(file_name)) = (
+            clip_rows.get(result.row_index),
+            result
+                .output_path
+                .as_deref()
+                .and_then(|p| Path::new(p).strip_prefix(output_dir).ok())
+                .map(|rel| rel.to_string_lossy().replace('\\', "/")),
+        ) else {
+            continue;
+        };
+
+        manifest.clips.retain(|c| c.file_name != file_name);
+        manifest.clips.push(ManifestEntry {
+            file_name,
+            clip_name: row.clip_name.clone(),
+            start_time: row.start_time.clone(),
+            end_time: row.end_time.clone(),
+            run_id: Some(run_id.to_string()),
+        });
+    }
+    write_manifest(output_dir, &manifest)
+}
+
+// Column names match DaVinci Resolve's metadata import, which pairs rows with
+// media pool clips by "File Name".
+fn append_resolve_metadata(path: &Path, record: &[String]) -> Result<(), String> {
+    let is_new = !path.exists();
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open Resolve metadata: {e}"))?;
+    let mut writer = csv::Writer::from_writer(file);
+    if is_new {
+        writer
+            .write_record(["File Name", "Clip Name", "Reel Name", "Scene", "Take"])
+            .map_err(|e| format!("Failed to write Resolve metadata: {e}"))?;
+    }
+    writer
+        .write_record(record)
+        .map_err(|e| format!("Failed to write Resolve metadata: {e}"))?;
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write Resolve metadata: {e}"))
+}
+
+// "Interview take 3" or "shot_t2" -> the trailing number; plain names are take 1.
+fn take_from_name(name: &str) -> String {
+    let digits = name
+        .trim_end()
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>();
+    match digits.chars().rev().collect::<String>().parse::<u32>() {
+        Ok(take) if take > 0 => take.to_string(),
+        _ => "1".to_string(),
+    }
+}
+
+fn list_media_files(dir: &Path) -> Result<Vec<String>, String> {
+    let mut names = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {e}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| MEDIA_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        })
+        .filter_map(|path| path.file_name().map(|n| n.to_string_lossy().to_string()))
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+fn read_clip_rows(
+    csv_path: &str,
+    encoding: &str,
+    timecode_offset: f64,
+    fps: f64,
+) -> Result<Vec<ClipRow>, String> {
+    read_clip_table(csv_path, encoding, timecode_offset, fps).map(|(rows, _)| rows)
+}
+
+/// Reads the clip rows and the columns they came from. Rows with a duration but
+/// no end time get an end of start + duration (see `end_from_duration`).
+fn read_clip_table(
+    csv_path: &str,
+    encoding: &str,
+    timecode_offset: f64,
+    fps: f64,
+) -> Result<(Vec<ClipRow>, CsvColumns), String> {
+    let path = Path::new(csv_path);
+    if !path.exists() {
+        return Err(format!("CSV file not found: {csv_path}"));
+    }
+
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to open CSV: {e}"))?;
+    let text = decode_csv_bytes(&bytes, encoding)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Failed reading CSV headers: {e}"))?
+        .clone();
+
+    let columns = resolve_columns(&headers)?;
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Failed reading CSV rows: {e}"))?;
+        let clip_name = record.get(columns.name).unwrap_or("").trim();
+        let start_time = record.get(columns.start).unwrap_or("").trim();
+        let end_time = match optional_field(&record, columns.end) {
+            Some(end) => end,
+            None => optional_field(&record, columns.duration)
+                .and_then(|duration| end_from_duration(start_time, &duration, timecode_offset, fps))
+                .unwrap_or_default(),
+        };
+
+        if clip_name.is_empty() && start_time.is_empty() && end_time.is_empty() {
+            continue;
+        }
+
+        rows.push(ClipRow {
+            clip_name: clip_name.to_string(),
+            start_time: start_time.to_string(),
+            end_time,
+            overrides: RowOverrides {
+                mode: optional_field(&record, columns.mode),
+                crf: optional_field(&record, columns.crf),
+                preset: optional_field(&record, columns.preset),
+                resolution: optional_field(&record, columns.resolution),
+                source: optional_field(&record, columns.source),
+                crop_x: optional_field(&record, columns.crop_x),
+                crop_y: optional_field(&record, columns.crop_y),
+                crop_w: optional_field(&record, columns.crop_w),
+                crop_h: optional_field(&record, columns.crop_h),
+                audio_offset_ms: optional_field(&record, columns.audio_offset_ms),
+                featured: optional_field(&record, columns.featured),
+                lufs: optional_field(&record, columns.lufs),
+                title: optional_field(&record, columns.title),
+                description: optional_field(&record, columns.description),
+            },
+        });
+    }
+
+    Ok((rows, columns))
+}
+
+/// End time for a start + duration row, written so `clip_time_seconds` reads
+/// it the way it reads the start: `start` begins at `timecode_offset`, and
+/// end-relative starts give end-relative ends (`end` once they reach it).
+fn end_from_duration(
+    start_time: &str,
+    duration: &str,
+    timecode_offset: f64,
+    fps: f64,
+) -> Option<String> {
+    let duration = convert_to_seconds(duration, fps).filter(|d| *d > 0.0)?;
+    let end = if start_time.eq_ignore_ascii_case("start") {
+        timecode_offset + duration
+    } else if counts_from_end(start_time) {
+        let before_end = seconds_before_end(start_time, fps)? - duration;
+        if before_end <= 0.0 {
+            return Some("end".to_string());
+        }
+        -before_end
+    } else {
+        convert_to_seconds(start_time, fps)? + duration
+    };
+    let end = format!("{:.3}", end);
+    Some(end.trim_end_matches('0').trim_end_matches('.').to_string())
+}
+
+fn decode_csv_bytes(bytes: &[u8], encoding: &str) -> Result<String, String> {
+    match encoding {
+        "latin-1" => Ok(bytes.iter().map(|&b| b as char).collect()),
+        "utf-16le" => decode_utf16(bytes, false),
+        "utf-16be" => decode_utf16(bytes, true),
+        "utf-8" => String::from_utf8(bytes.to_vec())
+            .map_err(|_| "CSV is not valid UTF-8. Try setting the CSV encoding.".to_string()),
+        _ => {
+            if bytes.starts_with(&[0xFF, 0xFE]) {
+                decode_utf16(bytes, false)
+            } else if bytes.starts_with(&[0xFE, 0xFF]) {
+                decode_utf16(bytes, true)
+            } else {
+                String::from_utf8(bytes.to_vec()).map_err(|_| {
+                    "CSV is not valid UTF-8. Set the CSV encoding to latin-1 or UTF-16.".to_string()
+                })
+            }
+        }
+    }
+}
+
+fn decode_utf16(bytes: &[u8], big_endian: bool) -> Result<String, String> {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| {
+            if big_endian {
+                u16::from_be_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_le_bytes([pair[0], pair[1]])
+            }
+        })
+        .collect::<Vec<_>>();
+    String::from_utf16(&units).map_err(|_| "CSV contains invalid UTF-16 data".to_string())
+}
+
+fn resolve_columns(headers: &StringRecord) -> Result<CsvColumns, String> {
+    if find_header_index(headers, END_TIME_ALIASES).is_none()
+        && find_header_index(headers, DURATION_ALIASES).is_none()
+    {
+        return Err("CSV missing clip end time or duration column".to_string());
+    }
+
+    Ok(CsvColumns {
+        name: find_header_index(headers, CLIP_NAME_ALIASES)
+            .ok_or_else(|| "CSV missing clip name column".to_string())?,
+        start: find_header_index(headers, START_TIME_ALIASES)
+            .ok_or_else(|| "CSV missing clip start time column".to_string())?,
+        end: find_header_index(headers, END_TIME_ALIASES),
+        duration: find_header_index(headers, DURATION_ALIASES),
+        mode: find_header_index(headers, MODE_ALIASES),
+        crf: find_header_index(headers, CRF_ALIASES),
+        preset: find_header_index(headers, PRESET_ALIASES),
+        resolution: find_header_index(headers, RESOLUTION_ALIASES),
+        source: find_header_index(headers, SOURCE_ALIASES),
+        crop_x: find_header_index(headers, CROP_X_ALIASES),
+        crop_y: find_header_index(headers, CROP_Y_ALIASES),
+        crop_w: find_header_index(headers, CROP_W_ALIASES),
+        crop_h: find_header_index(headers, CROP_H_ALIASES),
+        audio_offset_ms: find_header_index(headers, AUDIO_OFFSET_ALIASES),
+        featured: find_header_index(headers, FEATURED_ALIASES),
+        lufs: find_header_index(headers, LUFS_ALIASES),
+        title: find_header_index(headers, TITLE_ALIASES),
+        description: find_header_index(headers, DESCRIPTION_ALIASES),
+    })
+}
+
+fn optional_field(record: &StringRecord, idx: Option<usize>) -> Option<String> {
+    idx.and_then(|i| record.get(i))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+fn merge_adjacent_clips(
+    rows: Vec<ClipRow>,
+    gap_sec: f64,
+    offset: f64,
+    fps: f64,
+) -> (Vec<ClipRow>, usize) {
+    let mut timed = Vec::new();
+    let mut untimed = Vec::new();
+    for row in rows {
+        match (
+            timeline_seconds(&row.start_time, offset, fps),
+            timeline_seconds(&row.end_time, offset, fps),
+        ) {
+            (Some(start), Some(end)) if end > start => timed.push((start, end, row)),
+            _ => untimed.push(row),
+        }
+    }
+    timed.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut merged: Vec<(f64, f64, ClipRow)> = Vec::new();
+    let mut merge_count = 0usize;
+    for (start, end, row) in timed {
+        if let Some(last) = merged.last_mut() {
+            if start - last.1 < gap_sec {
+                if end > last.1 {
+                    last.1 = end;
+                    last.2.end_time = row.end_time;
+                }
+                merge_count += 1;
+                continue;
+            }
+        }
+        merged.push((start, end, row));
+    }
+
+    let mut result = merged
+        .into_iter()
+        .map(|(_, _, row)| row)
+        .collect::<Vec<_>>();
+    result.extend(untimed);
+    (result, merge_count)
+}
+
+impl RowOverrides {
+    fn has_crop(&self) -> bool {
+        self.crop_x.is_some()
+            || self.crop_y.is_some()
+            || self.crop_w.is_some()
+            || self.crop_h.is_some()
+    }
+}
+
+fn row_audio_offset(overrides: &RowOverrides, default_ms: i32) -> Result<i32, String> {
+    let Some(raw) = overrides.audio_offset_ms.as_deref() else {
+        return Ok(default_ms);
+    };
+
+    raw.parse::<f64>()
+        .ok()
+        .filter(|ms| ms.is_finite() && ms.abs() <= f64::from(MAX_AUDIO_OFFSET_MS))
+        .map(|ms| ms.round() as i32)
+        .ok_or_else(|| {
+            format!("invalid audio_offset_ms '{raw}' (must be within ±{MAX_AUDIO_OFFSET_MS} ms)")
+        })
+}
+
+fn row_loudness_target(
+    overrides: &RowOverrides,
+    default: Option<f64>,
+) -> Result<Option<f64>, String> {
+    let Some(raw) = overrides.lufs.as_deref() else {
+        return Ok(default);
+    };
+
+    raw.parse::<f64>()
+        .ok()
+        .filter(|lufs| LUFS_RANGE.contains(lufs))
+        .map(Some)
+        .ok_or_else(|| {
+            format!(
+                "invalid lufs '{raw}' (must be between {} and {})",
+                LUFS_RANGE.start(),
+                LUFS_RANGE.end()
+            )
+        })
+}
+
+/// `base` with the row's `mode`, `crf`, `preset` and `resolution` cells applied.
+/// Empty cells keep the base value.
+fn row_settings(overrides: &RowOverrides, base: &ExportSettings) -> Result<ExportSettings, String> {
+    let mut settings = base.clone();
+    if let Some(raw) = overrides.mode.as_deref() {
+        let mode = raw.to_lowercase();
+        if !PROCESSING_MODES.contains(&mode.as_str()) {
+            return Err(format!(
+                "invalid mode '{raw}' (use {})",
+                PROCESSING_MODES.join(", ")
+            ));
+        }
+        settings.processing_mode = mode;
+    }
+    if let Some(raw) = overrides.crf.as_deref() {
+        settings.crf = raw
+            .parse::<u8>()
+            .ok()
+            .filter(|crf| CRF_RANGE.contains(crf))
+            .ok_or_else(|| {
+                format!(
+                    "invalid crf '{raw}' (must be between {} and {})",
+                    CRF_RANGE.start(),
+                    CRF_RANGE.end()
+                )
+            })?;
+    }
+    if let Some(raw) = overrides.preset.as_deref() {
+        let preset = raw.to_lowercase();
+        if !X264_PRESETS.contains(&preset.as_str()) {
+            return Err(format!(
+                "invalid preset '{raw}' (use {})",
+                X264_PRESETS.join(", ")
+            ));
+        }
+        settings.preset = preset;
+    }
+    if let Some(raw) = overrides.resolution.as_deref() {
+        let resolution = raw.to_lowercase();
+        if resolution != "source" && resolution_size(&resolution).is_none() {
+            return Err(format!(
+                "invalid resolution '{raw}' (use source, 1080p, 720p, 480p or an even WxH)"
+            ));
+        }
+        settings.resolution = resolution;
+    }
+    Ok(settings)
+}
+
+fn row_featured(overrides: &RowOverrides) -> Result<bool, String> {
+    let Some(raw) = overrides.featured.as_deref() else {
+        return Ok(false);
+    };
+
+    match raw.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "y" | "x" => Ok(true),
+        "0" | "false" | "no" | "n" => Ok(false),
+        _ => Err(format!(
+            "invalid featured value '{raw}' (use yes/no, true/false or 1/0)"
+        )),
+    }
+}
+
+/// Valid rows as (name, start, end) on the source timeline, plus errors for the
+/// rows left out.
+fn timeline_clips(
+    rows: &[ClipRow],
+    timecode_offset: f64,
+    fps: f64,
+    info: &VideoInfo,
+) -> (Vec<(String, f64, f64)>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut clips = Vec::new();
+    for (idx, row) in rows.iter().enumerate() {
+        let time = |ts: &str| clip_time_seconds(ts, timecode_offset, fps, info.duration_sec).ok();
+        match (time(&row.start_time), time(&row.end_time)) {
+            (Some(start), Some(end)) if end > start => {
+                if let Some(duration) = info.duration_sec.filter(|d| end > *d) {
+                    errors.push(format!(
+                        "Row {} end time {} exceeds video length {}",
+                        idx + 2,
+                        row.end_time,
+                        format_timestamp(duration)
+                    ));
+                } else {
+                    clips.push((row.clip_name.clone(), start, end));
+                }
+            }
+            (Some(_), Some(_)) => errors.push(format!(
+                "Row {} end time must be greater than start time",
+                idx + 2
+            )),
+            _ => errors.push(format!("Row {} has an invalid start or end time", idx + 2)),
+        }
+    }
+    (clips, errors)
+}
+
+/// Low-resolution H.264 edit proxy of one clip.
+fn proxy_command(
+    tools: &Tools,
+    source: &Path,
+    start_sec: f64,
+    duration: f64,
+    settings: &ExportSettings,
+    destination: &Path,
+) -> Command {
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-y")
+        .arg("-loglevel")
+        .arg(ffmpeg_loglevel(settings))
+        .arg("-nostats")
+        .args(input_seek_args(start_sec, settings))
+        .arg("-i")
+        .arg(source)
+        .arg("-t")
+        .arg(time_arg(duration, settings))
+        .arg("-vf")
+        .arg(format!("scale=-2:{PROXY_HEIGHT}"))
+        .args(video_encoder_args("libx264", "veryfast", PROXY_CRF))
+        .arg("-c:a")
+        .arg("aac")
+        .arg("-b:a")
+        .arg("128k")
+        .arg("-movflags")
+        .arg("+faststart")
+        .arg(destination);
+    cmd
+}
+
+fn build_fcpxml(source: &Path, info: &VideoInfo, fps: f64, clips: &[(String, f64, f64)]) -> String {
+    // FCPXML wants rational times on the frame grid: frames * den / num seconds.
+    let (num, den) = fcpxml_frame_rate(fps);
+    let time = |seconds: f64| {
+        let frames = (seconds * num as f64 / den as f64).round() as u64;
+        format!("{}/{}s", frames * den, num)
+    };
+    let source_name = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "source".to_string());
+    let source_duration = info
+        .duration_sec
+        .unwrap_or_else(|| clips.iter().map(|c| c.2).fold(0.0, f64::max));
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE fcpxml>\n");
+    xml.push_str("<fcpxml version=\"1.8\">\n  <resources>\n");
+    xml.push_str(&format!(
+        "    <format id=\"r1\" frameDuration=\"{}/{}s\" width=\"{}\" height=\"{}\"/>\n",
+        den,
+        num,
+        info.width.unwrap_or(1920),
+        info.height.unwrap_or(1080)
+    ));
+    xml.push_str(&format!(
+        "    <asset id=\"r2\" name=\"{}\" src=\"{}\" start=\"0s\" duration=\"{}\" hasVideo=\"1\" hasAudio=\"{}\" format=\"r1\"/>\n",
+        xml_escape(&source_name),
+        xml_escape(&file_url(source)),
+        time(source_duration),
+        u8::from(info.audio_codec.is_some())
+    ));
+    xml.push_str("  </resources>\n  <library>\n    <event name=\"ClipChop\">\n");
+    xml.push_str(&format!(
+        "      <project name=\"{}\">\n        <sequence format=\"r1\" tcStart=\"0s\" tcFormat=\"NDF\">\n          <spine>\n",
+        xml_escape(&source_name)
+    ));
+
+    let mut offset = 0.0;
+    for (name, start, end) in clips {
+        xml.push_str(&format!(
+            "            <asset-clip ref=\"r2\" name=\"{}\" offset=\"{}\" start=\"{}\" duration=\"{}\"/>\n",
+            xml_escape(name),
+            time(offset),
+            time(*start),
+            time(end - start)
+        ));
+        offset += end - start;
+    }
+
+    xml.push_str("          </spine>\n        </sequence>\n      </project>\n    </event>\n  </library>\n</fcpxml>\n");
+    xml
+}
+
+fn fcpxml_frame_rate(fps: f64) -> (u64, u64) {
+    for (num, den) in [(24000, 1001), (30000, 1001), (60000, 1001)] {
+        if (fps - num as f64 / den as f64).abs() < 0.01 {
+            return (num, den);
+        }
+    }
+    (fps.round().max(1.0) as u64, 1)
+}
+
+fn file_url(path: &Path) -> String {
+    let raw = path.to_string_lossy().replace('\\', "/");
+    let mut url = String::from(if raw.starts_with('/') {
+        "file://"
+    } else {
+        "file:///"
+    });
+    for byte in raw.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' | b':' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    url
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The row's `source` cell, or `default` when it has none. Relative paths are
+/// resolved against `source_dir`, or the default video's folder without one.
+fn row_source_video(
+    overrides: &RowOverrides,
+    default: &Path,
+    source_dir: Option<&Path>,
+) -> Result<PathBuf, String> {
+    let Some(raw) = overrides.source.as_deref() else {
+        return Ok(default.to_path_buf());
+    };
+
+    let path = Path::new(raw);
+    let resolved = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        source_dir
+            .or_else(|| default.parent())
+            .unwrap_or(Path::new("."))
+            .join(path)
+    };
+    if resolved.is_file() {
+        Ok(resolved)
+    } else {
+        Err(format!("source video not found: {}", resolved.display()))
+    }
+}
+
+fn row_crop(
+    overrides: &RowOverrides,
+    frame_size: Option<(u32, u32)>,
+) -> Result<Option<CropRect>, String> {
+    if !overrides.has_crop() {
+        return Ok(None);
+    }
+
+    let parse = |value: &Option<String>, label: &str| -> Result<u32, String> {
+        let raw = value.as_deref().ok_or_else(|| {
+            format!("crop needs crop_x, crop_y, crop_w and crop_h ({label} missing)")
+        })?;
+        raw.parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .map(|v| v.round() as u32)
+            .ok_or_else(|| format!("invalid {label} '{raw}'"))
+    };
+
+    let crop = CropRect {
+        x: parse(&overrides.crop_x, "crop_x")?,
+        y: parse(&overrides.crop_y, "crop_y")?,
+        w: parse(&overrides.crop_w, "crop_w")? / 2 * 2,
+        h: parse(&overrides.crop_h, "crop_h")? / 2 * 2,
+    };
+
+    if crop.w == 0 || crop.h == 0 {
+        return Err("crop width and height must be at least 2 pixels".to_string());
+    }
+
+    if let Some((width, height)) = frame_size {
+        if crop.x + crop.w > width || crop.y + crop.h > height {
+            return Err(format!(
+                "crop {}x{}+{}+{} does not fit the {}x{} source frame",
+                crop.w, crop.h, crop.x, crop.y, width, height
+            ));
+        }
+    }
+
+    Ok(Some(crop))
+}
+
+fn find_header_index(headers: &StringRecord, aliases: &[&str]) -> Option<usize> {
+    let normalized_aliases = aliases
+        .iter()
+        .map(|alias| normalize_header(alias))
+        .collect::<Vec<_>>();
+
+    let normalized = headers
+        .iter()
+        .enumerate()
+        .map(|(i, h)| (i, normalize_header(h)))
+        .collect::<Vec<_>>();
+
+    normalized
+        .into_iter()
+        .find(|(_, header)| normalized_aliases.iter().any(|alias| header == alias))
+        .map(|(i, _)| i)
+}
+
+fn normalize_settings(input: ExportSettings) -> ExportSettings {
+    let processing_mode = if PROCESSING_MODES.contains(&input.processing_mode.as_str()) {
+        input.processing_mode
+    } else {
+        "copy_fast".to_string()
+    };
+
+    let preset = if X264_PRESETS.contains(&input.preset.as_str()) {
+        input.preset
+    } else {
+        "ultrafast".to_string()
+    };
+
+    let social_preset = input
+        .social_preset
+        .as_deref()
+        .map(|preset| preset.trim().to_lowercase())
+        .filter(|preset| matches!(preset.as_str(), "tiktok" | "reels" | "shorts"));
+    // Social presets level audio for the platforms unless a target is set.
+    let social_loudness = social_preset.is_some().then_some(SOCIAL_LOUDNESS_LUFS);
+    let resolution = input.resolution.trim().to_lowercase();
+    let resolution = if social_preset.is_some() {
+        SOCIAL_RESOLUTION.to_string()
+    } else if resolution_size(&resolution).is_some() {
+        resolution
+    } else {
+        "source".to_string()
+    };
+
+    let audio_codec = match input.audio_codec.as_str() {
+        "copy" if input.exact_frames => "aac".to_string(),
+        "aac" | "copy" | "none" => input.audio_codec,
+        _ => "aac".to_string(),
+    };
+
+    let audio_bitrate_kbps = input.audio_bitrate_kbps.clamp(64, 320);
+    let crf = input.crf.clamp(*CRF_RANGE.start(), *CRF_RANGE.end());
+
+    let fps = match input.fps {
+        Some(value) if value.is_finite() && (1.0..=120.0).contains(&value) => Some(value),
+        _ => None,
+    };
+
+    let assumed_fps = input
+        .assumed_fps
+        .filter(|fps| fps.is_finite() && (1.0..=240.0).contains(fps));
+
+    let source_timecode_offset = input
+        .source_timecode_offset
+        .map(|tc| tc.trim().to_string())
+        .filter(|tc| convert_to_seconds(tc, assumed_fps.unwrap_or(DEFAULT_TIMECODE_FPS)).is_some());
+
+    let watermark_position = match input.watermark_position.as_str() {
+        "top_left" | "top_right" | "bottom_left" | "bottom_right" | "center" => {
+            input.watermark_position
+        }
+        _ => "bottom_right".to_string(),
+    };
+    let watermark_opacity = if input.watermark_opacity.is_finite() {
+        input.watermark_opacity.clamp(0.0, 1.0)
+    } else {
+        0.5
+    };
+    let watermark_scale = if input.watermark_scale.is_finite() {
+        input.watermark_scale.clamp(0.01, 1.0)
+    } else {
+        0.15
+    };
+
+    let fallback_naming = match input.fallback_naming.as_str() {
+        "literal" | "index" | "source_stem" | "timecode" => input.fallback_naming,
+        _ => "literal".to_string(),
+    };
+
+    let downmix = match input.downmix.as_str() {
+        "none" | "stereo" => input.downmix,
+        _ => "none".to_string(),
+    };
+
+    let denoise = match input.denoise.as_str() {
+        "none" | "light" | "medium" => input.denoise,
+        _ => "none".to_string(),
+    };
+
+    let silence_threshold_db = if input.silence_threshold_db.is_finite() {
+        input
+            .silence_threshold_db
+            .clamp(*SILENCE_THRESHOLD_DB.start(), *SILENCE_THRESHOLD_DB.end())
+    } else {
+        -50.0
+    };
+    let black_threshold = if (0.0..=1.0).contains(&input.black_threshold) {
+        input.black_threshold
+    } else {
+        0.1
+    };
+    let black_max_trim_sec = if input.black_max_trim_sec.is_finite() {
+        input.black_max_trim_sec.clamp(0.1, MAX_EDGE_TRIM_SEC)
+    } else {
+        2.0
+    };
+    let silence_max_trim_sec = if input.silence_max_trim_sec.is_finite() {
+        input.silence_max_trim_sec.clamp(0.1, MAX_EDGE_TRIM_SEC)
+    } else {
+        1.5
+    };
+
+    let parallel_scope = match input.parallel_scope.as_str() {
+        "global" | "per_source" => input.parallel_scope,
+        _ => "global".to_string(),
+    };
+
+    let color_range = match input.color_range.as_str() {
+        "auto" | "tv" | "pc" => input.color_range,
+        _ => "auto".to_string(),
+    };
+
+    let csv_encoding = match input.csv_encoding.as_str() {
+        "auto" | "utf-8" | "utf-16le" | "utf-16be" | "latin-1" => input.csv_encoding,
+        _ => "auto".to_string(),
+    };
+
+    let webp_quality = input.webp_quality.min(100);
+    let preview_fps = if input.preview_fps.is_finite() {
+        input.preview_fps.clamp(1.0, 30.0)
+    } else {
+        12.0
+    };
+    let preview_width = input.preview_width.clamp(64, 1920) / 2 * 2;
+    let bookend_width = input.bookend_width.clamp(64, 1920) / 2 * 2;
+    let collision_suffix = if input.collision_suffix.contains("{n}")
+        && !input.collision_suffix.contains(['/', '\\'])
+    {
+        input.collision_suffix
+    } else {
+        "-{n}".to_string()
+    };
+
+    ExportSettings {
+        processing_mode,
+        preset,
+        crf,
+        resolution,
+        social_preset,
+        crop_w: input.crop_w.map(|w| w / 2 * 2).filter(|w| *w > 0),
+        crop_h: input.crop_h.map(|h| h / 2 * 2).filter(|h| *h > 0),
+        crop_x: input.crop_x,
+        crop_y: input.crop_y,
+        audio_codec,
+        audio_bitrate_kbps,
+        fps,
+        webp_quality,
+        preview_fps,
+        preview_width,
+        source_timecode_offset,
+        probe_start_timecode: input.probe_start_timecode,
+        output_mode: input
+            .output_mode
+            .filter(|mode| parse_octal_mode(mode).is_some()),
+        merge_gap_sec: input
+            .merge_gap_sec
+            .filter(|gap| gap.is_finite() && *gap > 0.0),
+        exact_frames: input.exact_frames,
+        fragmented_mp4: input.fragmented_mp4,
+        accurate_seek: input.accurate_seek,
+        watermark_image: input
+            .watermark_image
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+        watermark_position,
+        watermark_opacity,
+        watermark_scale,
+        csv_encoding,
+        audio_offset_ms: input
+            .audio_offset_ms
+            .clamp(-MAX_AUDIO_OFFSET_MS, MAX_AUDIO_OFFSET_MS),
+        export_bookends: input.export_bookends,
+        bookend_width,
+        generate_thumbnail: input.generate_thumbnail,
+        thumbnail_position: match input.thumbnail_position.as_str() {
+            "start" | "midpoint" | "offset" => input.thumbnail_position,
+            _ => "midpoint".to_string(),
+        },
+        thumbnail_offset_sec: Some(input.thumbnail_offset_sec)
+            .filter(|offset| offset.is_finite() && *offset >= 0.0)
+            .unwrap_or(0.0),
+        thumbnail_width: input.thumbnail_width.clamp(64, 3840) / 2 * 2,
+        rename_on_collision: input.rename_on_collision,
+        collision_suffix,
+        on_conflict: match input.on_conflict.as_str() {
+            "overwrite" | "skip" | "rename" => input.on_conflict,
+            _ => "overwrite".to_string(),
+        },
+        autoswap_times: input.autoswap_times,
+        force_keyframe_at_start: input.force_keyframe_at_start,
+        fixed_duration: input
+            .fixed_duration
+            .filter(|target| target.is_finite() && *target > 0.0),
+        end_hold_sec: input.end_hold_sec,
+        fade_in_sec: input.fade_in_sec,
+        fade_out_sec: input.fade_out_sec,
+        fade_audio: input.fade_audio,
+        chapters_per_source: input.chapters_per_source,
+        loudness_target: input
+            .loudness_target
+            .filter(|lufs| LUFS_RANGE.contains(lufs))
+            .or(input.normalize_audio.then_some(NORMALIZE_LOUDNESS_LUFS))
+            .or(social_loudness),
+        normalize_audio: input.normalize_audio,
+        loudnorm_two_pass: input.loudnorm_two_pass,
+        delete_source_on_success: input.delete_source_on_success,
+        confirm_delete_source: input.confirm_delete_source,
+        denoise,
+        sharpen: input.sharpen,
+        parallel_scope,
+        color_range,
+        start_index: input.start_index,
+        continue_numbering: input.continue_numbering,
+        resolve_export: input.resolve_export,
+        copy_container_fallback: input.copy_container_fallback,
+        max_total_duration: input
+            .max_total_duration
+            .filter(|budget| budget.is_finite() && *budget > 0.0),
+        unique_suffix: input
+            .unique_suffix
+            .map(|len| len.clamp(*UNIQUE_SUFFIX_LEN.start(), *UNIQUE_SUFFIX_LEN.end())),
+        filename_template: input
+            .filename_template
+            .map(|template| template.trim().to_string())
+            .filter(|template| filename_template_is_valid(template)),
+        trim_edge_silence: input.trim_edge_silence,
+        silence_threshold_db,
+        silence_max_trim_sec,
+        rules_file: input
+            .rules_file
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+        progress_log_file: input
+            .progress_log_file
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+        max_parallel: input.max_parallel.clamp(1, MAX_PARALLEL),
+        downmix,
+        trim_black: input.trim_black,
+        black_threshold,
+        black_max_trim_sec,
+        assumed_fps,
+        fallback_naming,
+        intro_file: input
+            .intro_file
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+        outro_file: input
+            .outro_file
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+        clamp_to_source: input.clamp_to_source,
+        muxer_format: input
+            .muxer_format
+            .map(|format| format.trim().to_lowercase())
+            .filter(|format| !format.is_empty()),
+        output_extension: input
+            .output_extension
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| {
+                !ext.is_empty() && ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric())
+            }),
+        locked_retries: input.locked_retries.min(MAX_LOCKED_RETRIES),
+        encoder: if VIDEO_ENCODERS.contains(&input.encoder.as_str()) {
+            input.encoder
+        } else {
+            "libx264".to_string()
+        },
+        output_format: input
+            .output_format
+            .map(|format| format.trim().to_lowercase())
+            .filter(|format| !format.is_empty()),
+        preserve_mtime: input.preserve_mtime,
+        concat: input.concat,
+        fallback_reencode: input.fallback_reencode,
+        snap_to_keyframe: input.snap_to_keyframe,
+        accurate_copy: input.accurate_copy,
+        burn_subtitle_track: input.burn_subtitle_track,
+        burn_label: input.burn_label,
+        label_position: match input.label_position.as_str() {
+            "top_left" | "top_right" | "bottom_left" | "bottom_right" | "center" => {
+                input.label_position
+            }
+            _ => "top_left".to_string(),
+        },
+        label_font_size: input.label_font_size.clamp(8, 200),
+        label_font_file: input
+            .label_font_file
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+        replaygain_tag: input.replaygain_tag,
+        report_path: input
+            .report_path
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+        verbose: input.verbose,
+        time_precision: input.time_precision,
+        strict_validation: input.strict_validation,
+        ffmpeg_path: input
+            .ffmpeg_path
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+        ffprobe_path: input
+            .ffprobe_path
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+        source_dir: input
+            .source_dir
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty()),
+        fast_segment_split: input.fast_segment_split,
+    }
+}
+
+fn webp_preview_args(settings: &ExportSettings, filters: &[String]) -> Vec<String> {
+    let mut chain = filters
+        .iter()
+        .filter(|f| !f.starts_with("scale="))
+        .cloned()
+        .collect::<Vec<_>>();
+    chain.push(format!(
+        "fps={},scale={}:-2:flags=lanczos",
+        settings.preview_fps, settings.preview_width
+    ));
+
+    vec![
+        "-vf".to_string(),
+        filter_graph(&chain, settings).unwrap_or_default(),
+        "-c:v".to_string(),
+        "libwebp_anim".to_string(),
+        "-lossless".to_string(),
+        "0".to_string(),
+        "-q:v".to_string(),
+        settings.webp_quality.to_string(),
+        "-loop".to_string(),
+        "0".to_string(),
+        "-an".to_string(),
+    ]
+}
+
+/// Source time of a clip's thumbnail, kept on a frame inside the clip.
+fn thumbnail_time(settings: &ExportSettings, start_sec: f64, end_sec: f64, frame_sec: f64) -> f64 {
+    let at_sec = match settings.thumbnail_position.as_str() {
+        "start" => start_sec,
+        "offset" => start_sec + settings.thumbnail_offset_sec,
+        _ => (start_sec + end_sec) / 2.0,
+    };
+    at_sec.clamp(start_sec, (end_sec - frame_sec).max(start_sec))
+}
+
+fn extract_frame(
+    tools: &Tools,
+    child_state: &Arc<Mutex<Vec<Child>>>,
+    source: &Path,
+    at_sec: f64,
+    width: u32,
+    filters: &[String],
+    destination: &Path,
+) -> Result<(), String> {
+    let mut chain = filters
+        .iter()
+        .filter(|f| !f.starts_with("scale="))
+        .cloned()
+        .collect::<Vec<_>>();
+    chain.push(format!("scale={width}:-2"));
+
+    let mut cmd = tools.ffmpeg();
+    cmd.arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-ss")
+        .arg(at_sec.max(0.0).to_string())
+        .arg("-i")
+        .arg(source)
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-vf")
+        .arg(chain.join(","))
+        .arg("-q:v")
+        .arg("3")
+        .arg(destination);
+    let (status, stderr) = run_ffmpeg(cmd, child_state, None)?;
+
+    if status.success() && destination.exists() {
+        Ok(())
+    } else {
+        Err(stderr.trim().to_string())
+    }
+}
+
+fn sidecar_path(destination: &Path, label: &str, ext: &str) -> PathBuf {
+    let stem = destination
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    destination.with_file_name(format!("{stem}-{label}.{ext}"))
+}
+
+// "auto" keeps (and tags) the source range; "tv"/"pc" convert to that range.
+// Returns the scale filter to add, if any, and the range to tag the output with.
+fn color_range_conversion(
+    setting: &str,
+    source_range: Option<String>,
+) -> (Option<String>, Option<String>) {
+    match setting {
+        "tv" | "pc" => {
+            let filter = match source_range.as_deref() {
+                Some(src) if src == setting => None,
+                Some(src) => Some(format!("scale=in_range={src}:out_range={setting}")),
+                None => Some(format!("scale=out_range={setting}")),
+            };
+            (filter, Some(setting.to_string()))
+        }
+        _ => (None, source_range),
+    }
+}
+
+fn end_pad_filters(filters: &[String], pad_sec: Option<f64>) -> Vec<String> {
+    let mut chain = filters.to_vec();
+    if let Some(pad) = pad_sec {
+        chain.push(format!("tpad=stop_mode=clone:stop_duration={pad}"));
+    }
+    chain
+}
+
+/// Scales both fades down by the same factor when together they're longer than
+/// the clip. The flag reports whether they were shortened.
+fn fit_fades(fade_in: f64, fade_out: f64, length: f64) -> (f64, f64, bool) {
+    let total = fade_in + fade_out;
+    if total <= length || total <= 0.0 {
+        return (fade_in, fade_out, false);
+    }
+    let scale = length.max(0.0) / total;
+    (fade_in * scale, fade_out * scale, true)
+}
+
+/// `fade` or `afade` filters for a clip of `length` seconds whose first frame
+/// sits at `base` on the filter timeline.
+fn fade_filters(
+    filter: &str,
+    fade_in: f64,
+    fade_out: f64,
+    base: f64,
+    length: f64,
+    settings: &ExportSettings,
+) -> Vec<String> {
+    let mut filters = Vec::new();
+    if fade_in > 0.0 {
+        filters.push(format!(
+            "{filter}=t=in:st={}:d={}",
+            time_arg(base, settings),
+            time_arg(fade_in, settings)
+        ));
+    }
+    if fade_out > 0.0 {
+        filters.push(format!(
+            "{filter}=t=out:st={}:d={}",
+            time_arg(base + length - fade_out, settings),
+            time_arg(fade_out, settings)
+        ));
+    }
+    filters
+}
+
+/// Stereo downmix for surround sources: centre and surrounds folded in at -3 dB,
+/// LFE dropped, gains normalised (`<`) so the mix can't clip. Returns `None` for
+/// layouts without a known matrix, which fall back to ffmpeg's `-ac 2`.
+fn downmix_filter(channels: u32) -> Option<String> {
+    // 5.1 and 7.1 share the FL FR FC LFE order; surrounds follow.
+    let surrounds: &[(&str, &str)] = match channels {
+        6 => &[("c4", "c5")],
+        8 => &[("c4", "c5"), ("c6", "c7")],
+        _ => return None,
+    };
+    let (left, right) = surrounds.iter().fold(
+        ("c0+0.707*c2".to_string(), "c1+0.707*c2".to_string()),
+        |(left, right), (l, r)| (format!("{left}+0.707*{l}"), format!("{right}+0.707*{r}")),
+    );
+    Some(format!("pan=stereo|FL<{left}|FR<{right}"))
+}
+
+fn video_filters(settings: &ExportSettings, crop: Option<&CropRect>) -> Vec<String> {
+    let mut filters = Vec::new();
+    // A row's crop box is in source pixels, so it replaces the run-wide crop.
+    match crop {
+        Some(crop) => filters.push(format!("crop={}:{}:{}:{}", crop.w, crop.h, crop.x, crop.y)),
+        None => filters.extend(settings_crop_filter(settings)),
+    }
+    // Denoise before scaling so the filter sees the original grain; sharpen after
+    // scaling so it works on the final pixels.
+    match settings.denoise.as_str() {
+        "light" => filters.push("hqdn3d=2:1:2:3".to_string()),
+        "medium" => filters.push("hqdn3d=4:3:6:4.5".to_string()),
+        _ => {}
+    }
+    if settings.social_preset.is_some() {
+        // Fill the vertical frame instead of letterboxing a landscape source.
+        filters.extend(social_crop_filters());
+    } else {
+        filters.extend(resolution_filter(&settings.resolution));
+    }
+    if settings.sharpen {
+        filters.push("unsharp=5:5:0.8:5:5:0.0".to_string());
+    }
+    filters
+}
+
+/// Centre crop to 9:16, then scale to the social preset's frame size.
+fn social_crop_filters() -> [String; 2] {
+    let (w, h) = resolution_size(SOCIAL_RESOLUTION).unwrap_or((1080, 1920));
+    [
+        "crop=w='min(iw,ih*9/16)':h='min(ih,iw*16/9)'".to_string(),
+        format!("scale={w}:{h},setsar=1"),
+    ]
+}
+
+/// `crop=` filter from the run-wide `crop_*` settings. Missing sides keep the
+/// full frame and a missing offset centres the box, as ffmpeg's crop does.
+fn settings_crop_filter(settings: &ExportSettings) -> Option<String> {
+    if !has_settings_crop(settings) {
+        return None;
+    }
+    let side = |value: Option<u32>, full: &str| value.map_or(full.to_string(), |v| v.to_string());
+    Some(format!(
+        "crop={}:{}:{}:{}",
+        side(settings.crop_w, "iw"),
+        side(settings.crop_h, "ih"),
+        side(settings.crop_x, "(iw-ow)/2"),
+        side(settings.crop_y, "(ih-oh)/2")
+    ))
+}
+
+fn has_settings_crop(settings: &ExportSettings) -> bool {
+    settings.crop_w.is_some()
+        || settings.crop_h.is_some()
+        || settings.crop_x.is_some()
+        || settings.crop_y.is_some()
+}
+
+/// `drawtext` filter that writes the clip name in a corner of the frame.
+/// Expansion is off so `%` in a name is drawn as-is; control characters,
+/// which `check_filter_text` would reject, become spaces.
+fn label_filter(clip_name: &str, settings: &ExportSettings) -> String {
+    let text: String = clip_name
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let margin = 20;
+    let (x, y) = match settings.label_position.as_str() {
+        "top_right" => (format!("w-tw-{margin}"), format!("{margin}")),
+        "bottom_left" => (format!("{margin}"), format!("h-th-{margin}")),
+        "bottom_right" => (format!("w-tw-{margin}"), format!("h-th-{margin}")),
+        "center" => ("(w-tw)/2".to_string(), "(h-th)/2".to_string()),
+        _ => (format!("{margin}"), format!("{margin}")),
+    };
+    let font = settings
+        .label_font_file
+        .as_deref()
+        .map(|path| format!("fontfile={}:", escape_filter_path(path)))
+        .unwrap_or_default();
+    format!(
+        "drawtext={font}text={}:expansion=none:fontsize={}:fontcolor=white:\
+         box=1:boxcolor=black@0.5:boxborderw=8:x={x}:y={y}",
+        escape_filter_text(&text),
+        settings.label_font_size
+    )
+}
+
+fn filter_graph(chain: &[String], settings: &ExportSettings) -> Option<String> {
+    let Some(image) = settings.watermark_image.as_deref() else {
+        return if chain.is_empty() {
+            None
+        } else {
+            Some(chain.join(","))
+        };
+    };
+
+    let base = if chain.is_empty() {
+        "null".to_string()
+    } else {
+        chain.join(",")
+    };
+    let margin = 20;
+    let (x, y) = match settings.watermark_position.as_str() {
+        "top_left" => (format!("{margin}"), format!("{margin}")),
+        "top_right" => (format!("W-w-{margin}"), format!("{margin}")),
+        "bottom_left" => (format!("{margin}"), format!("H-h-{margin}")),
+        "center" => ("(W-w)/2".to_string(), "(H-h)/2".to_string()),
+        _ => (format!("W-w-{margin}"), format!("H-h-{margin}")),
+    };
+
+    Some(format!(
+        "movie={},format=rgba,colorchannelmixer=aa={}[wm];\
+         [in]{}[base];\
+         [wm][base]scale2ref=w=main_w*{}:h=ow/dar[wms][base2];\
+         [base2][wms]overlay={}:{}[out]",
+        escape_filter_path(image),
+        settings.watermark_opacity,
+        base,
+        settings.watermark_scale,
+        x,
+        y
+    ))
+}
+
+// All user text placed in a filtergraph goes through these two. Control
+// characters are rejected; everything else is escaped for the option parser and
+// then quoted for the graph parser, so `:`, `'`, `\`, `,`, `;` and brackets stay literal.
+fn check_filter_text(value: &str, what: &str) -> Result<(), String> {
+    if value.chars().any(char::is_control) {
+        return Err(format!("{what} contains control characters"));
+    }
+    Ok(())
+}
+
+fn escape_filter_text(value: &str) -> String {
+    let option_level = value
+        .replace('\\', "\\\\")
+        .replace(':', "\\:")
+        .replace('\'', "\\'");
+    format!("'{}'", option_level.replace('\'', "'\\''"))
+}
+
+fn escape_filter_path(path: &str) -> String {
+    escape_filter_text(&path.replace('\\', "/"))
+}
+
+/// Frame size of a named preset or a custom `WxH` value with positive, even
+/// dimensions; `None` for `source` and anything malformed.
+fn resolution_size(resolution: &str) -> Option<(u32, u32)> {
+    match resolution {
+        "1080p" => Some((1920, 1080)),
+        "720p" => Some((1280, 720)),
+        "480p" => Some((854, 480)),
+        custom => {
+            let (w, h) = custom.split_once('x')?;
+            let even = |v: &str| v.parse::<u32>().ok().filter(|v| *v > 0 && v % 2 == 0);
+            Some((even(w)?, even(h)?))
+        }
+    }
+}
+
+fn resolution_filter(resolution: &str) -> Option<String> {
+    let (w, h) = resolution_size(resolution)?;
+
+    Some(format!(
+        "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2"
+    ))
+}
+
+/// A cut time for ffmpeg at `time_precision` decimal places, so repeating
+/// fractions like 83.33333333333333 don't reach the command line.
+fn time_arg(seconds: f64, settings: &ExportSettings) -> String {
+    let precision = settings.time_precision.min(MAX_TIME_PRECISION);
+    format!("{seconds:.precision$}")
+}
+
+// -accurate_seek/-noaccurate_seek are input options, so they only take effect
+// when -ss is placed before -i. The precise mode seeks on the output side and
+// always decodes up to the start point, so it never needs them.
+fn input_seek_args(start_sec: f64, settings: &ExportSettings) -> Vec<String> {
+    let mut args = vec!["-ss".to_string(), time_arg(start_sec, settings)];
+    match settings.accurate_seek {
+        Some(true) => args.push("-accurate_seek".to_string()),
+        Some(false) => args.push("-noaccurate_seek".to_string()),
+        None => {}
+    }
+    args
+}
+
+/// Stream-copy cut. Input seeking (`-ss` before `-i`) is fast but can start audio
+/// ahead of the first video keyframe; `accurate_copy` seeks on the output side
+/// instead, which reads from the start of the file but keeps the streams aligned.
+/// Either way timestamps are shifted to start at zero so players don't insert a
+/// gap or drop leading audio.
+fn copy_cut_args(
+    source: &Path,
+    start_sec: f64,
+    duration: f64,
+    settings: &ExportSettings,
+) -> Vec<std::ffi::OsString> {
+    let mut args = Vec::<std::ffi::OsString>::new();
+    if !settings.accurate_copy {
+        args.extend(
+            input_seek_args(start_sec, settings)
+                .into_iter()
+                .map(Into::into),
+        );
+    }
+    args.push("-i".into());
+    args.push(source.as_os_str().to_os_string());
+    if settings.accurate_copy {
+        args.extend(["-ss".into(), time_arg(start_sec, settings).into()]);
+    }
+    for arg in [
+        "-t".to_string(),
+        time_arg(duration, settings),
+        "-c".to_string(),
+        "copy".to_string(),
+        "-avoid_negative_ts".to_string(),
+        "make_zero".to_string(),
+    ] {
+        args.push(arg.into());
+    }
+    args
+}
+
+/// Re-encode of a Copy Streams cut at the source's frame size, used when the
+/// stream copy fails.
+fn reencode_cut_args(
+    source: &Path,
+    start_sec: f64,
+    duration: f64,
+    video_args: Vec<String>,
+    audio_encoder: &str,
+    settings: &ExportSettings,
+) -> Vec<std::ffi::OsString> {
+    let mut args = vec![
+        "-ss".into(),
+        time_arg(start_sec, settings).into(),
+        "-i".into(),
+        source.as_os_str().to_os_string(),
+        "-t".into(),
+        time_arg(duration, settings).into(),
+    ];
+    args.extend(video_args.into_iter().map(Into::into));
+    let audio: Vec<String> = match settings.audio_codec.as_str() {
+        "none" => vec!["-an".to_string()],
+        "copy" => vec!["-c:a".to_string(), "copy".to_string()],
+        _ => vec![
+            "-c:a".to_string(),
+            audio_encoder.to_string(),
+            "-b:a".to_string(),
+            format!("{}k", settings.audio_bitrate_kbps),
+        ],
+    };
+    args.extend(audio.into_iter().map(Into::into));
+    args
+}
+
+// A positive offset delays the audio, a negative one advances it. The source is
+// opened a second time with -itsoffset so only its audio timestamps shift; any
+// input seek must be repeated so both inputs start at the same point.
+fn audio_offset_args(source: &Path, seek: &[String], offset_ms: i32) -> Vec<std::ffi::OsString> {
+    let mut args = seek
+        .iter()
+        .map(Into::into)
+        .collect::<Vec<std::ffi::OsString>>();
+    args.push("-itsoffset".into());
+    args.push((f64::from(offset_ms) / 1000.0).to_string().into());
+    args.push("-i".into());
+    args.push(source.as_os_str().to_os_string());
+    for map in ["-map", "0:v:0", "-map", "1:a:0?"] {
+        args.push(map.into());
+    }
+    args
+}
+
+fn output_extension(mode: &str, source: &Path, output_format: Option<&str>) -> String {
+    match (mode, output_format) {
+        ("webp", _) => "webp".to_string(),
+        ("copy_fast", None) | (_, Some("source")) => source
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|s| s.to_lowercase())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "mp4".to_string()),
+        (_, Some(format)) => format.to_string(),
+        _ => "mp4".to_string(),
+    }
+}
+
+/// `output_format` values that drop the video stream.
+fn is_audio_format(format: &str) -> bool {
+    matches!(format, "mp3" | "wav" | "m4a")
+}
+
+/// Rejects `output_format` values and codec/container pairs ffmpeg can't write.
+/// Problems `normalize_settings` can't settle by falling back to a default.
+/// Exports refuse to start while any remain, and CSV previews list them.
+fn settings_errors(settings: &ExportSettings) -> Vec<String> {
+    let mut errors = Vec::new();
+    if settings.exact_frames && settings.fps.is_none() {
+        errors.push("exact_frames requires an FPS so frame numbers can be computed".to_string());
+    }
+    if settings.time_precision > MAX_TIME_PRECISION {
+        errors.push(format!(
+            "time_precision must be between 0 and {MAX_TIME_PRECISION} decimal places"
+        ));
+    }
+    if !(settings.end_hold_sec.is_finite() && settings.end_hold_sec >= 0.0) {
+        errors.push("end_hold_sec must be zero or a positive number of seconds".to_string());
+    }
+    let fades = [settings.fade_in_sec, settings.fade_out_sec];
+    if !fades.iter().all(|sec| sec.is_finite() && *sec >= 0.0) {
+        errors.push(
+            "fade_in_sec/fade_out_sec must be zero or a positive number of seconds".to_string(),
+        );
+    }
+    let copy_streams = settings.processing_mode == "copy_fast"
+        && !settings
+            .output_format
+            .as_deref()
+            .is_some_and(is_audio_format);
+    if copy_streams && fades.iter().any(|sec| *sec > 0.0) {
+        errors.push(
+            "fade_in_sec/fade_out_sec need a re-encode mode; Copy Streams can't fade copied streams"
+                .to_string(),
+        );
+    }
+    if settings.processing_mode == "copy_fast" && has_settings_crop(settings) {
+        errors.push(
+            "crop_w/crop_h/crop_x/crop_y need a re-encode mode; Copy Streams can't filter copied streams"
+                .to_string(),
+        );
+    }
+    if let Err(err) = check_output_format(settings) {
+        errors.push(err);
+    }
+    if let Some(format) = settings.muxer_format.as_deref() {
+        if muxer_name(format).is_none() {
+            errors.push(format!(
+                "Unsupported muxer_format '{format}'; use ts, mp4, mkv, mov or webm"
+            ));
+        }
+    }
+    if settings.normalize_audio {
+        if matches!(settings.audio_codec.as_str(), "copy" | "none") {
+            errors.push(format!(
+                "normalize_audio re-encodes the audio, so audio_codec can't be '{}'; use aac",
+                settings.audio_codec
+            ));
+        } else if copy_streams {
+            errors.push(
+                "normalize_audio needs a re-encode mode; Copy Streams copies the audio untouched"
+                    .to_string(),
+            );
+        }
+    }
+    if settings.burn_label && settings.processing_mode == "copy_fast" {
+        errors.push(
+            "burn_label needs a re-encode mode; Copy Streams can't draw on copied streams"
+                .to_string(),
+        );
+    }
+    let files = [
+        (&settings.label_font_file, "Label font"),
+        (&settings.watermark_image, "Watermark image"),
+    ];
+    for (path, what) in files {
+        let Some(path) = path.as_deref() else {
+            continue;
+        };
+        if let Err(err) = check_filter_text(path, &format!("{what} path")) {
+            errors.push(err);
+        } else if !Path::new(path).is_file() {
+            errors.push(format!("{what} not found: {path}"));
+        }
+    }
+    errors
+}
+
+fn check_output_format(settings: &ExportSettings) -> Result<(), String> {
+    let Some(format) = settings.output_format.as_deref() else {
+        return Ok(());
+    };
+    if !matches!(format, "mp4" | "mov" | "mkv" | "webm" | "source") && !is_audio_format(format) {
+        return Err(format!(
+            "Unsupported output_format '{format}'; use mp4, mov, mkv, webm, source, mp3, wav or m4a"
+        ));
+    }
+    if is_audio_format(format) {
+        if settings.processing_mode == "webp" {
+            return Err(format!("{format} output can't be made from WebP previews"));
+        }
+        return match settings.audio_codec.as_str() {
+            "none" => Err(format!(
+                "{format} output is audio-only; choose an audio codec other than none"
+            )),
+            "copy" if format != "m4a" => Err(format!(
+                "{format} output re-encodes the audio; choose AAC instead of copy"
+            )),
+            _ => Ok(()),
+        };
+    }
+    if format != "webm" || settings.processing_mode == "webp" {
+        return Ok(());
+    }
+    if settings.processing_mode == "copy_fast" {
+        return Err(
+            "webm output needs a re-encode: Copy Streams can't put H.264/AAC streams in WebM"
+                .to_string(),
+        );
+    }
+    if settings.encoder != "libx264" {
+        return Err(format!(
+            "webm output is encoded as VP9; {} writes {} and can't be used with it",
+            settings.encoder,
+            if settings.encoder.starts_with("hevc") {
+                "HEVC"
+            } else {
+                "H.264"
+            }
+        ));
+    }
+    if settings.audio_codec == "copy" {
+        return Err(
+            "webm output can't copy the source audio; choose AAC (written as Opus) or none"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// ffmpeg muxer (`-f`) for a `muxer_format` setting.
+fn muxer_name(format: &str) -> Option<&'static str> {
+    match format {
+        "ts" => Some("mpegts"),
+        "mp4" => Some("mp4"),
+        "mkv" => Some("matroska"),
+        "mov" => Some("mov"),
+        "webm" => Some("webm"),
+        _ => None,
+    }
+}
+
+/// `-c:v` plus quality flags for `encoder`. The hardware encoders don't take
+/// `-crf`, so `crf` maps to their nearest constant-quality control and the x264
+/// preset to the closest speed level.
+fn video_encoder_args(encoder: &str, preset: &str, crf: u8) -> Vec<String> {
+    let mut args = vec!["-c:v".to_string(), encoder.to_string()];
+    match encoder {
+        "h264_nvenc" | "hevc_nvenc" => {
+            let level = match preset {
+                "ultrafast" => "p1",
+                "superfast" => "p2",
+                "veryfast" => "p3",
+                "faster" => "p4",
+                "fast" => "p5",
+                _ => "p6",
+            };
+            args.extend(
+                [
+                    "-preset",
+                    level,
+                    "-rc",
+                    "vbr",
+                    "-cq",
+                    &crf.to_string(),
+                    "-b:v",
+                    "0",
+                ]
+                .map(str::to_string),
+            );
+        }
+        "h264_videotoolbox" => {
+            // -q:v runs 1-100 with higher meaning better, the reverse of CRF.
+            let quality = 100u8.saturating_sub(crf.saturating_mul(2)).max(1);
+            args.extend(["-q:v".to_string(), quality.to_string()]);
+        }
+        "libvpx-vp9" => {
+            // Constant quality needs -b:v 0; -cpu-used trades quality for speed
+            // the way the x264 presets do.
+            let speed = match preset {
+                "ultrafast" | "superfast" => "8",
+                "veryfast" => "6",
+                "faster" => "5",
+                "fast" => "4",
+                _ => "2",
+            };
+            args.extend(
+                [
+                    "-crf",
+                    &crf.to_string(),
+                    "-b:v",
+                    "0",
+                    "-row-mt",
+                    "1",
+                    "-cpu-used",
+                    speed,
+                ]
+                .map(str::to_string),
+            );
+        }
+        "h264_qsv" => {
+            let level = match preset {
+                "ultrafast" | "superfast" => "veryfast",
+                other => other,
+            };
+            args.extend(
+                ["-preset", level, "-global_quality", &crf.to_string()].map(str::to_string),
+            );
+        }
+        _ => {
+            args.extend(["-preset", preset, "-crf", &crf.to_string()].map(str::to_string));
+        }
+    }
+    args
+}
+
+fn movflags(output_ext: &str, fragmented: bool) -> Option<&'static str> {
+    match (output_ext, fragmented) {
+        ("mp4" | "m4v" | "mov", true) => Some("+frag_keyframe+empty_moov+default_base_moof"),
+        ("mp4" | "m4v" | "m4a" | "mov", false) => Some("+faststart"),
+        _ => None,
+    }
+}
+
+fn frame_range(start_sec: f64, end_sec: f64, fps: f64) -> (u64, u64) {
+    let first = (start_sec * fps).round() as u64;
+    let end = (end_sec * fps).round() as u64;
+    (first, end.max(first + 1) - 1)
+}
+
+fn exact_frame_filter(first_frame: u64, last_frame: u64) -> String {
+    format!("select='between(n,{first_frame},{last_frame})',setpts=N/FRAME_RATE/TB")
+}
+
+// UTF-16 files are decoded before parsing, so their BOM shows up here as the
+// same U+FEFF character a UTF-8 BOM decodes to.
+fn normalize_header(input: &str) -> String {
+    input
+        .trim_start_matches('\u{feff}')
+        .trim()
+        .to_lowercase()
+        .replace(['_', '-'], " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parses SS, MM:SS, HH:MM:SS or HH:MM:SS:FF, dividing frames by `fps`.
+fn convert_to_seconds(ts: &str, fps: f64) -> Option<f64> {
+    let ts = ts.trim();
+    if ts.is_empty() {
+        return None;
+    }
+    if ts.starts_with(['P', 'p']) {
+        return iso8601_duration_seconds(ts);
+    }
+
+    let parts = ts.split(':').collect::<Vec<_>>();
+
+    let result = match parts.len() {
+        4 => {
+            let h = parts[0].parse::<f64>().ok()?;
+            let m = parts[1].parse::<f64>().ok()?;
+            let s = parts[2].parse::<f64>().ok()?;
+            let f = parts[3].parse::<f64>().ok()?;
+            // Frame numbers run 0..fps; 29.97 fps still allows frame 29.
+            if !(fps.is_finite() && fps > 0.0) || f < 0.0 || f >= fps.ceil() {
+                return None;
+            }
+            (h * 3600.0) + (m * 60.0) + s + (f / fps)
+        }
+        3 => {
+            let h = parts[0].parse::<f64>().ok()?;
+            let m = parts[1].parse::<f64>().ok()?;
+            let s = parts[2].parse::<f64>().ok()?;
+            (h * 3600.0) + (m * 60.0) + s
+        }
+        2 => {
+            let m = parts[0].parse::<f64>().ok()?;
+            let s = parts[1].parse::<f64>().ok()?;
+            (m * 60.0) + s
+        }
+        1 => parts[0].parse::<f64>().ok()?,
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+/// ISO 8601 time durations such as `PT1M30S` or `PT2H`: hours, minutes and
+/// seconds in that order, each at most once, at least one present.
+fn iso8601_duration_seconds(ts: &str) -> Option<f64> {
+    let rest = ts.to_ascii_uppercase();
+    let mut rest = rest.strip_prefix("PT")?;
+    if rest.is_empty() {
+        return None;
+    }
+    let mut total = 0.0;
+    for (unit, scale) in [('H', 3600.0), ('M', 60.0), ('S', 1.0)] {
+        if let Some((value, tail)) = rest.split_once(unit) {
+            // A later unit inside `value` means the units are out of order.
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+                return None;
+            }
+            total += value.parse::<f64>().ok()? * scale;
+            rest = tail;
+        }
+    }
+    rest.is_empty().then_some(total)
+}
+
+/// Extra context for a time that didn't parse.
+fn invalid_time_hint(ts: &str) -> &'static str {
+    if ts.trim().starts_with(['P', 'p']) {
+        " (expected an ISO 8601 duration like PT1M30S)"
+    } else {
+        ""
+    }
+}
+
+/// Whether `ts` parses and is written the usual way: zero-padded hours, two-digit
+/// minutes/seconds/frames under their limits, and bare seconds under a minute.
+fn is_canonical_timecode(ts: &str, fps: f64) -> bool {
+    if convert_to_seconds(ts, fps).is_none() {
+        return false;
+    }
+    let two_digits = |part: &str, limit: f64| {
+        let whole = part.split('.').next().unwrap_or_default();
+        whole.len() == 2 && whole.parse::<f64>().is_ok_and(|v| v < limit)
+    };
+    match ts.split(':').collect::<Vec<_>>().as_slice() {
+        [seconds] => seconds.parse::<f64>().is_ok_and(|s| s < 60.0),
+        [_, seconds] => two_digits(seconds, 60.0),
+        [hours, minutes, seconds] => {
+            hours.len() >= 2 && two_digits(minutes, 60.0) && two_digits(seconds, 60.0)
+        }
+        [hours, minutes, seconds, frames] => {
+            hours.len() >= 2
+                && two_digits(minutes, 60.0)
+                && two_digits(seconds, 60.0)
+                && !frames.contains('.')
+                && two_digits(frames, fps)
+        }
+        _ => false,
+    }
+}
+
+/// Reads unit-suffixed times such as `1h2m3s` or `90s`.
+fn unit_seconds(ts: &str) -> Option<f64> {
+    let mut total = 0.0;
+    let mut number = String::new();
+    let mut found_unit = false;
+    for c in ts.chars() {
+        match c.to_ascii_lowercase() {
+            '0'..='9' | '.' => number.push(c),
+            unit @ ('h' | 'm' | 's') => {
+                let value = number.parse::<f64>().ok()?;
+                total += value
+                    * match unit {
+                        'h' => 3600.0,
+                        'm' => 60.0,
+                        _ => 1.0,
+                    };
+                number.clear();
+                found_unit = true;
+            }
+            ' ' => {}
+            _ => return None,
+        }
+    }
+    (found_unit && number.is_empty()).then_some(total)
+}
+
+/// Suggests a corrected `HH:MM:SS[.mmm]` (or `HH:MM:SS:FF`) value for a malformed
+/// timestamp, or `None` when it is already fine or can't be salvaged.
+fn suggest_timecode(ts: &str, fps: f64) -> Option<String> {
+    let ts = ts.trim();
+    if ts.is_empty() || is_canonical_timecode(ts, fps) {
+        return None;
+    }
+    if let Some(seconds) = unit_seconds(ts) {
+        return Some(format_precise_timestamp(seconds));
+    }
+
+    // Keep digits and separators: `;` (drop-frame) becomes `:`, a decimal comma
+    // becomes `.`, and stray letters, spaces and quotes are dropped.
+    let cleaned = ts
+        .chars()
+        .filter_map(|c| match c {
+            '0'..='9' | '.' | ':' => Some(c),
+            ';' => Some(':'),
+            ',' => Some('.'),
+            _ => None,
+        })
+        .collect::<String>();
+    let parts = cleaned
+        .split(':')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>();
+    let cleaned = parts.join(":");
+
+    // Frames past the frame rate would fail to parse, so carry them into seconds
+    // before anything checks the value.
+    let suggested = if let [hours, minutes, secs, frames] = parts.as_slice() {
+        let nominal = fps.round().max(1.0) as u64;
+        let frames = frames.parse::<u64>().ok()?;
+        let whole = hours.parse::<u64>().ok()? * 3600
+            + minutes.parse::<u64>().ok()? * 60
+            + secs.parse::<u64>().ok()?
+            + frames / nominal;
+        format!("{}:{:02}", format_timestamp(whole as f64), frames % nominal)
+    } else {
+        let seconds = convert_to_seconds(&cleaned, fps).filter(|s| *s >= 0.0)?;
+        format_precise_timestamp(seconds)
+    };
+    Some(suggested).filter(|suggested| suggested != ts)
+}
+
+fn timeline_seconds(ts: &str, offset: f64, fps: f64) -> Option<f64> {
+    let seconds = convert_to_seconds(ts, fps)? - offset;
+    if seconds < 0.0 {
+        None
+    } else {
+        Some(seconds)
+    }
+}
+
+/// `end`/`eof` and negative times like `-00:00:10` (ten seconds before the end)
+/// count back from the end of the video, so they need its duration.
+fn counts_from_end(ts: &str) -> bool {
+    let ts = ts.trim();
+    ts.eq_ignore_ascii_case("end") || ts.eq_ignore_ascii_case("eof") || ts.starts_with('-')
+}
+
+/// How far before the end of the video an end-relative time lands; `None` for
+/// other times and malformed ones.
+fn seconds_before_end(ts: &str, fps: f64) -> Option<f64> {
+    let ts = ts.trim();
+    match ts.strip_prefix('-') {
+        Some(rest) => convert_to_seconds(rest, fps),
+        None => counts_from_end(ts).then_some(0.0),
+    }
+}
+
+/// Source seconds for a CSV time: `timeline_seconds`, plus `start` for the
+/// beginning and the end-relative forms of `counts_from_end`, resolved against
+/// the probed `duration`. Errors read after "start time"/"end time".
+fn clip_time_seconds(
+    ts: &str,
+    offset: f64,
+    fps: f64,
+    duration: Option<f64>,
+) -> Result<f64, String> {
+    let ts = ts.trim();
+    let invalid = || format!("'{ts}' is not a valid time{}", invalid_time_hint(ts));
+    if ts.eq_ignore_ascii_case("start") {
+        return Ok(0.0);
+    }
+    if !counts_from_end(ts) {
+        return timeline_seconds(ts, offset, fps).ok_or_else(invalid);
+    }
+    let before_end = seconds_before_end(ts, fps).ok_or_else(invalid)?;
+    let Some(duration) = duration else {
+        return Err(format!(
+            "'{ts}' counts from the end of the video, but its duration couldn't be read"
+        ));
+    };
+    if before_end > duration {
+        return Err(format!(
+            "'{ts}' is before the start of the video ({} long)",
+            format_timestamp(duration)
+        ));
+    }
+    Ok(duration - before_end)
+}
+
+fn parse_octal_mode(input: &str) -> Option<u32> {
+    let digits = input.trim().trim_start_matches("0o");
+    if digits.is_empty() || digits.len() > 4 {
+        return None;
+    }
+    u32::from_str_radix(digits, 8).ok()
+}
+
+#[cfg(unix)]
+fn apply_output_mode(path: &Path, mode: u32) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("Failed to set permissions on {}: {e}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn apply_output_mode(_path: &Path, _mode: u32) -> Result<(), String> {
+    Err("output_mode is only supported on Unix".to_string())
+}
+
+fn copy_mtime(source: &Path, destination: &Path) -> Result<(), String> {
+    let modified = std::fs::metadata(source)
+        .and_then(|m| m.modified())
+        .map_err(|e| format!("Could not read the source modified time: {e}"))?;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(destination)
+        .and_then(|file| file.set_modified(modified))
+        .map_err(|e| {
+            format!(
+                "Failed to set the modified time of {}: {e}",
+                destination.display()
+            )
+        })
+}
+
+fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        (total % 3600) / 60,
+        total % 60
+    )
+}
+
+/// Like `format_timestamp`, but keeps milliseconds when there are any.
+fn format_precise_timestamp(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    let base = format_timestamp((millis / 1000) as f64);
+    match millis % 1000 {
+        0 => base,
+        ms => format!("{base}.{}", format!("{ms:03}").trim_end_matches('0')),
+    }
+}
+
+/// Values for the `filename_template` tokens of one clip.
+struct FilenameTokens<'a> {
+    index: usize,
+    name: &'a str,
+    start: &'a str,
+    end: &'a str,
+    duration_sec: f64,
+    ext: &'a str,
+}
+
+/// Expands `{index}`, `{index:0N}`, `{name}`, `{start}`, `{end}`, `{duration}`
+/// and `{ext}`; anything else in braces is kept as written.
+fn render_filename_template(template: &str, tokens: &FilenameTokens) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('}').map(|close| open + close) else {
+            rest = &rest[open..];
+            break;
+        };
+        let token = &rest[open + 1..close];
+        let value = match token {
+            "index" => Some(tokens.index.to_string()),
+            "name" => Some(tokens.name.to_string()),
+            "start" => Some(tokens.start.to_string()),
+            "end" => Some(tokens.end.to_string()),
+            "duration" => Some(
+                format!("{:.3}", tokens.duration_sec)
+                    .trim_end_matches('0')
+                    .trim_end_matches('.')
+                    .to_string(),
+            ),
+            "ext" => Some(tokens.ext.to_string()),
+            _ => token
+                .strip_prefix("index:0")
+                .and_then(|width| width.parse::<usize>().ok())
+                .filter(|width| (1..=9).contains(width))
+                .map(|width| format!("{:0width$}", tokens.index)),
+        };
+        match value {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[open..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Output file name from `filename_template`. `.{ext}` is added when the
+/// template leaves it out, and any `unique_suffix` hash goes before it.
+fn templated_file_name(template: &str, tokens: &FilenameTokens, hash_suffix: &str) -> String {
+    let template = template
+        .strip_suffix(".{ext}")
+        .unwrap_or(template)
+        .to_string();
+    let template = if template.contains("{ext}") {
+        template
+    } else {
+        format!("{template}{hash_suffix}.{{ext}}")
+    };
+    render_filename_template(&template, tokens)
+}
+
+/// Rejects templates that would write outside the output folder or leave
+/// nothing but the extension.
+fn filename_template_is_valid(template: &str) -> bool {
+    let sample = FilenameTokens {
+        index: 1,
+        name: "clip",
+        start: "000000",
+        end: "000010",
+        duration_sec: 10.0,
+        ext: "mp4",
+    };
+    let stem = render_filename_template(
+        template.strip_suffix(".{ext}").unwrap_or(template),
+        &FilenameTokens { ext: "", ..sample },
+    );
+    !stem.contains(['/', '\\']) && !stem.trim_matches(['.', ' ']).is_empty()
+}
+
+fn renumbered_name(name: &str, index: usize) -> String {
+    let rest = match name.split_once('-') {
+        Some((prefix, rest))
+            if !prefix.is_empty() && prefix.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            rest
+        }
+        _ => name,
+    };
+    format!("{:03}-{}", index + 1, rest)
+}
+
+/// Short, stable hex digest of a clip's source and cut points (FNV-1a), so the
+/// same inputs always produce the same file name across runs.
+fn clip_hash(source: &Path, start_sec: f64, end_sec: f64, len: usize) -> String {
+    let source = source_key(source);
+    let key = format!(
+        "{}|{:.3}|{:.3}",
+        source.to_string_lossy(),
+        start_sec,
+        end_sec
+    );
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{hash:016x}")[..len.min(16)].to_string()
+}
+
+fn unique_destination(destination: &Path, suffix_template: &str) -> PathBuf {
+    if !destination.exists() {
+        return destination.to_path_buf();
+    }
+
+    let stem = destination
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = destination
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (2..)
+        .map(|n| {
+            let suffix = suffix_template.replace("{n}", &n.to_string());
+            destination.with_file_name(format!("{stem}{suffix}{ext}"))
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| destination.to_path_buf())
+}
+
+/// Name for a row whose clip name has nothing usable in a file name.
+fn fallback_clip_name(
+    naming: &str,
+    index: usize,
+    source: &Path,
+    start_sec: f64,
+    end_sec: f64,
+) -> String {
+    match naming {
+        "index" => format!("clip-{index:03}"),
+        "source_stem" => format!(
+            "{}-{index:03}",
+            source
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default()
+        ),
+        "timecode" => format!(
+            "{}_to_{}",
+            format_timestamp(start_sec).replace(':', "-"),
+            format_timestamp(end_sec).replace(':', "-")
+        ),
+        _ => "clip".to_string(),
+    }
+}
+
+fn sanitize_filename(name: &str) -> String {
+    let compact = clean_filename(name);
+    if compact.is_empty() {
+        "clip".to_string()
+    } else {
+        compact
+    }
+}
+
+fn clean_filename(name: &str) -> String {
+    let cleaned = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            ' ' => '-',
+            _ => '-',
+        })
+        .collect::<String>();
+
+    cleaned
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn main() {
+    tauri::Builder::default()
+        .manage(ProcessState::default())
+        .invoke_handler(tauri::generate_handler![
+            pick_csv_file,
+            pick_video_file,
+            pick_output_dir,
+            check_dependencies,
+            default_output_dir,
+            total_duration,
+            suggest_timecode_fixes,
+            detect_encoders,
+            segment_export,
+            cleanup_orphans,
+            list_subtitle_tracks,
+            preview_csv,
+            preview_csv_batch,
+            preview_copy_starts,
+            generate_qc_report,
+            renumber_outputs,
+            probe_sources,
+            export_fcpxml,
+            export_proxy_package,
+            clip_strip,
+            detect_black,
+            start_export,
+            stop_export
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PATH_TOOLS: Tools = Tools {
+        ffmpeg: None,
+        ffprobe: None,
+    };
+
+    fn args_of(cmd: &Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    fn clip_spec<'a>(settings: &'a ExportSettings, source: &'a Path) -> ClipSpec<'a> {
+        ClipSpec {
+            settings,
+            tools: &PATH_TOOLS,
+            mode: &settings.processing_mode,
+            source,
+            clip_name: "goal",
+            start_sec: 10.0,
+            end_sec: 25.5,
+            exact_fps: None,
+            exact_seek: None,
+            fixed_duration: None,
+            end_hold: None,
+            crop: None,
+            audio_offset: None,
+            source_info: None,
+            container: "mp4",
+            row_ext: "mp4",
+            muxer: None,
+            encoder: &settings.encoder,
+            audio_encoder: "aac",
+            loudness_target: None,
+            loudnorm_measured: None,
+            replaygain: None,
+        }
+    }
+
+    #[test]
+    fn copy_clip_command_seeks_before_the_input() {
+        let settings = ExportSettings::default();
+        let clip = clip_command(&clip_spec(&settings, Path::new("match.mp4")));
+        assert_eq!(
+            args_of(&clip.cmd),
+            [
+                "-y",
+                "-loglevel",
+                "error",
+                "-nostats",
+                "-ss",
+                "10.000",
+                "-i",
+                "match.mp4",
+                "-t",
+                "15.500",
+                "-c",
+                "copy",
+                "-avoid_negative_ts",
+                "make_zero",
+                "-movflags",
+                "+faststart",
+            ]
+        );
+        assert!(!clip.reports_progress);
+        assert!(clip.fallbacks.container.is_none() && clip.fallbacks.reencode.is_none());
+    }
+
+    #[test]
+    fn precise_clip_command_seeks_after_the_input() {
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            crf: 23,
+            ..ExportSettings::default()
+        };
+        let clip = clip_command(&clip_spec(&settings, Path::new("match.mp4")));
+        let args = args_of(&clip.cmd);
+        let input = args.iter().position(|a| a == "-i").unwrap();
+        let seek = args.iter().position(|a| a == "-ss").unwrap();
+        assert!(seek > input);
+        assert_eq!(args[seek + 1..seek + 4], ["10.000", "-to", "25.500"]);
+        assert!(args.windows(2).any(|w| w == ["-crf", "23"]));
+        assert!(args.windows(2).any(|w| w == ["-c:a", "aac"]));
+        assert!(clip.reports_progress);
+        assert_eq!(clip.output_duration, 15.5);
+    }
+
+    #[test]
+    fn planned_command_writes_the_partial_file_and_lists_fallbacks() {
+        let settings = ExportSettings {
+            copy_container_fallback: true,
+            fallback_reencode: true,
+            ..ExportSettings::default()
+        };
+        let spec = clip_spec(&settings, Path::new("match.mp4"));
+        let clip = clip_command(&spec);
+        let destination = Path::new("out").join("001-goal-0010.mp4");
+        let planned = planned_command(0, &destination, &clip, None, &spec);
+
+        let partial = Path::new("out").join("001-goal-0010.partial.mp4");
+        let mkv = Path::new("out").join("001-goal-0010.partial.mkv");
+        assert_eq!(planned.output_path, destination.to_string_lossy());
+        assert_eq!(
+            planned.args[1..],
+            [
+                args_of(&clip.cmd),
+                vec![partial.to_string_lossy().to_string()]
+            ]
+            .concat()
+        );
+        assert_eq!(planned.fallback_args.len(), 2);
+        assert_eq!(
+            planned.fallback_args[0].last().unwrap(),
+            &mkv.to_string_lossy()
+        );
+        assert!(planned.fallback_args[0].contains(&"copy".to_string()));
+        assert_eq!(
+            planned.fallback_args[1].last().unwrap(),
+            &partial.to_string_lossy()
+        );
+        assert!(planned.fallback_args[1].contains(&"libx264".to_string()));
+        assert!(planned.bumper_args.is_none());
+    }
+
+    #[test]
+    fn planned_command_joins_bumpers_around_the_clip() {
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            resolution: "720p".to_string(),
+            fps: Some(25.0),
+            ..ExportSettings::default()
+        };
+        let spec = clip_spec(&settings, Path::new("match.mp4"));
+        let clip = clip_command(&spec);
+        let bumper = |path: &str| Bumper {
+            path: PathBuf::from(path),
+            duration: 2.0,
+            has_audio: false,
+        };
+        let bumpers = Bumpers {
+            intro: Some(bumper("intro.mp4")),
+            outro: Some(bumper("outro.mp4")),
+            encoder: "libx264".to_string(),
+            audio_encoder: "aac".to_string(),
+            preset: "fast".to_string(),
+            crf: 20,
+            audio_bitrate_kbps: 128,
+            movflags: None,
+        };
+        let destination = Path::new("out").join("001-goal-0010.mp4");
+        let planned = planned_command(0, &destination, &clip, Some(&bumpers), &spec);
+
+        let args = planned.bumper_args.expect("bumper pass planned");
+        let inputs = args
+            .windows(2)
+            .filter(|w| w[0] == "-i")
+            .map(|w| w[1].clone())
+            .collect::<Vec<_>>();
+        let body = Path::new("out").join("001-goal-0010.partial-body.mp4");
+        assert_eq!(
+            inputs,
+            [
+                "intro.mp4".to_string(),
+                body.to_string_lossy().to_string(),
+                "outro.mp4".to_string()
+            ]
+        );
+        let graph = &args[args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+        assert!(graph.contains("scale=1280:720") && graph.contains("fps=25"));
+        assert!(graph.contains("concat=n=3:v=1:a=1"));
+        assert!(graph.contains("anullsrc"));
+    }
+
+    /// A fresh, empty scratch folder under the system temp dir.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("clipchop-test-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes stand-in ffmpeg/ffprobe scripts into `dir` and returns settings
+    /// that use them. Every ffmpeg call is appended to `dir/ffmpeg.log`.
+    #[cfg(unix)]
+    fn fake_tools(dir: &Path, ffmpeg_body: &str) -> ExportSettings {
+        use std::os::unix::fs::PermissionsExt;
+        let write = |name: &str, body: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let log = dir.join("ffmpeg.log");
+        let ffmpeg = write(
+            "ffmpeg",
+            &format!("echo \"$@\" >> '{}'\n{ffmpeg_body}", log.display()),
+        );
+        let ffprobe = write("ffprobe", "exit 1");
+        ExportSettings {
+            ffmpeg_path: Some(ffmpeg),
+            ffprobe_path: Some(ffprobe),
+            ..ExportSettings::default()
+        }
+    }
+
+    /// A fake ffmpeg body that succeeds and writes a byte to its output file.
+    #[cfg(unix)]
+    const WRITES_OUTPUT: &str = "for out; do :; done\nprintf x > \"$out\"";
+
+    fn row(clip_name: &str, start_time: &str, end_time: &str) -> ClipRowInput {
+        ClipRowInput {
+            clip_name: clip_name.to_string(),
+            start_time: start_time.to_string(),
+            end_time: end_time.to_string(),
+            overrides: RowOverrides::default(),
+        }
+    }
+
+    /// Runs an export of `rows` from an empty `dir/match.mp4` into `dir/out`,
+    /// returning the summary and every progress line it sent.
+    fn export_rows(
+        dir: &Path,
+        settings: ExportSettings,
+        rows: Vec<ClipRowInput>,
+        dry_run: bool,
+    ) -> (Result<RunSummary, String>, Vec<ProgressLogLine>) {
+        let video = dir.join("match.mp4");
+        std::fs::write(&video, b"").unwrap();
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = lines.clone();
+        let result = run_export(
+            Box::new(move |line| sink.lock().unwrap().push(line.clone())),
+            Arc::new(Mutex::new(Vec::new())),
+            Arc::new(AtomicBool::new(false)),
+            None,
+            video.to_string_lossy().to_string(),
+            dir.join("out").to_string_lossy().to_string(),
+            settings,
+            Some(rows),
+            None,
+            false,
+            dry_run,
+        );
+        let lines = std::mem::take(&mut *lines.lock().unwrap());
+        (result, lines)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dry_run_plans_without_running_analysis_passes() {
+        let dir = scratch_dir("dry-run-analysis");
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            trim_black: true,
+            loudnorm_two_pass: true,
+            ..fake_tools(&dir, "exit 1")
+        };
+        let (summary, _) = export_rows(
+            &dir,
+            settings,
+            vec![row("goal", "00:00:10", "00:00:20")],
+            true,
+        );
+        let summary = summary.unwrap();
+
+        assert!(!dir.join("ffmpeg.log").exists(), "dry run started ffmpeg");
+        assert_eq!(summary.planned_commands.len(), 1);
+        assert!(summary.planned_commands[0]
+            .args
+            .iter()
+            .any(|arg| arg == "-i"));
+        assert!(summary.warnings.iter().any(|w| w.contains("trim_black")));
+        assert!(!dir.join("out").join("001-goal-0010.mp4").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cleanup_stops_a_leftover_ffmpeg_from_the_pidfile() {
+        let dir = scratch_dir("pidfile");
+        // A copy of `sleep` named ffmpeg passes the process-name check.
+        let fake = dir.join("ffmpeg");
+        std::fs::copy("/bin/sleep", &fake).unwrap();
+        let mut orphan = Command::new(&fake).arg("30").spawn().unwrap();
+        let mut live = Command::new(&fake).arg("30").spawn().unwrap();
+        let pidfile = dir.join(PID_FILE);
+        write_pids(&pidfile, &[orphan.id(), live.id()]).unwrap();
+
+        let listed = cleanup_pidfile(&pidfile, &HashSet::from([live.id()]), true).unwrap();
+        assert_eq!(listed.found, [orphan.id()]);
+        assert!(listed.killed.is_empty());
+        assert_eq!(read_pids(&pidfile).len(), 2);
+
+        let cleaned = cleanup_pidfile(&pidfile, &HashSet::from([live.id()]), false).unwrap();
+        assert_eq!(cleaned.killed, [orphan.id()]);
+        assert!(!orphan.wait().unwrap().success());
+        assert_eq!(read_pids(&pidfile), [live.id()]);
+        let _ = live.kill();
+        let _ = live.wait();
+    }
+
+    #[test]
+    fn remove_partials_deletes_both_partials_and_reports_the_first() {
+        let dir = scratch_dir("partials");
+        let partial = partial_path(&dir.join("goal.mp4"));
+        std::fs::write(&partial, b"x").unwrap();
+        std::fs::write(partial.with_extension("mkv"), b"x").unwrap();
+
+        assert_eq!(remove_partials(&partial), Some(partial.clone()));
+        assert!(!partial.exists() && !partial.with_extension("mkv").exists());
+        assert_eq!(remove_partials(&partial), None);
+    }
+
+    #[test]
+    fn timecode_frames_count_at_24_and_25_fps() {
+        assert_eq!(convert_to_seconds("00:00:01:12", 24.0), Some(1.5));
+        assert_eq!(convert_to_seconds("00:00:01:20", 25.0), Some(1.8));
+        assert_eq!(convert_to_seconds("00:00:01:24", 25.0), Some(1.96));
+        assert_eq!(convert_to_seconds("00:00:01:24", 24.0), None);
+        assert_eq!(convert_to_seconds("00:00:01:25", 25.0), None);
+    }
+
+    #[test]
+    fn output_fps_wins_over_probed_and_assumed_rates() {
+        let settings = ExportSettings {
+            fps: Some(25.0),
+            assumed_fps: Some(24.0),
+            ..ExportSettings::default()
+        };
+        assert_eq!(settings.timecode_fps(Some(30.0)), 25.0);
+        let settings = ExportSettings {
+            fps: None,
+            ..settings
+        };
+        assert_eq!(settings.timecode_fps(Some(30.0)), 30.0);
+        assert_eq!(settings.timecode_fps(None), 24.0);
+        assert_eq!(
+            ExportSettings::default().timecode_fps(None),
+            DEFAULT_TIMECODE_FPS
+        );
+    }
+
+    #[test]
+    fn preview_reads_frames_at_the_output_fps() {
+        let dir = scratch_dir("preview-fps");
+        let csv = dir.join("clips.csv");
+        std::fs::write(&csv, "name,start,end\ngoal,00:00:01:24,00:00:02:00\n").unwrap();
+        let settings = ExportSettings {
+            fps: Some(24.0),
+            ..ExportSettings::default()
+        };
+        let csv = csv.to_string_lossy();
+        let preview = build_csv_preview(&csv, &settings, 0.0, 24.0, None).unwrap();
+        assert_eq!(preview.validation_errors.len(), 1);
+        let preview = build_csv_preview(&csv, &settings, 0.0, 25.0, None).unwrap();
+        assert!(preview.validation_errors.is_empty());
+    }
+
+    #[test]
+    fn run_ids_are_distinct_v4_uuids() {
+        let id = new_run_id();
+        let groups = id.split('-').map(str::len).collect::<Vec<_>>();
+        assert_eq!(groups, [8, 4, 4, 4, 12]);
+        assert!(id.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+        assert_eq!(&id[14..15], "4");
+        assert!(matches!(&id[19..20], "8" | "9" | "a" | "b"));
+        assert_ne!(id, new_run_id());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn progress_events_and_summary_share_the_run_id() {
+        let dir = scratch_dir("run-id");
+        let (summary, lines) = export_rows(
+            &dir,
+            fake_tools(&dir, "exit 1"),
+            vec![
+                row("goal", "00:00:10", "00:00:20"),
+                row("save", "00:01:00", "00:01:05"),
+            ],
+            true,
+        );
+        let summary = summary.unwrap();
+        assert!(!lines.is_empty());
+        assert!(lines.iter().all(|line| line.run_id == summary.run_id));
+    }
+
+    #[test]
+    fn segment_split_is_checked_against_the_plan() {
+        let boundaries = [10.0, 25.5, 40.0];
+        let listing = "00000.mp4,0.000000,10.000000\n00001.mp4,10.000000,25.520000\n\
+                       00002.mp4,25.520000,40.000000\n00003.mp4,40.000000,60.000000\n";
+        let starts = segment_list_starts(listing);
+        assert_eq!(starts, [0.0, 10.0, 25.52, 40.0]);
+        assert!(check_segments(&starts, &boundaries, 25.0).is_ok());
+
+        // A keyframe two seconds late moved the second piece.
+        let late = [0.0, 10.0, 27.5, 40.0];
+        assert!(check_segments(&late, &boundaries, 25.0).is_err());
+        // No keyframe between two boundaries merged two pieces.
+        let merged = [0.0, 10.0, 40.0];
+        let err = check_segments(&merged, &boundaries, 25.0).unwrap_err();
+        assert!(err.contains("3 pieces instead of 4"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn budget_includes_the_first_two_of_three_clips() {
+        let rows = || {
+            vec![
+                row("one", "00:00:00", "00:00:10"),
+                row("two", "00:01:00", "00:01:10"),
+                row("three", "00:02:00", "00:02:10"),
+            ]
+        };
+        for dry_run in [true, false] {
+            let dir = scratch_dir(&format!("budget-{dry_run}"));
+            // Every encode fails; a failure must not hand its share to a later row.
+            let settings = ExportSettings {
+                max_total_duration: Some(25.0),
+                ..fake_tools(&dir, "exit 1")
+            };
+            let summary = export_rows(&dir, settings, rows(), dry_run).0.unwrap();
+            assert_eq!(summary.budget_included, Some(2));
+            assert_eq!(summary.budget_used_sec, Some(20.0));
+            assert_eq!(summary.rows[2].status, "skipped (budget)");
+        }
+    }
+
+    #[test]
+    fn preview_lists_settings_an_export_would_refuse() {
+        let dir = scratch_dir("settings-errors");
+        let csv = dir.join("clips.csv");
+        std::fs::write(&csv, "name,start,end\ngoal,00:00:10,00:00:20\n").unwrap();
+        let settings = normalize_settings(ExportSettings {
+            fade_in_sec: 1.0,
+            muxer_format: Some("avi".to_string()),
+            ..ExportSettings::default()
+        });
+        let errors = settings_errors(&settings);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("fade_in_sec"));
+        assert!(errors[1].contains("muxer_format 'avi'"));
+
+        let preview =
+            build_csv_preview(&csv.to_string_lossy(), &settings, 0.0, 30.0, None).unwrap();
+        assert_eq!(preview.validation_errors, errors);
+    }
+
+    #[test]
+    fn duration_ends_are_counted_like_their_starts() {
+        assert_eq!(
+            end_from_duration("0:10", "5", 0.0, 30.0).as_deref(),
+            Some("15")
+        );
+        assert_eq!(
+            end_from_duration("start", "0:30", 3600.0, 30.0).as_deref(),
+            Some("3630")
+        );
+        assert_eq!(
+            end_from_duration("-30", "10", 0.0, 30.0).as_deref(),
+            Some("-20")
+        );
+        assert_eq!(
+            end_from_duration("-0:10", "15", 0.0, 30.0).as_deref(),
+            Some("end")
+        );
+        assert_eq!(
+            end_from_duration("end", "5", 0.0, 30.0).as_deref(),
+            Some("end")
+        );
+        assert_eq!(end_from_duration("0:10", "0", 0.0, 30.0), None);
+
+        let dir = scratch_dir("duration-column");
+        let csv = dir.join("clips.csv");
+        std::fs::write(
+            &csv,
+            "name,start,end,duration\nintro,start,,30\noutro,-60,,15\nmid,1:00:10,1:00:20,5\n",
+        )
+        .unwrap();
+        let preview = build_csv_preview(
+            &csv.to_string_lossy(),
+            &ExportSettings::default(),
+            3600.0,
+            30.0,
+            Some(600.0),
+        )
+        .unwrap();
+        assert!(preview.validation_errors.is_empty());
+        assert!(preview
+            .validation_warnings
+            .iter()
+            .any(|w| w.contains("both an end time and a duration column")));
+
+        let rows = read_clip_rows(&csv.to_string_lossy(), "auto", 3600.0, 30.0).unwrap();
+        let time = |ts: &str| clip_time_seconds(ts, 3600.0, 30.0, Some(600.0)).unwrap();
+        assert_eq!(time(&rows[0].end_time), 30.0);
+        assert_eq!(time(&rows[1].end_time), 555.0);
+        assert_eq!(rows[2].end_time, "1:00:20");
+    }
+
+    #[test]
+    fn fallback_names_keep_blank_rows_apart() {
+        let source = Path::new("/videos/match day.mp4");
+        assert_eq!(
+            fallback_clip_name("source_stem", 7, source, 60.0, 90.0),
+            "match day-007"
+        );
+        assert_ne!(
+            fallback_clip_name("source_stem", 1, source, 0.0, 5.0),
+            fallback_clip_name("source_stem", 2, source, 0.0, 5.0)
+        );
+        assert_eq!(
+            fallback_clip_name("timecode", 1, source, 60.0, 3690.0),
+            "00-01-00_to_01-01-30"
+        );
+        assert_eq!(
+            fallback_clip_name("index", 12, source, 0.0, 1.0),
+            "clip-012"
+        );
+        assert_eq!(fallback_clip_name("literal", 3, source, 0.0, 1.0), "clip");
+    }
+
+    #[test]
+    fn override_columns_match_variant_header_spellings() {
+        let headers = StringRecord::from(vec![
+            "\u{feff}Clip_Name",
+            " START ",
+            "End-Time",
+            "CRF ",
+            "Processing  Mode",
+            "source-video",
+            "Crf",
+        ]);
+        let columns = resolve_columns(&headers).unwrap();
+        assert_eq!(columns.name, 0);
+        assert_eq!(columns.start, 1);
+        assert_eq!(columns.end, Some(2));
+        assert_eq!(columns.crf, Some(3));
+        assert_eq!(columns.mode, Some(4));
+        assert_eq!(columns.source, Some(5));
+        assert_eq!(columns.preset, None);
+
+        for spelling in ["crf", "Crf", " crf ", "CRF"] {
+            let headers = StringRecord::from(vec!["name", "start", "end", spelling]);
+            assert_eq!(find_header_index(&headers, CRF_ALIASES), Some(3));
+        }
+    }
+
+    #[test]
+    fn row_crop_box_becomes_the_crop_filter() {
+        let headers = StringRecord::from(vec![
+            "name", "start", "end", "Crop_X", "crop y", "x", "width",
+        ]);
+        let columns = resolve_columns(&headers).unwrap();
+        assert_eq!((columns.crop_x, columns.crop_y), (Some(3), Some(4)));
+        assert_eq!((columns.crop_w, columns.crop_h), (None, None));
+
+        let overrides = RowOverrides {
+            crop_x: Some("100".to_string()),
+            crop_y: Some("50".to_string()),
+            crop_w: Some("641".to_string()),
+            crop_h: Some("360".to_string()),
+            ..RowOverrides::default()
+        };
+        let crop = row_crop(&overrides, Some((1920, 1080))).unwrap().unwrap();
+        let filters = video_filters(&ExportSettings::default(), Some(&crop));
+        assert_eq!(
+            filters.first().map(String::as_str),
+            Some("crop=640:360:100:50")
+        );
+
+        assert!(row_crop(&RowOverrides::default(), Some((1920, 1080)))
+            .unwrap()
+            .is_none());
+        assert!(row_crop(&overrides, Some((640, 360))).is_err());
+        let partial = RowOverrides {
+            crop_h: None,
+            ..overrides
+        };
+        assert!(row_crop(&partial, None)
+            .unwrap_err()
+            .contains("crop_h missing"));
+    }
+
+    #[test]
+    fn webp_preview_encodes_with_libwebp_anim() {
+        let settings = ExportSettings {
+            processing_mode: "webp".to_string(),
+            webp_quality: 70,
+            preview_fps: 12.0,
+            preview_width: 480,
+            ..ExportSettings::default()
+        };
+        let spec = ClipSpec {
+            container: "webp",
+            row_ext: "webp",
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        let args = args_of(&clip_command(&spec).cmd);
+        let input = args.iter().position(|a| a == "-i").unwrap();
+        assert_eq!(args[input - 2..input], ["-ss", "10.000"]);
+        assert_eq!(
+            args[input + 2..],
+            [
+                "-t",
+                "15.500",
+                "-vf",
+                "fps=12,scale=480:-2:flags=lanczos",
+                "-c:v",
+                "libwebp_anim",
+                "-lossless",
+                "0",
+                "-q:v",
+                "70",
+                "-loop",
+                "0",
+                "-an",
+            ]
+        );
+    }
+
+    #[test]
+    fn smpte_times_subtract_the_source_start_timecode() {
+        let settings = ExportSettings {
+            source_timecode_offset: Some("10:00:00:00".to_string()),
+            ..ExportSettings::default()
+        };
+        let offset = resolve_timecode_offset(&PATH_TOOLS, &settings, 25.0, None);
+        assert_eq!(offset, 36000.0);
+        let seconds = timeline_seconds("10:00:05:12", offset, 25.0).unwrap();
+        assert!((seconds - 5.48).abs() < 1e-9);
+        assert_eq!(timeline_seconds("10:01:00:00", offset, 25.0), Some(60.0));
+        assert_eq!(timeline_seconds("09:59:59:24", offset, 25.0), None);
+        assert_eq!(timeline_seconds("10:00:00:25", offset, 25.0), None);
+        assert!(clip_time_seconds("00:00:05:00", offset, 25.0, Some(120.0)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_mode_sets_unix_permission_bits() {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(parse_octal_mode("0640"), Some(0o640));
+        assert_eq!(parse_octal_mode("0o755"), Some(0o755));
+        assert_eq!(parse_octal_mode("888"), None);
+        assert_eq!(parse_octal_mode("12345"), None);
 
-    ensure_ffmpeg_exists()?;
+        let dir = scratch_dir("output-mode");
+        let settings = ExportSettings {
+            output_mode: Some("640".to_string()),
+            ..fake_tools(&dir, WRITES_OUTPUT)
+        };
+        let (summary, _) = export_rows(
+            &dir,
+            settings,
+            vec![row("goal", "00:00:10", "00:00:20")],
+            false,
+        );
+        let summary = summary.unwrap();
+        assert_eq!(summary.exported, 1, "{:?}", summary.errors);
+        let output = summary.rows[0].output_path.as_deref().unwrap();
+        let mode = std::fs::metadata(output).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
 
-    let clip_rows = if let Some(rows) = edited_rows {
-        let normalized = rows
-            .into_iter()
-            .map(|r| ClipRow {
-                clip_name: if r.clip_name.trim().is_empty() {
-                    "clip".to_string()
-                } else {
-                    r.clip_name.trim().to_string()
-                },
-                start_time: r.start_time.trim().to_string(),
-                end_time: r.end_time.trim().to_string(),
-            })
-            .filter(|r| {
-                !(r.clip_name.is_empty() && r.start_time.is_empty() && r.end_time.is_empty())
+    #[test]
+    fn clips_closer_than_the_gap_are_merged() {
+        let clip = |name: &str, start: &str, end: &str| ClipRow {
+            clip_name: name.to_string(),
+            start_time: start.to_string(),
+            end_time: end.to_string(),
+            overrides: RowOverrides::default(),
+        };
+        let rows = vec![
+            clip("second", "00:00:10.2", "00:00:20"),
+            clip("first", "00:00:00", "00:00:10"),
+            clip("apart", "00:00:21", "00:00:30"),
+        ];
+        let (merged, count) = merge_adjacent_clips(rows, 0.5, 0.0, 30.0);
+        assert_eq!(count, 1);
+        let spans = merged
+            .iter()
+            .map(|r| {
+                (
+                    r.clip_name.as_str(),
+                    r.start_time.as_str(),
+                    r.end_time.as_str(),
+                )
             })
             .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [
+                ("first", "00:00:00", "00:00:20"),
+                ("apart", "00:00:21", "00:00:30"),
+            ]
+        );
+    }
 
-        if normalized.is_empty() {
-            return Err("No editable rows to export. Load a CSV first.".to_string());
-        }
+    #[test]
+    fn exact_frames_select_the_frame_range() {
+        assert_eq!(frame_range(10.0, 25.5, 25.0), (250, 637));
+        assert_eq!(frame_range(1.0, 1.0, 25.0), (25, 25));
 
-        normalized
-    } else {
-        read_clip_rows(&csv_path)?
-    };
-    let total = clip_rows.len();
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            ..ExportSettings::default()
+        };
+        let spec = ClipSpec {
+            exact_fps: Some(25.0),
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        let args = args_of(&clip_command(&spec).cmd);
+        let filter = args.iter().position(|a| a == "-vf").unwrap();
+        assert_eq!(
+            args[filter + 1..filter + 6],
+            [
+                "select='between(n,250,637)',setpts=N/FRAME_RATE/TB",
+                "-frames:v",
+                "388",
+                "-r",
+                "25",
+            ]
+        );
+        assert!(!args.contains(&"-ss".to_string()));
+        assert!(args.contains(&"atrim=start=10:end=25.52,asetpts=PTS-STARTPTS".to_string()));
+    }
 
-    if total == 0 {
-        return Err("CSV has no rows".to_string());
+    #[test]
+    fn fragmented_mp4_sets_fragment_movflags() {
+        let settings = ExportSettings {
+            fragmented_mp4: true,
+            ..ExportSettings::default()
+        };
+        let args = args_of(&clip_command(&clip_spec(&settings, Path::new("match.mp4"))).cmd);
+        assert_eq!(
+            args[args.len() - 2..],
+            ["-movflags", "+frag_keyframe+empty_moov+default_base_moof"]
+        );
+        assert!(!args.contains(&"+faststart".to_string()));
+
+        let mkv = ClipSpec {
+            container: "mkv",
+            row_ext: "mkv",
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        assert!(!args_of(&clip_command(&mkv).cmd).contains(&"-movflags".to_string()));
+        assert_eq!(movflags("mov", false), Some("+faststart"));
+        assert_eq!(movflags("m4a", true), None);
     }
 
-    let source_video = PathBuf::from(&video_path);
-    if !source_video.exists() {
-        return Err(format!("Video file not found: {video_path}"));
+    #[test]
+    fn a_second_export_is_refused_while_one_runs() {
+        let state = ProcessState::default();
+        let first = RunningGuard::acquire(state.is_running.clone()).unwrap();
+        assert_eq!(
+            RunningGuard::acquire(state.is_running.clone())
+                .err()
+                .as_deref(),
+            Some("An export is already in progress")
+        );
+        drop(first);
+        assert!(!state.is_running.load(Ordering::SeqCst));
+        assert!(RunningGuard::acquire(state.is_running.clone()).is_ok());
     }
 
-    let output_path = PathBuf::from(&output_dir);
-    std::fs::create_dir_all(&output_path)
-        .map_err(|e| format!("Failed to create output directory: {e}"))?;
+    #[test]
+    fn seek_accuracy_flags_come_before_the_input() {
+        for (accurate_seek, flag) in [
+            (Some(false), "-noaccurate_seek"),
+            (Some(true), "-accurate_seek"),
+        ] {
+            let settings = ExportSettings {
+                processing_mode: "reencode_fast_seek".to_string(),
+                accurate_seek,
+                ..ExportSettings::default()
+            };
+            let args = args_of(&clip_command(&clip_spec(&settings, Path::new("match.mp4"))).cmd);
+            let input = args.iter().position(|a| a == "-i").unwrap();
+            assert_eq!(args[input - 3..=input], ["-ss", "10.000", flag, "-i"]);
+        }
 
-    let mut exported = 0usize;
-    let mut skipped = 0usize;
-    let mut failed = 0usize;
-    let mut errors = Vec::new();
+        let settings = ExportSettings {
+            processing_mode: "reencode_fast_seek".to_string(),
+            ..ExportSettings::default()
+        };
+        let args = args_of(&clip_command(&clip_spec(&settings, Path::new("match.mp4"))).cmd);
+        assert!(!args.iter().any(|a| a.ends_with("accurate_seek")));
+    }
 
-    emit_progress(
-        &app,
-        ProgressPayload {
-            total,
-            completed: 0,
-            current_clip: String::new(),
-            status: "running".to_string(),
-            message: "Starting export...".to_string(),
-            row_index: None,
-            row_result: None,
-        },
-    );
+    #[test]
+    fn watermark_overlays_the_image_on_the_filtered_video() {
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            watermark_image: Some("/marks/draft.png".to_string()),
+            watermark_position: "top_left".to_string(),
+            watermark_opacity: 0.3,
+            watermark_scale: 0.2,
+            ..ExportSettings::default()
+        };
+        let graph = "movie='/marks/draft.png',format=rgba,colorchannelmixer=aa=0.3[wm];\
+                     [in]scale=1280:-2[base];\
+                     [wm][base]scale2ref=w=main_w*0.2:h=ow/dar[wms][base2];\
+                     [base2][wms]overlay=20:20[out]";
+        assert_eq!(
+            filter_graph(&["scale=1280:-2".to_string()], &settings).as_deref(),
+            Some(graph)
+        );
+        assert!(filter_graph(&[], &settings)
+            .unwrap()
+            .contains("[in]null[base]"));
 
-    for (idx, row) in clip_rows.iter().enumerate() {
-        if stop_state.load(Ordering::SeqCst) {
-            emit_progress(
-                &app,
-                ProgressPayload {
-                    total,
-                    completed: idx,
-                    current_clip: row.clip_name.clone(),
-                    status: "stopped".to_string(),
-                    message: "Export stopped by user".to_string(),
-                    row_index: Some(idx),
-                    row_result: Some("failed".to_string()),
-                },
-            );
-            break;
+        let args = args_of(&clip_command(&clip_spec(&settings, Path::new("match.mp4"))).cmd);
+        let filter = args.iter().position(|a| a == "-vf").unwrap();
+        assert!(args[filter + 1].ends_with("[base2][wms]overlay=20:20[out]"));
+    }
+
+    #[test]
+    fn csv_reads_utf16le_and_latin1_files() {
+        let dir = scratch_dir("csv-encoding");
+        let text = "Clip Name,Start,End\nCafé goal,00:00:10,00:00:20\n";
+
+        let utf16 = dir.join("utf16.csv");
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        std::fs::write(&utf16, bytes).unwrap();
+        for encoding in ["auto", "utf-16le"] {
+            let rows = read_clip_rows(&utf16.to_string_lossy(), encoding, 0.0, 30.0).unwrap();
+            assert_eq!(rows[0].clip_name, "Café goal");
+            assert_eq!(rows[0].end_time, "00:00:20");
         }
 
-        let start_sec = match convert_to_seconds(&row.start_time) {
-            Some(v) => v,
-            None => {
-                skipped += 1;
-                let err = format!(
-                    "Row {} skipped: invalid start time '{}'",
-                    idx + 2,
-                    row.start_time
-                );
-                errors.push(err.clone());
-                emit_progress(
-                    &app,
-                    ProgressPayload {
-                        total,
-                        completed: idx + 1,
-                        current_clip: row.clip_name.clone(),
-                        status: "running".to_string(),
-                        message: err,
-                        row_index: Some(idx),
-                        row_result: Some("failed".to_string()),
-                    },
-                );
-                continue;
-            }
+        let latin1 = dir.join("latin1.csv");
+        std::fs::write(&latin1, text.chars().map(|c| c as u8).collect::<Vec<_>>()).unwrap();
+        let rows = read_clip_rows(&latin1.to_string_lossy(), "latin-1", 0.0, 30.0).unwrap();
+        assert_eq!(rows[0].clip_name, "Café goal");
+        let err = read_clip_rows(&latin1.to_string_lossy(), "auto", 0.0, 30.0)
+            .err()
+            .unwrap();
+        assert!(err.contains("latin-1"));
+    }
+
+    #[test]
+    fn dependency_status_reports_missing_tools() {
+        let dir = scratch_dir("missing-tools");
+        let ffmpeg = dir.join("ffmpeg").to_string_lossy().to_string();
+        let ffprobe = dir.join("ffprobe").to_string_lossy().to_string();
+        let status = check_dependencies(Some(ffmpeg.clone()), Some(ffprobe));
+        assert!(!status.ffmpeg.found);
+        assert!(status.ffmpeg.path.is_none() && status.ffmpeg.version.is_none());
+        assert!(!status.ffprobe.found);
+        assert_eq!(status.install_hint.as_deref(), ffmpeg_install_hint());
+
+        let err = Tools::new(Some(&ffmpeg), None).ensure_ffmpeg().unwrap_err();
+        assert!(err.contains("missing or not executable"));
+    }
+
+    #[test]
+    fn audio_offset_reads_the_audio_from_a_delayed_second_input() {
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            ..ExportSettings::default()
         };
+        let spec = ClipSpec {
+            audio_offset: Some(-250),
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        let args = args_of(&clip_command(&spec).cmd);
+        let inputs = args
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| *a == "-i")
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        assert_eq!(inputs.len(), 2);
+        let offset = args.iter().position(|a| a == "-itsoffset").unwrap();
+        assert!(inputs[0] < offset);
+        assert_eq!(
+            args[offset..inputs[1] + 6],
+            [
+                "-itsoffset",
+                "-0.25",
+                "-i",
+                "match.mp4",
+                "-map",
+                "0:v:0",
+                "-map",
+                "1:a:0?"
+            ]
+        );
+    }
 
-        let end_sec = match convert_to_seconds(&row.end_time) {
-            Some(v) => v,
-            None => {
-                skipped += 1;
-                let err = format!(
-                    "Row {} skipped: invalid end time '{}'",
-                    idx + 2,
-                    row.end_time
-                );
-                errors.push(err.clone());
-                emit_progress(
-                    &app,
-                    ProgressPayload {
-                        total,
-                        completed: idx + 1,
-                        current_clip: row.clip_name.clone(),
-                        status: "running".to_string(),
-                        message: err,
-                        row_index: Some(idx),
-                        row_result: Some("failed".to_string()),
-                    },
-                );
-                continue;
-            }
+    #[cfg(unix)]
+    #[test]
+    fn export_runs_from_edited_rows_without_a_csv() {
+        let dir = scratch_dir("edited-rows");
+        let rows = vec![
+            row(" goal ", " 00:00:10", "00:00:20 "),
+            row("", "", ""),
+            row("save", "00:01:00", "00:01:05"),
+        ];
+        let summary = export_rows(&dir, fake_tools(&dir, "exit 1"), rows, true)
+            .0
+            .unwrap();
+        assert_eq!(summary.total_rows, 2);
+        assert_eq!(summary.planned_commands.len(), 2);
+        let output = &summary.planned_commands[0].output_path;
+        assert!(output.ends_with("001-goal-000010.mp4"), "{output}");
+
+        let blank = export_rows(
+            &dir,
+            fake_tools(&dir, "exit 1"),
+            vec![row("", " ", "")],
+            true,
+        )
+        .0;
+        assert_eq!(
+            blank.err().as_deref(),
+            Some("No editable rows to export. Load a CSV first.")
+        );
+    }
+
+    #[test]
+    fn collision_suffix_template_numbers_taken_names() {
+        let dir = scratch_dir("collision-suffix");
+        let destination = dir.join("name.mp4");
+        assert_eq!(unique_destination(&destination, "_v{n}"), destination);
+
+        std::fs::write(&destination, b"").unwrap();
+        assert_eq!(
+            unique_destination(&destination, "_v{n}"),
+            dir.join("name_v2.mp4")
+        );
+        std::fs::write(dir.join("name_v2.mp4"), b"").unwrap();
+        assert_eq!(
+            unique_destination(&destination, "_v{n}"),
+            dir.join("name_v3.mp4")
+        );
+        assert_eq!(
+            unique_destination(&destination, " ({n})"),
+            dir.join("name (2).mp4")
+        );
+
+        let normalized = |suffix: &str| {
+            normalize_settings(ExportSettings {
+                collision_suffix: suffix.to_string(),
+                ..ExportSettings::default()
+            })
+            .collision_suffix
         };
+        assert_eq!(normalized("_v{n}"), "_v{n}");
+        assert_eq!(normalized("_copy"), "-{n}");
+        assert_eq!(normalized("/{n}"), "-{n}");
+    }
 
-        if end_sec <= start_sec {
-            skipped += 1;
-            let err = format!(
-                "Row {} skipped: end time must be greater than start time",
-                idx + 2
-            );
-            errors.push(err.clone());
-            emit_progress(
-                &app,
-                ProgressPayload {
-                    total,
-                    completed: idx + 1,
-                    current_clip: row.clip_name.clone(),
-                    status: "running".to_string(),
-                    message: err,
-                    row_index: Some(idx),
-                    row_result: Some("failed".to_string()),
-                },
-            );
-            continue;
+    #[cfg(unix)]
+    #[test]
+    fn swapped_times_are_corrected_when_autoswap_is_on() {
+        let dir = scratch_dir("autoswap");
+        let rows = || vec![row("goal", "00:00:20", "00:00:10")];
+        let settings = ExportSettings {
+            autoswap_times: true,
+            ..fake_tools(&dir, "exit 1")
+        };
+        let summary = export_rows(&dir, settings, rows(), true).0.unwrap();
+        assert!(summary.rows[0].times_swapped);
+        assert!(
+            summary.warnings.iter().any(|w| w.contains("swapped them")),
+            "{:?}",
+            summary.warnings
+        );
+        let args = &summary.planned_commands[0].args;
+        assert!(args.windows(2).any(|w| w == ["-ss", "10.000"]));
+        assert!(args.windows(2).any(|w| w == ["-t", "10.000"]));
+        assert!(summary.planned_commands[0]
+            .output_path
+            .ends_with("001-goal-000010.mp4"));
+
+        let summary = export_rows(&dir, fake_tools(&dir, "exit 1"), rows(), true)
+            .0
+            .unwrap();
+        assert!(summary.planned_commands.is_empty());
+        assert_eq!(summary.skipped, 1);
+    }
+
+    #[test]
+    fn keyframe_at_start_forces_only_the_first_frame() {
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            force_keyframe_at_start: true,
+            ..ExportSettings::default()
+        };
+        let args = args_of(&clip_command(&clip_spec(&settings, Path::new("match.mp4"))).cmd);
+        assert!(args.windows(2).any(|w| w == ["-force_key_frames", "0"]));
+
+        let copy = ExportSettings {
+            force_keyframe_at_start: true,
+            ..ExportSettings::default()
+        };
+        let args = args_of(&clip_command(&clip_spec(&copy, Path::new("match.mp4"))).cmd);
+        assert!(!args.contains(&"-force_key_frames".to_string()));
+    }
+
+    #[test]
+    fn renumbering_reverses_the_folder_order() {
+        let dir = scratch_dir("renumber");
+        for name in ["001-a.mp4", "002-b.mp4", "003-c.mp4"] {
+            std::fs::write(dir.join(name), name).unwrap();
         }
+        let names = renumber_outputs(
+            dir.to_string_lossy().to_string(),
+            vec!["003-c.mp4".into(), "002-b.mp4".into(), "001-a.mp4".into()],
+        )
+        .unwrap();
+        assert_eq!(names, ["001-c.mp4", "002-b.mp4", "003-a.mp4"]);
+        for (new, old) in [
+            ("001-c.mp4", "003-c.mp4"),
+            ("002-b.mp4", "002-b.mp4"),
+            ("003-a.mp4", "001-a.mp4"),
+        ] {
+            assert_eq!(std::fs::read_to_string(dir.join(new)).unwrap(), old);
+        }
+        assert!(!dir.join("001-a.mp4").exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
 
-        let safe_name = sanitize_filename(&row.clip_name);
-        let start_label = row.start_time.replace(':', "");
-        let output_ext = if settings.processing_mode == "copy_fast" {
-            source_video
-                .extension()
-                .and_then(|e| e.to_str())
-                .map(|s| s.to_lowercase())
-                .filter(|s| !s.is_empty())
-                .unwrap_or_else(|| "mp4".to_string())
-        } else {
-            "mp4".to_string()
+        let err = renumber_outputs(
+            dir.to_string_lossy().to_string(),
+            vec!["001-c.mp4".into(), "001-c.mp4".into()],
+        )
+        .unwrap_err();
+        assert_eq!(err, "File listed more than once: 001-c.mp4");
+    }
+
+    #[test]
+    fn short_range_is_padded_to_the_fixed_duration() {
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            ..ExportSettings::default()
         };
-        let file_name = format!(
-            "{:03}-{}-{}.{}",
-            idx + 1,
-            safe_name,
-            start_label,
-            output_ext
-        );
-        let destination = output_path.join(file_name);
+        let spec = ClipSpec {
+            end_sec: 13.0,
+            fixed_duration: Some(5.0),
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        let clip = clip_command(&spec);
+        let args = args_of(&clip.cmd);
+        let input = args.iter().position(|a| a == "-i").unwrap();
+        assert_eq!(args[input - 2..input], ["-to", "13.000"]);
+        assert_eq!(args[input + 2..input + 6], ["-ss", "10.000", "-t", "5.000"]);
+        assert!(args.contains(&"tpad=stop_mode=clone:stop_duration=2".to_string()));
+        assert!(args.windows(2).any(|w| w == ["-af", "apad"]));
+        assert_eq!(clip.output_duration, 5.0);
+    }
 
-        emit_progress(
-            &app,
-            ProgressPayload {
-                total,
-                completed: idx,
-                current_clip: row.clip_name.clone(),
-                status: "running".to_string(),
-                message: format!("Exporting clip {} of {}", idx + 1, total),
-                row_index: Some(idx),
-                row_result: Some("running".to_string()),
+    #[cfg(unix)]
+    #[test]
+    fn featured_rows_go_to_the_featured_folder() {
+        let dir = scratch_dir("featured");
+        let mut featured = row("goal", "00:00:10", "00:00:20");
+        featured.overrides.featured = Some("Yes".to_string());
+        let rows = vec![featured, row("save", "00:01:00", "00:01:05")];
+        let summary = export_rows(&dir, fake_tools(&dir, "exit 1"), rows, true)
+            .0
+            .unwrap();
+        let out = dir.join("out");
+        let planned = |idx: usize| PathBuf::from(&summary.planned_commands[idx].output_path);
+        assert_eq!(planned(0).parent(), Some(out.join("featured").as_path()));
+        assert_eq!(planned(1).parent(), Some(out.as_path()));
+        assert!(summary.rows[0].featured);
+        assert!(!summary.rows[1].featured);
+
+        let mut invalid = row("goal", "00:00:10", "00:00:20");
+        invalid.overrides.featured = Some("maybe".to_string());
+        assert!(row_featured(&invalid.overrides).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn validate_only_writes_no_files() {
+        let dir = scratch_dir("validate-only");
+        let video = dir.join("match.mp4");
+        std::fs::write(&video, b"").unwrap();
+        let summary = run_export(
+            Box::new(|_| {}),
+            Arc::new(Mutex::new(Vec::new())),
+            Arc::new(AtomicBool::new(false)),
+            None,
+            video.to_string_lossy().to_string(),
+            dir.join("out").to_string_lossy().to_string(),
+            ExportSettings {
+                resolve_export: true,
+                ..fake_tools(&dir, WRITES_OUTPUT)
             },
+            Some(vec![
+                row("goal", "00:00:10", "00:00:20"),
+                row("save", "00:01:00", "00:01:05"),
+            ]),
+            None,
+            true,
+            false,
+        )
+        .unwrap();
+        assert!(summary.validate_only);
+        assert_eq!(summary.exported, 0);
+        assert!(summary.rows.iter().all(|r| r.status == "valid"));
+        assert!(!dir.join("out").exists());
+        assert!(!dir.join("ffmpeg.log").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn row_lufs_overrides_the_run_loudness_target() {
+        let lufs = |value: &str| RowOverrides {
+            lufs: Some(value.to_string()),
+            ..RowOverrides::default()
+        };
+        assert_eq!(
+            row_loudness_target(&lufs("-23"), Some(-16.0)),
+            Ok(Some(-23.0))
+        );
+        assert_eq!(
+            row_loudness_target(&RowOverrides::default(), Some(-16.0)),
+            Ok(Some(-16.0))
         );
+        assert!(row_loudness_target(&lufs("-80"), Some(-16.0)).is_err());
 
-        let mut cmd = Command::new("ffmpeg");
-        let duration = end_sec - start_sec;
-        cmd.arg("-y").arg("-loglevel").arg("error").arg("-nostats");
-
-        match settings.processing_mode.as_str() {
-            "copy_fast" => {
-                cmd.arg("-ss")
-                    .arg(start_sec.to_string())
-                    .arg("-i")
-                    .arg(&source_video)
-                    .arg("-t")
-                    .arg(duration.to_string())
-                    .arg("-c")
-                    .arg("copy");
-            }
-            "reencode_fast_seek" => {
-                cmd.arg("-ss")
-                    .arg(start_sec.to_string())
-                    .arg("-i")
-                    .arg(&source_video)
-                    .arg("-t")
-                    .arg(duration.to_string())
-                    .arg("-c:v")
-                    .arg("libx264")
-                    .arg("-preset")
-                    .arg(&settings.preset)
-                    .arg("-crf")
-                    .arg(settings.crf.to_string());
-
-                if let Some(filter) = resolution_filter(&settings.resolution) {
-                    cmd.arg("-vf").arg(filter);
-                }
-
-                if let Some(fps) = settings.fps {
-                    cmd.arg("-r").arg(fps.to_string());
-                }
-            }
-            _ => {
-                cmd.arg("-i")
-                    .arg(&source_video)
-                    .arg("-ss")
-                    .arg(start_sec.to_string())
-                    .arg("-to")
-                    .arg(end_sec.to_string())
-                    .arg("-c:v")
-                    .arg("libx264")
-                    .arg("-preset")
-                    .arg(&settings.preset)
-                    .arg("-crf")
-                    .arg(settings.crf.to_string());
+        let dir = scratch_dir("row-lufs");
+        let mut quiet = row("quiet", "00:00:10", "00:00:20");
+        quiet.overrides = lufs("-23");
+        let mut broken = row("broken", "00:02:00", "00:02:05");
+        broken.overrides = lufs("-80");
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            loudness_target: Some(-16.0),
+            ..fake_tools(&dir, "exit 1")
+        };
+        let rows = vec![quiet, row("loud", "00:01:00", "00:01:05"), broken];
+        let summary = export_rows(&dir, settings, rows, true).0.unwrap();
+        let planned = &summary.planned_commands;
+        assert_eq!(planned.len(), 2);
+        assert!(planned[0]
+            .args
+            .contains(&"loudnorm=I=-23:TP=-1.5:LRA=11".to_string()));
+        assert!(planned[1]
+            .args
+            .contains(&"loudnorm=I=-16:TP=-1.5:LRA=11".to_string()));
+        assert_eq!(summary.skipped, 1);
+    }
 
-                if let Some(filter) = resolution_filter(&settings.resolution) {
-                    cmd.arg("-vf").arg(filter);
-                }
+    #[cfg(unix)]
+    #[test]
+    fn source_is_kept_when_a_clip_fails() {
+        let rows = || {
+            vec![
+                row("goal", "00:00:10", "00:00:20"),
+                row("save", "00:01:00", "00:01:05"),
+            ]
+        };
+        let deleting = |dir: &Path, body: &str| ExportSettings {
+            delete_source_on_success: true,
+            confirm_delete_source: true,
+            ..fake_tools(dir, body)
+        };
 
-                if let Some(fps) = settings.fps {
-                    cmd.arg("-r").arg(fps.to_string());
-                }
-            }
-        }
+        let dir = scratch_dir("delete-source-failed");
+        let fails_save = format!("case \"$*\" in *save*) exit 1;; esac\n{WRITES_OUTPUT}");
+        let summary = export_rows(&dir, deleting(&dir, &fails_save), rows(), false)
+            .0
+            .unwrap();
+        assert_eq!((summary.exported, summary.failed), (1, 1));
+        assert!(summary.deleted_sources.is_empty());
+        assert!(dir.join("match.mp4").exists());
 
-        if settings.processing_mode != "copy_fast" {
-            match settings.audio_codec.as_str() {
-                "none" => {
-                    cmd.arg("-an");
-                }
-                "copy" => {
-                    cmd.arg("-c:a").arg("copy");
-                }
-                _ => {
-                    cmd.arg("-c:a")
-                        .arg("aac")
-                        .arg("-b:a")
-                        .arg(format!("{}k", settings.audio_bitrate_kbps));
-                }
-            }
-        }
+        let dir = scratch_dir("delete-source-done");
+        let summary = export_rows(&dir, deleting(&dir, WRITES_OUTPUT), rows(), false)
+            .0
+            .unwrap();
+        assert_eq!(summary.exported, 2);
+        assert_eq!(summary.deleted_sources.len(), 1);
+        assert!(!dir.join("match.mp4").exists());
+    }
+
+    #[test]
+    fn denoise_runs_before_scaling_and_sharpen_after() {
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            denoise: "medium".to_string(),
+            sharpen: true,
+            resolution: "720p".to_string(),
+            ..ExportSettings::default()
+        };
+        let filters = video_filters(&settings, None);
+        assert_eq!(filters.len(), 3);
+        assert_eq!(filters[0], "hqdn3d=4:3:6:4.5");
+        assert!(filters[1].starts_with("scale=1280:720"));
+        assert_eq!(filters[2], "unsharp=5:5:0.8:5:5:0.0");
+
+        let args = args_of(&clip_command(&clip_spec(&settings, Path::new("match.mp4"))).cmd);
+        assert!(args.contains(&filters.join(",")));
+    }
 
-        if output_ext == "mp4" || output_ext == "m4v" {
-            cmd.arg("-movflags").arg("+faststart");
+    #[cfg(unix)]
+    #[test]
+    fn per_source_scope_never_runs_one_source_twice_at_once() {
+        for (scope, overlaps) in [("per_source", false), ("global", true)] {
+            let dir = scratch_dir(&format!("parallel-{scope}"));
+            let lock = dir.join("running");
+            let overlap = dir.join("overlap");
+            let body = format!(
+                "mkdir '{}' 2>/dev/null || touch '{}'\nsleep 0.3\nrmdir '{}' 2>/dev/null\n{WRITES_OUTPUT}",
+                lock.display(),
+                overlap.display(),
+                lock.display()
+            );
+            let settings = ExportSettings {
+                max_parallel: 4,
+                parallel_scope: scope.to_string(),
+                ..fake_tools(&dir, &body)
+            };
+            let rows = vec![
+                row("one", "00:00:00", "00:00:10"),
+                row("two", "00:01:00", "00:01:10"),
+                row("three", "00:02:00", "00:02:10"),
+            ];
+            let summary = export_rows(&dir, settings, rows, false).0.unwrap();
+            assert_eq!(summary.exported, 3);
+            assert_eq!(overlap.exists(), overlaps, "{scope}");
         }
+    }
 
-        cmd.arg(&destination)
-            .stdin(Stdio::null())
-            .stdout(Stdio::null())
-            .stderr(Stdio::null());
+    #[test]
+    fn full_range_source_is_converted_to_tv_range() {
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            color_range: "tv".to_string(),
+            ..ExportSettings::default()
+        };
+        let info = VideoInfo {
+            color_range: Some("pc".to_string()),
+            ..VideoInfo::default()
+        };
+        let spec = ClipSpec {
+            source_info: Some(&info),
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        let args = args_of(&clip_command(&spec).cmd);
+        assert!(args
+            .windows(2)
+            .any(|w| w == ["-vf", "scale=in_range=pc:out_range=tv"]));
+        assert!(args.windows(2).any(|w| w == ["-color_range", "tv"]));
+
+        assert_eq!(
+            color_range_conversion("tv", Some("tv".to_string())),
+            (None, Some("tv".to_string()))
+        );
+        assert_eq!(
+            color_range_conversion("auto", Some("pc".to_string())),
+            (None, Some("pc".to_string()))
+        );
+    }
 
-        let child = cmd
-            .spawn()
-            .map_err(|e| format!("Failed to start ffmpeg process: {e}"))?;
+    #[cfg(unix)]
+    #[test]
+    fn start_index_numbers_the_first_clip() {
+        let dir = scratch_dir("start-index");
+        let settings = ExportSettings {
+            start_index: Some(100),
+            ..fake_tools(&dir, "exit 1")
+        };
+        let rows = vec![
+            row("goal", "00:00:10", "00:00:20"),
+            row("save", "00:01:00", "00:01:05"),
+        ];
+        let summary = export_rows(&dir, settings, rows, true).0.unwrap();
+        let names = summary
+            .planned_commands
+            .iter()
+            .map(|c| {
+                Path::new(&c.output_path)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["100-goal-000010.mp4", "101-save-000100.mp4"]);
+    }
 
-        {
-            let mut guard = child_state.lock().map_err(|e| e.to_string())?;
-            *guard = Some(child);
-        }
-
-        let output_status = loop {
-            let status = {
-                let mut guard = child_state.lock().map_err(|e| e.to_string())?;
-                let running = guard
-                    .as_mut()
-                    .ok_or_else(|| "Internal error: ffmpeg process missing".to_string())?;
-                running
-                    .try_wait()
-                    .map_err(|e| format!("Failed waiting on ffmpeg process: {e}"))?
+    #[test]
+    fn filter_text_escapes_graph_and_option_syntax() {
+        assert_eq!(
+            escape_filter_text("goal: it's 1-0 \\ [home],away;"),
+            r"'goal\: it\'\''s 1-0 \\ [home],away;'"
+        );
+        assert_eq!(
+            escape_filter_path(r"C:\clips\it's.srt"),
+            r"'C\:/clips/it\'\''s.srt'"
+        );
+        assert!(check_filter_text("name\n[out]drawtext", "Clip name").is_err());
+        assert!(check_filter_text("%{pts} ; rm -rf", "Clip name").is_ok());
+
+        let settings = ExportSettings::default();
+        let label = label_filter("%{pts}'; x", &settings);
+        assert!(label.contains(r"text='%{pts}\'\''; x':expansion=none"));
+    }
+
+    #[test]
+    fn total_duration_counts_only_valid_rows() {
+        let summary = total_duration(
+            vec![
+                row("goal", "00:00:10", "00:00:40"),
+                row("save", "1:00", "1:30.5"),
+                row("backwards", "00:02:00", "00:01:00"),
+                row("typo", "00:0x:10", "00:00:20"),
+                row("blank", "", ""),
+            ],
+            None,
+        );
+        assert_eq!(summary.total_seconds, 60.5);
+        assert_eq!(summary.formatted, format_timestamp(60.5));
+        assert_eq!((summary.valid_clips, summary.invalid_clips), (2, 3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_mp4_copy_is_retried_as_mkv() {
+        let dir = scratch_dir("container-fallback");
+        // Fails like a muxer error whenever the output (the last argument) is an .mp4.
+        let body = format!(
+            "for out; do :; done\n\
+             case \"$out\" in *.mp4) echo 'Could not write header' >&2; exit 1;; esac\n\
+             {WRITES_OUTPUT}"
+        );
+        let settings = ExportSettings {
+            copy_container_fallback: true,
+            ..fake_tools(&dir, &body)
+        };
+        let summary = export_rows(
+            &dir,
+            settings,
+            vec![row("goal", "00:00:10", "00:00:20")],
+            false,
+        )
+        .0
+        .unwrap();
+        assert_eq!(summary.exported, 1, "{:?}", summary.errors);
+        let result = &summary.rows[0];
+        let output = result.output_path.as_deref().unwrap();
+        assert!(output.ends_with("001-goal-000010.mkv"), "{output}");
+        assert!(Path::new(output).is_file());
+        assert!(result.container_fallback.is_some());
+        assert!(!dir.join("out").join("001-goal-000010.mp4").exists());
+    }
+
+    #[test]
+    fn chapter_metadata_lists_titled_chapters_back_to_back() {
+        let part = |title: &str, description: Option<&str>| ChapterPart {
+            path: PathBuf::from("clip.mp4"),
+            title: title.to_string(),
+            description: description.map(str::to_string),
+        };
+        let metadata = chapter_metadata(
+            &[
+                part("Kick-off", None),
+                part("Goal; 1=0 #9", Some("Header\nfrom a corner")),
+            ],
+            &[12.5, 30.0],
+        );
+        assert_eq!(
+            metadata,
+            ";FFMETADATA1\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=12500\ntitle=Kick-off\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=12500\nEND=42500\ntitle=Goal\\; 1\\=0 \\#9\n\
+             description=Header\\\nfrom a corner\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn identical_inputs_get_identical_hash_suffixes() {
+        let source = Path::new("/videos/match.mp4");
+        let hash = clip_hash(source, 10.0, 20.0, 8);
+        assert_eq!(hash.len(), 8);
+        assert!(hash.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_eq!(clip_hash(source, 10.0, 20.0, 8), hash);
+        assert_eq!(clip_hash(source, 10.0004, 20.0, 8), hash);
+        assert_ne!(clip_hash(source, 10.0, 20.5, 8), hash);
+        assert_ne!(
+            clip_hash(Path::new("/videos/other.mp4"), 10.0, 20.0, 8),
+            hash
+        );
+        assert!(clip_hash(source, 10.0, 20.0, 12).starts_with(&hash));
+
+        let names = || {
+            let dir = scratch_dir("unique-suffix");
+            let settings = ExportSettings {
+                unique_suffix: Some(6),
+                ..fake_tools(&dir, "exit 1")
             };
+            let summary = export_rows(
+                &dir,
+                settings,
+                vec![row("goal", "00:00:10", "00:00:20")],
+                true,
+            )
+            .0
+            .unwrap();
+            Path::new(&summary.planned_commands[0].output_path)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .to_string()
+        };
+        let first = names();
+        assert_eq!(
+            first.len(),
+            "001-goal-000010-".len() + 6 + ".mp4".len(),
+            "{first}"
+        );
+        assert_eq!(names(), first);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn edge_silence_is_detected_and_trimmed_from_the_cut() {
+        assert_eq!(
+            silence_detect_filter(-35.0),
+            "silencedetect=noise=-35dB:d=0.1"
+        );
+
+        let dir = scratch_dir("edge-silence");
+        let body = format!(
+            "case \"$*\" in *silencedetect*)\n\
+             echo '[silencedetect @ 0x1] silence_start: 0' >&2\n\
+             echo '[silencedetect @ 0x1] silence_end: 1.5 | silence_duration: 1.5' >&2\n\
+             echo '[silencedetect @ 0x1] silence_start: 8.75' >&2\n\
+             exit 0;;\n\
+             esac\n\
+             {WRITES_OUTPUT}"
+        );
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            trim_edge_silence: true,
+            silence_threshold_db: -35.0,
+            ..fake_tools(&dir, &body)
+        };
+        let summary = export_rows(
+            &dir,
+            settings,
+            vec![row("goal", "00:00:10", "00:00:20")],
+            false,
+        )
+        .0
+        .unwrap();
+        assert_eq!(summary.exported, 1, "{:?}", summary.errors);
+        assert_eq!(summary.rows[0].trimmed_duration_sec, Some(7.25));
+
+        let log = std::fs::read_to_string(dir.join("ffmpeg.log")).unwrap();
+        let mut calls = log.lines();
+        let detect = calls.next().unwrap();
+        assert!(detect.contains("-ss 10 -t 10 -i"), "{detect}");
+        assert!(detect.contains("-af silencedetect=noise=-35dB:d=0.1 -f null -"));
+        let cut = calls.next().unwrap();
+        assert!(cut.contains("-ss 11.500 -to 18.750"), "{cut}");
+    }
+
+    #[test]
+    fn first_matching_rule_applies_and_others_keep_run_settings() {
+        let rule = |name: &str, pattern: &str, settings: ExportSettings| SettingsRule {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            settings,
+        };
+        let rules = [
+            rule(
+                "intros",
+                "intro",
+                ExportSettings {
+                    crf: 16,
+                    ..ExportSettings::default()
+                },
+            ),
+            rule(
+                "goals",
+                "goal",
+                ExportSettings {
+                    resolution: "720p".to_string(),
+                    ..ExportSettings::default()
+                },
+            ),
+        ];
+        let name = |clip_name: &str| matching_rule(&rules, clip_name).map(|r| r.name.as_str());
+        assert_eq!(name("Intro goal"), Some("intros"));
+        assert_eq!(name("Late GOAL"), Some("goals"));
+        assert_eq!(name("Penalty save"), None);
+        assert_eq!(matching_rule(&rules, "INTRO").unwrap().settings.crf, 16);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn progress_log_file_gets_one_line_per_event() {
+        let dir = scratch_dir("progress-log");
+        let log = dir.join("progress.jsonl");
+        let settings = ExportSettings {
+            progress_log_file: Some(log.to_string_lossy().to_string()),
+            ..fake_tools(&dir, "exit 1")
+        };
+        let rows = vec![
+            row("goal", "00:00:10", "00:00:20"),
+            row("save", "00:01:00", "00:01:05"),
+        ];
+        let (summary, lines) = export_rows(&dir, settings, rows, true);
+        summary.unwrap();
+        assert!(lines.len() >= 2);
+        let written = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(written.lines().count(), lines.len());
+        assert!(written
+            .lines()
+            .all(|line| line.starts_with('{') && line.ends_with('}')));
+    }
 
-            if let Some(status) = status {
-                break status;
-            }
+    #[test]
+    fn surround_sources_downmix_with_a_pan_matrix() {
+        assert_eq!(
+            downmix_filter(6).as_deref(),
+            Some("pan=stereo|FL<c0+0.707*c2+0.707*c4|FR<c1+0.707*c2+0.707*c5")
+        );
+        assert!(downmix_filter(8).unwrap().ends_with("+0.707*c5+0.707*c7"));
+        assert_eq!(downmix_filter(4), None);
 
-            std::thread::sleep(std::time::Duration::from_millis(120));
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            downmix: "stereo".to_string(),
+            ..ExportSettings::default()
+        };
+        let info = VideoInfo {
+            audio_channels: Some(6),
+            ..VideoInfo::default()
         };
+        let spec = ClipSpec {
+            source_info: Some(&info),
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        let args = args_of(&clip_command(&spec).cmd);
+        let filter = args.iter().position(|a| a == "-af").unwrap();
+        assert!(args[filter + 1].starts_with("pan=stereo|"));
+        assert!(!args.contains(&"-ac".to_string()));
+    }
 
-        {
-            let mut guard = child_state.lock().map_err(|e| e.to_string())?;
-            let _ = guard.take();
-        }
+    #[cfg(unix)]
+    #[test]
+    fn progress_is_weighted_by_clip_length() {
+        let weights = [10.0, 30.0, 60.0];
+        let results = [
+            RowResult::new(0, "one", "success"),
+            RowResult::new(2, "three", "failed"),
+        ];
+        assert_eq!(finished_weight(&weights, &results), 70.0);
+        assert_eq!(weighted_percent(70.0, 100.0), Some(70.0));
+        assert_eq!(weighted_percent(0.0, 0.0), None);
 
-        if stop_state.load(Ordering::SeqCst) {
-            failed += 1;
-            errors.push(format!("Stopped while exporting row {}", idx + 2));
-            break;
-        }
+        let dir = scratch_dir("weighted-progress");
+        let rows = vec![
+            row("short", "00:00:00", "00:00:10"),
+            row("long", "00:01:00", "00:01:30"),
+        ];
+        let (summary, lines) = export_rows(&dir, fake_tools(&dir, "exit 1"), rows, true);
+        summary.unwrap();
+        let percents = lines
+            .iter()
+            .filter(|line| line.payload.row_result.is_some())
+            .filter_map(|line| line.payload.weighted_percent)
+            .collect::<Vec<_>>();
+        assert_eq!(percents, [25.0, 100.0]);
+    }
 
-        if output_status.success() && destination.exists() {
-            exported += 1;
-        } else {
-            failed += 1;
-            errors.push(format!("Row {} failed ({})", idx + 2, row.clip_name));
-        }
+    #[test]
+    fn bumper_graph_scales_each_part_before_concat() {
+        let scale = "scale=1280:720:force_original_aspect_ratio=decrease,\
+                     pad=1280:720:(ow-iw)/2:(oh-ih)/2,setsar=1,fps=25,format=yuv420p";
+        assert_eq!(
+            bumper_filter_graph(&[(2.0, false), (15.5, true)], 1280, 720, 25.0, true),
+            format!(
+                "[0:v]{scale}[v0];\
+                 anullsrc=r=48000:cl=stereo,atrim=duration=2[a0];\
+                 [1:v]{scale}[v1];\
+                 [1:a]aformat=sample_rates=48000:channel_layouts=stereo[a1];\
+                 [v0][a0][v1][a1]concat=n=2:v=1:a=1[v][a]"
+            )
+        );
+        assert_eq!(
+            bumper_filter_graph(&[(15.5, true), (3.0, true)], 1280, 720, 25.0, false),
+            format!("[0:v]{scale}[v0];[1:v]{scale}[v1];[v0][v1]concat=n=2:v=1:a=0[v]")
+        );
+    }
 
-        emit_progress(
-            &app,
-            ProgressPayload {
-                total,
-                completed: idx + 1,
-                current_clip: row.clip_name.clone(),
-                status: "running".to_string(),
-                message: format!("Finished clip {} of {}", idx + 1, total),
-                row_index: Some(idx),
-                row_result: Some(if output_status.success() && destination.exists() {
-                    "success".to_string()
-                } else {
-                    "failed".to_string()
-                }),
-            },
+    #[test]
+    fn malformed_times_get_suggested_fixes() {
+        let fixes = suggest_timecode_fixes(
+            vec![
+                row("units", "1m30s", "00:02:00"),
+                row("short fields", "0:5:3", "'00:06:00"),
+                row("drop frame", "00:01:30;15", "1,5"),
+                row("hopeless", "soon", "00:00:10"),
+            ],
+            Some(30.0),
+        );
+        let found = fixes
+            .iter()
+            .map(|fix| (fix.row_index, fix.field.as_str(), fix.suggested.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                (0, "start_time", "00:01:30"),
+                (1, "start_time", "00:05:03"),
+                (1, "end_time", "00:06:00"),
+                (2, "start_time", "00:01:30:15"),
+                (2, "end_time", "00:00:01.5"),
+            ]
+        );
+        assert_eq!(fixes[1].original, "0:5:3");
+        assert_eq!(
+            suggest_timecode("00:00:59:45", 30.0).as_deref(),
+            Some("00:01:00:15")
         );
     }
 
-    let status = if stop_state.load(Ordering::SeqCst) {
-        "stopped"
-    } else {
-        "done"
-    };
+    #[cfg(unix)]
+    #[test]
+    fn ts_muxer_copies_into_a_ts_file() {
+        let dir = scratch_dir("mpegts");
+        let settings = ExportSettings {
+            processing_mode: "copy_fast".to_string(),
+            muxer_format: Some("ts".to_string()),
+            output_extension: Some("ts".to_string()),
+            ..fake_tools(&dir, "exit 1")
+        };
+        let (summary, _) = export_rows(
+            &dir,
+            settings.clone(),
+            vec![row("goal", "00:00:10", "00:00:20")],
+            true,
+        );
+        let args = &summary.unwrap().planned_commands[0].args;
 
-    emit_progress(
-        &app,
-        ProgressPayload {
-            total,
-            completed: exported + failed + skipped,
-            current_clip: String::new(),
-            status: status.to_string(),
-            message: format!(
-                "Done. Exported: {}, Skipped: {}, Failed: {}",
-                exported, skipped, failed
-            ),
-            row_index: None,
-            row_result: None,
-        },
-    );
+        let muxer = args.iter().position(|a| a == "-f").expect("muxer passed");
+        assert_eq!(args[muxer + 1], "mpegts");
+        assert!(args.last().unwrap().ends_with(".ts"));
+        assert!(!args.iter().any(|a| a == "-movflags"));
 
-    Ok(RunSummary {
-        total_rows: total,
-        exported,
-        skipped,
-        failed,
-        errors,
-    })
-}
+        let settings = ExportSettings {
+            muxer_format: Some("avi".to_string()),
+            ..settings
+        };
+        let (result, _) = export_rows(&dir, settings, vec![row("goal", "0:10", "0:20")], true);
+        assert!(result
+            .err()
+            .is_some_and(|err| err.contains("Unsupported muxer_format 'avi'")));
+    }
 
-fn emit_progress(app: &AppHandle, payload: ProgressPayload) {
-    let _ = app.emit("export-progress", payload);
-}
+    #[test]
+    fn sharing_violations_count_as_locked_files() {
+        assert!(is_locked_file_error(
+            "match.mp4: The process cannot access the file because it is being used by another process."
+        ));
+        assert!(is_locked_file_error("Sharing violation opening input"));
+        assert!(is_locked_file_error("Access failed (os error 32)"));
+        assert!(!is_locked_file_error(
+            "Invalid data found when processing input"
+        ));
+    }
 
-fn ensure_ffmpeg_exists() -> Result<(), String> {
-    which::which("ffmpeg")
-        .map(|_| ())
-        .map_err(|_| "ffmpeg not found in PATH. Install ffmpeg before running exports.".to_string())
-}
+    #[cfg(windows)]
+    #[test]
+    fn windows_permission_denied_is_retried_as_locked() {
+        assert!(is_locked_file_error("match.mp4: Permission denied"));
+    }
 
-fn read_clip_rows(csv_path: &str) -> Result<Vec<ClipRow>, String> {
-    let path = Path::new(csv_path);
-    if !path.exists() {
-        return Err(format!("CSV file not found: {csv_path}"));
+    #[cfg(not(windows))]
+    #[test]
+    fn permission_denied_elsewhere_is_not_a_lock() {
+        assert!(!is_locked_file_error("match.mp4: Permission denied"));
     }
 
-    let mut reader = csv::ReaderBuilder::new()
-        .flexible(true)
-        .from_path(path)
-        .map_err(|e| format!("Failed to open CSV: {e}"))?;
+    #[test]
+    fn segment_muxer_splits_on_forced_keyframes() {
+        let pattern = Path::new("out").join("match-%03d.mp4");
+        let copy = ExportSettings {
+            processing_mode: "copy_fast".to_string(),
+            ..ExportSettings::default()
+        };
+        let args = args_of(&segment_command(
+            &PATH_TOOLS,
+            Path::new("match.mp4"),
+            &pattern,
+            10.0,
+            &copy,
+            "mp4",
+        ));
+        let tail = [
+            "-c",
+            "copy",
+            "-f",
+            "segment",
+            "-segment_time",
+            "10",
+            "-reset_timestamps",
+            "1",
+        ];
+        assert!(args.windows(tail.len()).any(|w| w == tail));
+        assert_eq!(args.last().unwrap(), &pattern.to_string_lossy());
+        assert!(!args.iter().any(|a| a == "-force_key_frames"));
 
-    let headers = reader
-        .headers()
-        .map_err(|e| format!("Failed reading CSV headers: {e}"))?
-        .clone();
+        let reencode = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            muxer_format: Some("ts".to_string()),
+            ..ExportSettings::default()
+        };
+        let args = args_of(&segment_command(
+            &PATH_TOOLS,
+            Path::new("match.mp4"),
+            &pattern,
+            7.5,
+            &reencode,
+            "mp4",
+        ));
+        let keyframes = args.iter().position(|a| a == "-force_key_frames").unwrap();
+        assert_eq!(args[keyframes + 1], "expr:gte(t,n_forced*7.5)");
+        let format = args.iter().position(|a| a == "-segment_format").unwrap();
+        assert_eq!(args[format + 1], "mpegts");
+    }
 
-    let idx_name = find_header_index(&headers, &["clip name", "name", "clip"])
-        .ok_or_else(|| "CSV missing clip name column".to_string())?;
-    let idx_start = find_header_index(&headers, &["clip start time", "start time", "start", "in"])
-        .ok_or_else(|| "CSV missing clip start time column".to_string())?;
-    let idx_end = find_header_index(&headers, &["clip end time", "end time", "end", "out"])
-        .ok_or_else(|| "CSV missing clip end time column".to_string())?;
+    #[cfg(unix)]
+    #[test]
+    fn preserved_mtime_matches_the_source() {
+        let dir = scratch_dir("preserve-mtime");
+        // Backdate the source while "encoding" so the two times can't match by chance.
+        let body = format!(
+            "touch -t 200001021530 '{}'\n{WRITES_OUTPUT}",
+            dir.join("match.mp4").display()
+        );
+        let settings = ExportSettings {
+            preserve_mtime: true,
+            ..fake_tools(&dir, &body)
+        };
+        let (summary, _) = export_rows(
+            &dir,
+            settings,
+            vec![row("goal", "00:00:10", "00:00:20")],
+            false,
+        );
+        let summary = summary.unwrap();
+        assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
 
-    let mut rows = Vec::new();
-    for record in reader.records() {
-        let record = record.map_err(|e| format!("Failed reading CSV rows: {e}"))?;
-        let clip_name = record.get(idx_name).unwrap_or("clip").trim();
-        let start_time = record.get(idx_start).unwrap_or("").trim();
-        let end_time = record.get(idx_end).unwrap_or("").trim();
+        let modified = |path: &Path| std::fs::metadata(path).unwrap().modified().unwrap();
+        let output = dir.join("out").join("001-goal-000010.mp4");
+        assert_eq!(modified(&output), modified(&dir.join("match.mp4")));
+        assert!(
+            modified(&output) < std::time::SystemTime::now() - std::time::Duration::from_secs(3600)
+        );
+    }
 
-        if clip_name.is_empty() && start_time.is_empty() && end_time.is_empty() {
-            continue;
-        }
+    #[test]
+    fn subtitle_burn_picks_the_track_by_si() {
+        assert_eq!(
+            subtitle_filter(Path::new("match.mkv"), 2, 0.0),
+            "subtitles=filename='match.mkv':si=2"
+        );
+        assert_eq!(
+            subtitle_filter(Path::new("match.mkv"), 0, 12.5),
+            "setpts=PTS+12.5/TB,subtitles=filename='match.mkv':si=0,setpts=PTS-STARTPTS"
+        );
 
-        rows.push(ClipRow {
-            clip_name: if clip_name.is_empty() {
-                "clip".to_string()
-            } else {
-                clip_name.to_string()
-            },
-            start_time: start_time.to_string(),
-            end_time: end_time.to_string(),
-        });
+        let settings = ExportSettings {
+            processing_mode: "reencode_fast".to_string(),
+            burn_subtitle_track: Some(1),
+            ..ExportSettings::default()
+        };
+        let spec = clip_spec(&settings, Path::new("match.mkv"));
+        let args = args_of(&clip_command(&spec).cmd);
+        assert!(args
+            .iter()
+            .any(|a| a.contains("subtitles=filename='match.mkv':si=1")));
     }
 
-    Ok(rows)
-}
+    #[test]
+    fn replaygain_tags_keep_their_metadata_in_mp4() {
+        assert_eq!(
+            replaygain_args(-6.456, 0.9876543),
+            [
+                "-metadata",
+                "replaygain_track_gain=-6.46 dB",
+                "-metadata",
+                "replaygain_track_peak=0.987654",
+            ]
+        );
 
-fn find_header_index(headers: &StringRecord, aliases: &[&str]) -> Option<usize> {
-    let normalized_aliases = aliases
-        .iter()
-        .map(|alias| normalize_header(alias))
-        .collect::<Vec<_>>();
+        let settings = ExportSettings {
+            processing_mode: "reencode_fast".to_string(),
+            ..ExportSettings::default()
+        };
+        let mp4 = ClipSpec {
+            replaygain: Some((-3.0, 0.5)),
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        let args = args_of(&clip_command(&mp4).cmd);
+        assert!(args.iter().any(|a| a == "replaygain_track_gain=-3.00 dB"));
+        let flags = args.iter().position(|a| a == "-movflags").unwrap();
+        assert!(args[flags + 1].ends_with("+use_metadata_tags"));
 
-    let normalized = headers
-        .iter()
-        .enumerate()
-        .map(|(i, h)| (i, normalize_header(h)))
-        .collect::<Vec<_>>();
+        let mkv = ClipSpec {
+            container: "mkv",
+            row_ext: "mkv",
+            ..mp4
+        };
+        let args = args_of(&clip_command(&mkv).cmd);
+        assert!(args.iter().any(|a| a == "replaygain_track_peak=0.500000"));
+        assert!(!args.iter().any(|a| a == "-movflags"));
+    }
 
-    normalized
-        .into_iter()
-        .find(|(_, header)| normalized_aliases.iter().any(|alias| header == alias))
-        .map(|(i, _)| i)
-}
+    #[test]
+    fn end_hold_clones_the_last_frame_with_tpad() {
+        let scale = vec!["scale=1280:-2".to_string()];
+        assert_eq!(end_pad_filters(&scale, None), scale);
+        assert_eq!(
+            end_pad_filters(&scale, Some(1.5)),
+            ["scale=1280:-2", "tpad=stop_mode=clone:stop_duration=1.5"]
+        );
 
-fn normalize_settings(input: ExportSettings) -> ExportSettings {
-    let processing_mode = match input.processing_mode.as_str() {
-        "reencode_precise" | "copy_fast" | "reencode_fast_seek" => input.processing_mode,
-        _ => "copy_fast".to_string(),
-    };
-
-    let valid_presets = [
-        "ultrafast",
-        "superfast",
-        "veryfast",
-        "faster",
-        "fast",
-        "medium",
-    ];
+        let settings = ExportSettings {
+            processing_mode: "reencode_fast".to_string(),
+            ..ExportSettings::default()
+        };
+        // 15.5 s of source padded to 20 s, then held for another 2 s.
+        let spec = ClipSpec {
+            fixed_duration: Some(20.0),
+            end_hold: Some(2.0),
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        let clip = clip_command(&spec);
+        assert_eq!(clip.output_duration, 22.0);
+        let args = args_of(&clip.cmd);
+        let video = &args[args.iter().position(|a| a == "-vf").unwrap() + 1];
+        assert!(video.ends_with("tpad=stop_mode=clone:stop_duration=6.5"));
+        let audio = &args[args.iter().position(|a| a == "-af").unwrap() + 1];
+        assert!(audio.contains("apad"));
+    }
 
-    let preset = if valid_presets.contains(&input.preset.as_str()) {
-        input.preset
-    } else {
-        "ultrafast".to_string()
-    };
+    #[test]
+    fn iso8601_durations_convert_to_seconds() {
+        assert_eq!(convert_to_seconds("PT1M30S", 30.0), Some(90.0));
+        assert_eq!(convert_to_seconds("PT2H", 30.0), Some(7200.0));
+        assert_eq!(convert_to_seconds("pt1h0m2.5s", 30.0), Some(3602.5));
+        for invalid in ["PTX", "PT", "P1D", "PT30S1M", "PT1M1M", "PT-5S"] {
+            assert_eq!(convert_to_seconds(invalid, 30.0), None, "{invalid}");
+        }
+        assert_eq!(
+            invalid_time_hint("PTX"),
+            " (expected an ISO 8601 duration like PT1M30S)"
+        );
+    }
 
-    let resolution = match input.resolution.as_str() {
-        "source" | "1080p" | "720p" | "480p" => input.resolution,
-        _ => "source".to_string(),
-    };
+    #[test]
+    fn repeating_decimal_seeks_are_rounded() {
+        let settings = ExportSettings::default();
+        assert_eq!(time_arg(250.0 / 3.0, &settings), "83.333");
+        assert_eq!(input_seek_args(250.0 / 3.0, &settings), ["-ss", "83.333"]);
 
-    let audio_codec = match input.audio_codec.as_str() {
-        "aac" | "copy" | "none" => input.audio_codec,
-        _ => "aac".to_string(),
-    };
+        let frames = ExportSettings {
+            time_precision: 0,
+            ..ExportSettings::default()
+        };
+        assert_eq!(time_arg(83.6, &frames), "84");
 
-    let audio_bitrate_kbps = input.audio_bitrate_kbps.clamp(64, 320);
-    let crf = input.crf.clamp(16, 35);
+        let spec = ClipSpec {
+            start_sec: 250.0 / 3.0,
+            end_sec: 100.0,
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        let args = args_of(&clip_command(&spec).cmd);
+        let seek = args.iter().position(|a| a == "-ss").unwrap();
+        assert_eq!(args[seek + 1], "83.333");
+    }
 
-    let fps = match input.fps {
-        Some(value) if value.is_finite() && (1.0..=120.0).contains(&value) => Some(value),
-        _ => None,
-    };
+    #[cfg(unix)]
+    #[test]
+    fn strict_validation_aborts_on_one_invalid_row() {
+        let dir = scratch_dir("strict-validation");
+        let settings = ExportSettings {
+            strict_validation: true,
+            ..fake_tools(&dir, WRITES_OUTPUT)
+        };
+        let rows = vec![
+            row("goal", "00:00:10", "00:00:20"),
+            row("backwards", "00:00:40", "00:00:30"),
+            row("save", "00:01:00", "00:01:10"),
+        ];
+        let (result, _) = export_rows(&dir, settings, rows, false);
 
-    ExportSettings {
-        processing_mode,
-        preset,
-        crf,
-        resolution,
-        audio_codec,
-        audio_bitrate_kbps,
-        fps,
+        let err = result.err().expect("strict run should fail");
+        assert!(
+            err.starts_with("strict_validation: 1 problem(s) found, nothing was exported:\n"),
+            "{err}"
+        );
+        assert!(err.ends_with("Row 3 end time must be greater than start time"));
+        assert!(
+            !dir.join("ffmpeg.log").exists(),
+            "strict run started ffmpeg"
+        );
+        assert!(std::fs::read_dir(dir.join("out"))
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(true));
     }
-}
-
-fn resolution_filter(resolution: &str) -> Option<String> {
-    let (w, h) = match resolution {
-        "1080p" => (1920, 1080),
-        "720p" => (1280, 720),
-        "480p" => (854, 480),
-        _ => return None,
-    };
 
-    Some(format!(
-        "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2"
-    ))
-}
+    #[test]
+    fn thumbnail_time_stays_inside_the_clip() {
+        let frame = 1.0 / 25.0;
+        let at = |position: &str, offset: f64, end: f64| {
+            let settings = ExportSettings {
+                thumbnail_position: position.to_string(),
+                thumbnail_offset_sec: offset,
+                ..ExportSettings::default()
+            };
+            thumbnail_time(&settings, 10.0, end, frame)
+        };
+        assert_eq!(at("midpoint", 0.0, 20.0), 15.0);
+        assert_eq!(at("start", 0.0, 20.0), 10.0);
+        assert_eq!(at("offset", 2.5, 20.0), 12.5);
+        // An offset past the end lands on the last frame instead.
+        assert_eq!(at("offset", 30.0, 20.0), 20.0 - frame);
+        assert_eq!(at("offset", 30.0, 10.0), 10.0);
+    }
 
-fn normalize_header(input: &str) -> String {
-    input
-        .trim_start_matches('\u{feff}')
-        .trim()
-        .to_lowercase()
-        .replace(['_', '-'], " ")
-        .split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" ")
-}
+    #[cfg(unix)]
+    #[test]
+    fn thumbnail_is_extracted_next_to_the_clip() {
+        let dir = scratch_dir("thumbnail");
+        let settings = ExportSettings {
+            generate_thumbnail: true,
+            thumbnail_width: 320,
+            ..fake_tools(&dir, WRITES_OUTPUT)
+        };
+        let (summary, _) = export_rows(
+            &dir,
+            settings,
+            vec![row("goal", "00:00:10", "00:00:20")],
+            false,
+        );
+        let summary = summary.unwrap();
 
-fn convert_to_seconds(ts: &str) -> Option<f64> {
-    let ts = ts.trim();
-    if ts.is_empty() {
-        return None;
+        let image = dir.join("out").join("001-goal-000010.jpg");
+        assert_eq!(
+            summary.rows[0].thumbnail_path.as_deref(),
+            Some(image.to_string_lossy().as_ref())
+        );
+        let log = std::fs::read_to_string(dir.join("ffmpeg.log")).unwrap();
+        let call = log.lines().last().unwrap();
+        assert!(call.contains("-ss 15 "), "{call}");
+        assert!(call.contains("-frames:v 1 -vf scale=320:-2"), "{call}");
     }
 
-    let parts = ts.split(':').collect::<Vec<_>>();
-
-    let result = match parts.len() {
-        4 => {
-            let h = parts[0].parse::<f64>().ok()?;
-            let m = parts[1].parse::<f64>().ok()?;
-            let s = parts[2].parse::<f64>().ok()?;
-            let f = parts[3].parse::<f64>().ok()?;
-            (h * 3600.0) + (m * 60.0) + s + (f / 30.0)
-        }
-        3 => {
-            let h = parts[0].parse::<f64>().ok()?;
-            let m = parts[1].parse::<f64>().ok()?;
-            let s = parts[2].parse::<f64>().ok()?;
-            (h * 3600.0) + (m * 60.0) + s
+    #[test]
+    fn pool_collects_fast_jobs_before_a_slow_first_one() {
+        let mut pool = WorkerPool::new();
+        for (job, millis) in [("slow", 400), ("fast-1", 10), ("fast-2", 20)] {
+            let handle = std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(millis));
+                job.len()
+            });
+            pool.push(job, handle);
         }
-        2 => {
-            let m = parts[0].parse::<f64>().ok()?;
-            let s = parts[1].parse::<f64>().ok()?;
-            (m * 60.0) + s
+        assert!(pool.take_finished(false).is_none());
+
+        let mut order = Vec::new();
+        while let Some((job, result)) = pool.take_finished(true) {
+            assert_eq!(result.unwrap(), job.len());
+            order.push(job);
         }
-        1 => parts[0].parse::<f64>().ok()?,
-        _ => return None,
-    };
+        assert_eq!(order, ["fast-1", "fast-2", "slow"]);
+        assert_eq!(pool.len(), 0);
+    }
 
-    Some(result)
-}
+    #[cfg(unix)]
+    #[test]
+    fn clips_finished_after_a_stop_count_as_exported() {
+        let dir = scratch_dir("stop-finished");
+        let body = "for out; do :; done\ncase \"$out\" in *slow*) sleep 0.8 ;; *) sleep 0.2 ;; esac\nprintf x > \"$out\"";
+        let settings = ExportSettings {
+            max_parallel: 2,
+            ..fake_tools(&dir, body)
+        };
+        let video = dir.join("match.mp4");
+        std::fs::write(&video, b"").unwrap();
+        // Stop as soon as the quick clip is in, while the slow one is still running.
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_on_first = stop.clone();
+        let result = run_export(
+            Box::new(move |line| {
+                if line.payload.message.starts_with("Finished clip 1 of") {
+                    stop_on_first.store(true, Ordering::SeqCst);
+                }
+            }),
+            Arc::new(Mutex::new(Vec::new())),
+            stop,
+            None,
+            video.to_string_lossy().to_string(),
+            dir.join("out").to_string_lossy().to_string(),
+            settings,
+            Some(vec![
+                row("quick", "00:00:10", "00:00:20"),
+                row("slow", "00:00:30", "00:00:40"),
+                row("later", "00:00:50", "00:01:00"),
+            ]),
+            None,
+            false,
+            false,
+        );
+        let summary = result.unwrap();
 
-fn sanitize_filename(name: &str) -> String {
-    let cleaned = name
-        .chars()
-        .map(|c| match c {
-            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
-            ' ' => '-',
-            _ => '-',
-        })
-        .collect::<String>();
+        assert_eq!(
+            (summary.exported, summary.failed),
+            (2, 0),
+            "{:?}",
+            summary.errors
+        );
+        let statuses = summary
+            .rows
+            .iter()
+            .map(|r| (r.clip_name.as_str(), r.status.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(statuses, [("quick", "success"), ("slow", "success")]);
+        assert!(dir.join("out").join("002-slow-000030.mp4").exists());
+    }
 
-    let compact = cleaned
-        .split('-')
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<_>>()
-        .join("-");
+    #[cfg(unix)]
+    #[test]
+    fn social_preset_re_encodes_copy_rows() {
+        let dir = scratch_dir("social-copy");
+        let settings = normalize_settings(ExportSettings {
+            processing_mode: "copy_fast".to_string(),
+            social_preset: Some("reels".to_string()),
+            ..fake_tools(&dir, "exit 1")
+        });
+        let (summary, _) = export_rows(
+            &dir,
+            settings,
+            vec![row("goal", "00:00:10", "00:00:20")],
+            true,
+        );
+        let summary = summary.unwrap();
 
-    if compact.is_empty() {
-        "clip".to_string()
-    } else {
-        compact
+        let args = &summary.planned_commands[0].args;
+        assert!(!args.windows(2).any(|w| w[0] == "-c" && w[1] == "copy"));
+        let video = &args[args.iter().position(|a| a == "-vf").unwrap() + 1];
+        assert!(
+            video.contains("crop=") && video.contains("1080:1920"),
+            "{video}"
+        );
+        assert!(
+            !summary.warnings.iter().any(|w| w.contains("loudness")),
+            "{:?}",
+            summary.warnings
+        );
     }
-}
 
-fn main() {
-    tauri::Builder::default()
-        .manage(ProcessState::default())
-        .invoke_handler(tauri::generate_handler![
-            pick_csv_file,
-            pick_video_file,
-            pick_output_dir,
-            preview_csv,
-            start_export,
-            stop_export
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+    #[test]
+    fn exact_frames_seek_to_the_keyframe_and_count_source_frames() {
+        let settings = ExportSettings {
+            processing_mode: "reencode_precise".to_string(),
+            ..ExportSettings::default()
+        };
+        let info = VideoInfo {
+            fps: Some(50.0),
+            ..VideoInfo::default()
+        };
+        // 10.0–25.5 s of a 50 fps source, decoded from the keyframe at 9.6 s and
+        // written at 25 fps.
+        let spec = ClipSpec {
+            exact_fps: Some(25.0),
+            exact_seek: Some(9.6),
+            source_info: Some(&info),
+            ..clip_spec(&settings, Path::new("match.mp4"))
+        };
+        let args = args_of(&clip_command(&spec).cmd);
+        assert!(args
+            .windows(4)
+            .any(|w| w == ["-ss", "9.6", "-i", "match.mp4"]));
+        let filter = args.iter().position(|a| a == "-vf").unwrap();
+        assert_eq!(
+            args[filter + 1..filter + 6],
+            [
+                "select='between(n,20,794)',setpts=N/FRAME_RATE/TB",
+                "-frames:v",
+                "388",
+                "-r",
+                "25",
+            ]
+        );
+        assert!(args.contains(&"atrim=start=0.4:end=15.9,asetpts=PTS-STARTPTS".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn budget_charges_the_clamped_length() {
+        let dir = scratch_dir("budget-clamped");
+        let mut settings = fake_tools(&dir, "exit 1");
+        let ffprobe = dir.join("ffprobe");
+        std::fs::write(&ffprobe, "#!/bin/sh\necho 30.0\n").unwrap();
+        settings.ffprobe_path = Some(ffprobe.to_string_lossy().to_string());
+        let settings = ExportSettings {
+            clamp_to_source: true,
+            max_total_duration: Some(25.0),
+            ..settings
+        };
+        // The first row asks for 30 s but only 10 s of it exist.
+        let rows = vec![
+            row("late", "00:00:20", "00:00:50"),
+            row("early", "00:00:00", "00:00:10"),
+        ];
+        let summary = export_rows(&dir, settings, rows, true).0.unwrap();
+
+        assert_eq!(summary.budget_included, Some(2));
+        assert_eq!(summary.budget_used_sec, Some(20.0));
+        assert!(summary.rows.iter().all(|r| r.status == "planned"));
+    }
 }
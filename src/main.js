@@ -4,7 +4,9 @@ const { listen } = window.__TAURI__.event;
 const csvPathInput = document.getElementById("csvPath");
 const videoPathInput = document.getElementById("videoPath");
 const outputDirInput = document.getElementById("outputDir");
+const sourceDirInput = document.getElementById("sourceDir");
 const previewMeta = document.getElementById("previewMeta");
+const durationMeta = document.getElementById("durationMeta");
 const previewBody = document.querySelector("#previewTable tbody");
 const progressBar = document.getElementById("progressBar");
 const progressText = document.getElementById("progressText");
@@ -13,16 +15,20 @@ const csvHelpBtn = document.getElementById("csvHelpBtn");
 const csvHelpPanel = document.getElementById("csvHelpPanel");
 const addRowBtn = document.getElementById("addRowBtn");
 const removeRowBtn = document.getElementById("removeRowBtn");
+const copyStartsBtn = document.getElementById("copyStartsBtn");
 
 const pickCsvBtn = document.getElementById("pickCsvBtn");
 const pickVideoBtn = document.getElementById("pickVideoBtn");
 const pickOutputBtn = document.getElementById("pickOutputBtn");
+const pickSourceDirBtn = document.getElementById("pickSourceDirBtn");
 const startBtn = document.getElementById("startBtn");
 const stopBtn = document.getElementById("stopBtn");
 const processingModeInput = document.getElementById("processingMode");
 const modeHint = document.getElementById("modeHint");
 const resolutionInput = document.getElementById("resolution");
+const socialPresetInput = document.getElementById("socialPreset");
 const presetInput = document.getElementById("preset");
+const encoderInput = document.getElementById("encoder");
 const crfInput = document.getElementById("crf");
 const audioCodecInput = document.getElementById("audioCodec");
 const audioBitrateInput = document.getElementById("audioBitrate");
@@ -41,13 +47,17 @@ function updateAudioBitrateState() {
 function updateModeControlState() {
   const mode = processingModeInput.value;
   resolutionInput.disabled = running;
+  socialPresetInput.disabled = running;
   presetInput.disabled = running;
+  encoderInput.disabled = running;
   crfInput.disabled = running;
   fpsInput.disabled = running;
   audioCodecInput.disabled = running;
 
   if (mode === "copy_fast") {
     modeHint.textContent = "Copy Streams mode is fastest and keeps source resolution and container/extension. Re-encode controls (resolution, preset, CRF, FPS, audio re-encode options) are ignored in this mode.";
+  } else if (mode === "webp") {
+    modeHint.textContent = "Animated WebP mode writes small looping .webp previews (no audio). Resolution, preset, CRF, and audio settings are ignored in this mode.";
   } else if (mode === "reencode_fast_seek") {
     modeHint.textContent = "Fast Seek mode re-encodes and applies your quality/resolution/audio settings, usually faster than precise mode.";
   } else {
@@ -70,12 +80,15 @@ function setRunning(value) {
   pickCsvBtn.disabled = value;
   pickVideoBtn.disabled = value;
   pickOutputBtn.disabled = value;
+  pickSourceDirBtn.disabled = value;
+  sourceDirInput.disabled = value;
   csvHelpBtn.disabled = value;
   processingModeInput.disabled = value;
   for (const input of previewBody.querySelectorAll(".cell-input")) {
     input.disabled = value;
   }
   addRowBtn.disabled = value;
+  copyStartsBtn.disabled = value;
   removeRowBtn.disabled = value || editableRows.length === 0;
   updateModeControlState();
 }
@@ -108,6 +121,7 @@ function renderPreview(rows) {
       <td><input class="cell-input" data-row="${i}" data-field="clip_name" value="${escapeHtml(row.clip_name)}" /></td>
       <td><input class="cell-input" data-row="${i}" data-field="start_time" value="${escapeHtml(row.start_time)}" /></td>
       <td><input class="cell-input" data-row="${i}" data-field="end_time" value="${escapeHtml(row.end_time)}" /></td>
+      <td class="copy-start">${escapeHtml(row._copy_start || "")}</td>
       <td><button class="row-remove-btn" type="button" data-action="remove-row" data-row="${i}" ${running ? "disabled" : ""}>-</button></td>
     `;
     previewBody.appendChild(tr);
@@ -247,7 +261,10 @@ function readSettings() {
   return {
     processing_mode: processingModeInput.value,
     resolution: resolutionInput.value,
+    social_preset: socialPresetInput.value || null,
+    source_dir: sourceDirInput.value.trim() || null,
     preset: presetInput.value,
+    encoder: encoderInput.value,
     crf,
     audio_codec: audioCodecInput.value,
     audio_bitrate_kbps,
@@ -259,7 +276,8 @@ function getEditedRowsForExport() {
   return editableRows.map((row) => ({
     clip_name: (row.clip_name || "").trim(),
     start_time: (row.start_time || "").trim(),
-    end_time: (row.end_time || "").trim()
+    end_time: (row.end_time || "").trim(),
+    overrides: row.overrides || {}
   }));
 }
 
@@ -278,24 +296,48 @@ function setRowStatus(index, status) {
   renderPreview(editableRows);
 }
 
+async function updateDurationTotal() {
+  if (editableRows.length === 0) {
+    durationMeta.textContent = "";
+    return;
+  }
+  try {
+    const summary = await invoke("total_duration", { rows: getEditedRowsForExport() });
+    const invalidNote = summary.invalid_clips > 0 ? ` (${summary.invalid_clips} invalid row(s) not counted)` : "";
+    durationMeta.textContent = `Selected ${summary.formatted} of footage across ${summary.valid_clips} clips${invalidNote}.`;
+  } catch (error) {
+    durationMeta.textContent = "";
+  }
+}
+
 async function loadCsvPreview(csvPath) {
   try {
-    const preview = await invoke("preview_csv", { csvPath });
+    const preview = await invoke("preview_csv", {
+      csvPath,
+      videoPath: videoPathInput.value || null,
+      settings: readSettings()
+    });
     editableRows = (Array.isArray(preview.rows) ? preview.rows : []).map((row) => ({
       ...row,
       _status: "pending"
     }));
     selectedRowIndex = editableRows.length > 0 ? 0 : -1;
     renderPreview(editableRows);
+    updateDurationTotal();
 
     const errorCount = preview.validation_errors.length;
-    previewMeta.textContent = `${preview.total_rows} rows loaded. Editable table ready. Validation issues: ${errorCount}.`;
+    const offsetNote = preview.timecode_offset_sec > 0 ? ` Timecode offset: ${preview.timecode_offset_sec}s.` : "";
+    const lengthNote = preview.source_duration_sec != null ? ` Video length: ${Math.round(preview.source_duration_sec)}s.` : "";
+    previewMeta.textContent = `${preview.total_rows} rows loaded. Editable table ready. Validation issues: ${errorCount}.${offsetNote}${lengthNote}`;
 
     if (errorCount > 0) {
       appendLog(`CSV validation: ${errorCount} issue(s). First: ${preview.validation_errors[0]}`);
     } else {
       appendLog(`CSV validation passed for ${preview.total_rows} rows.`);
     }
+    for (const warning of preview.validation_warnings || []) {
+      appendLog(`CSV warning: ${warning}`);
+    }
   } catch (error) {
     previewMeta.textContent = "Failed to preview CSV";
     previewBody.innerHTML = "";
@@ -324,6 +366,16 @@ pickVideoBtn.addEventListener("click", async () => {
   appendLog(`Selected video: ${path}`);
 });
 
+pickSourceDirBtn.addEventListener("click", async () => {
+  const path = await invoke("pick_output_dir");
+  if (!path) {
+    return;
+  }
+
+  sourceDirInput.value = path;
+  appendLog(`Selected source folder: ${path}`);
+});
+
 pickOutputBtn.addEventListener("click", async () => {
   const path = await invoke("pick_output_dir");
   if (!path) {
@@ -361,6 +413,13 @@ startBtn.addEventListener("click", async () => {
     );
     const summary = await invoke("start_export", { csvPath, videoPath, outputDir, settings, editedRows });
     appendLog(`Completed. Exported ${summary.exported}, skipped ${summary.skipped}, failed ${summary.failed}.`);
+    appendLog(`Run ID: ${summary.run_id}`);
+    if (summary.merged > 0) {
+      appendLog(`Merged ${summary.merged} tiny adjacent clip(s) before export.`);
+    }
+    if (summary.budget_included !== null && summary.budget_included !== undefined) {
+      appendLog(`Duration budget: ${summary.budget_included} clip(s), ${summary.budget_used_sec.toFixed(1)}s used.`);
+    }
     if (summary.errors.length > 0) {
       appendLog(`First error: ${summary.errors[0]}`);
     }
@@ -396,6 +455,9 @@ previewBody.addEventListener("input", (event) => {
   }
 
   editableRows[rowIndex][field] = target.value;
+  if (field !== "clip_name") {
+    updateDurationTotal();
+  }
 });
 
 previewBody.addEventListener("click", (event) => {
@@ -410,6 +472,7 @@ previewBody.addEventListener("click", (event) => {
         selectedRowIndex = Math.min(idx, editableRows.length - 1);
       }
       renderPreview(editableRows);
+      updateDurationTotal();
     }
     return;
   }
@@ -449,6 +512,36 @@ csvHelpBtn.addEventListener("click", () => {
   csvHelpPanel.hidden = !csvHelpPanel.hidden;
 });
 
+copyStartsBtn.addEventListener("click", async () => {
+  const videoPath = videoPathInput.value;
+  if (!videoPath || editableRows.length === 0) {
+    appendLog("Select a source video and load rows before checking copy drift.");
+    return;
+  }
+
+  try {
+    const results = await invoke("preview_copy_starts", {
+      videoPath,
+      rows: getEditedRowsForExport(),
+      settings: readSettings()
+    });
+    for (const result of results) {
+      const row = editableRows[result.row_index];
+      if (!row) {
+        continue;
+      }
+      row._copy_start =
+        result.actual_start === null
+          ? result.error || ""
+          : `${result.actual_start.toFixed(3)}s (-${result.delta_sec.toFixed(3)}s)`;
+    }
+    renderPreview(editableRows);
+    appendLog("Copy-mode start times updated.");
+  } catch (error) {
+    appendLog(`Copy drift check failed: ${error}`);
+  }
+});
+
 addRowBtn.addEventListener("click", () => {
   editableRows.push({
     clip_name: "New Clip",
@@ -458,6 +551,7 @@ addRowBtn.addEventListener("click", () => {
   });
   selectedRowIndex = editableRows.length - 1;
   renderPreview(editableRows);
+  updateDurationTotal();
   removeRowBtn.disabled = running || editableRows.length === 0;
 });
 
@@ -474,6 +568,7 @@ removeRowBtn.addEventListener("click", () => {
     selectedRowIndex = Math.min(idx, editableRows.length - 1);
   }
   renderPreview(editableRows);
+  updateDurationTotal();
   removeRowBtn.disabled = running || editableRows.length === 0;
 });
 
@@ -492,7 +587,11 @@ async function initProgressListener() {
         return;
       }
 
-      const percentage = Math.round((payload.completed / payload.total) * 100);
+      const partial = typeof payload.clip_progress === "number" ? payload.clip_progress : 0;
+      const percentage =
+        typeof payload.weighted_percent === "number"
+          ? Math.round(payload.weighted_percent)
+          : Math.round(((payload.completed + partial) / payload.total) * 100);
       progressBar.value = Math.min(100, Math.max(0, percentage));
       progressText.textContent = `${payload.message} (${payload.completed}/${payload.total})`;
 
@@ -526,7 +625,75 @@ async function initProgressListener() {
   }
 }
 
+async function initDefaultOutputDir() {
+  try {
+    const dir = await invoke("default_output_dir");
+    if (dir && !outputDirInput.value) {
+      outputDirInput.value = dir;
+      appendLog(`Using default output dir from CLIPCHOP_OUTPUT_DIR: ${dir}`);
+    }
+  } catch (error) {
+    appendLog(`Default output dir unavailable: ${error}`);
+  }
+}
+
+async function initDependencyCheck() {
+  try {
+    const status = await invoke("check_dependencies");
+    for (const [name, tool] of [["ffmpeg", status.ffmpeg], ["ffprobe", status.ffprobe]]) {
+      if (tool.found) {
+        appendLog(`${name} found: ${tool.version || tool.path}`);
+      } else {
+        appendLog(`${name} not found on PATH. Exports need ffmpeg installed.`);
+      }
+    }
+    if (status.install_hint) {
+      appendLog(`Install suggestion: ${status.install_hint}`);
+    }
+  } catch (error) {
+    appendLog(`Dependency check failed: ${error}`);
+  }
+}
+
+async function initOrphanCheck() {
+  try {
+    const orphans = await invoke("cleanup_orphans", { listOnly: true });
+    if (orphans.found.length === 0) {
+      return;
+    }
+    appendLog(`${orphans.found.length} ffmpeg process(es) from a previous session are still running.`);
+    if (!window.confirm(`Stop ${orphans.found.length} leftover ffmpeg process(es) from a previous ClipChop session?`)) {
+      return;
+    }
+    const result = await invoke("cleanup_orphans", { listOnly: false });
+    appendLog(`Stopped ${result.killed.length} leftover ffmpeg process(es).`);
+    for (const error of result.errors) {
+      appendLog(error);
+    }
+  } catch (error) {
+    appendLog(`Leftover ffmpeg check failed: ${error}`);
+  }
+}
+
+async function initEncoderOptions() {
+  try {
+    const encoders = await invoke("detect_encoders");
+    for (const encoder of encoders) {
+      const option = encoderInput.querySelector(`option[value="${encoder.name}"]`);
+      if (option) {
+        option.disabled = !encoder.available;
+      }
+    }
+  } catch (error) {
+    appendLog(`Encoder check failed: ${error}`);
+  }
+}
+
 initProgressListener();
+initDependencyCheck();
+initEncoderOptions();
+initOrphanCheck();
+initDefaultOutputDir();
 updateModeControlState();
 removeRowBtn.disabled = true;
 